@@ -5,13 +5,176 @@ use std::convert::Infallible;
 // SPDX-License-Identifier: MIT OR Apache-2.0
 use std::fmt::Display;
 use std::future::Future;
+use std::panic::Location;
 use std::pin::Pin;
+use std::sync::Arc;
 use some_executor::{DynExecutor, SomeExecutor, SomeExecutorExt};
-use some_executor::observer::{Observer, ObserverNotified};
-use some_executor::task::Task;
+use some_executor::observer::{Observation, Observer, ObserverNotified, TypedObserver};
+use some_executor::task::{Configuration, Task, TaskID};
+use crate::hooks::ExecutorHooks;
+use crate::sys::time::Instant;
+
+/**
+The task accepted by `SomeExecutor::spawn_objsafe`.
+*/
+type ObjsafeTask = Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>;
+
+/**
+How one of the runtimes in this module runs a task it has spawned.  The runtimes spawn through [start] and
+[start_objsafe], which call this.
+*/
+trait RunSpawned: SomeExecutor<ExecutorNotifier = Infallible> + Clone + 'static {
+    /**
+    Runs `task`, labeled `label`, without polling it before `poll_after`.  `spawned_at` is where it was spawned, for
+    [crate::debug::running_tasks].
+    */
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F);
+}
+
+/**
+Spawns `task` onto `runtime`, as spawned from `spawned_at`.
+*/
+fn start<R, F, Notifier>(runtime: &mut R, task: Task<F, Notifier>, spawned_at: &'static Location<'static>) -> TypedObserver<F::Output, Infallible>
+where
+    R: RunSpawned,
+    F: Future + Send + 'static,
+    Notifier: ObserverNotified<F::Output> + Send,
+    F::Output: Send,
+{
+    let (spawned, observer) = task.spawn(runtime);
+    let label = spawned.label().to_string();
+    let poll_after = spawned.poll_after();
+    runtime.run_spawned(&label, poll_after, spawned_at, crate::logging::instrument!(spawned, &label));
+    observer
+}
+
+std::thread_local! {
+    /**
+    The task that [Current::spawn_detached] is spawning on this thread, whose observer [start_objsafe] detaches.
+    */
+    static DETACHING: std::cell::Cell<Option<TaskID>> = const { std::cell::Cell::new(None) };
+}
+
+/**
+Stands in for the observer of a task that [start_objsafe] detached.
+*/
+struct DetachedObserver(TaskID);
+
+impl Observer for DetachedObserver {
+    type Value = Box<dyn Any + Send>;
+
+    fn observe(&self) -> Observation<Self::Value> {
+        Observation::Done
+    }
+
+    fn task_id(&self) -> &TaskID {
+        &self.0
+    }
+}
+
+/**
+Like [start], for `SomeExecutor::spawn_objsafe`.
+*/
+fn start_objsafe<R: RunSpawned>(runtime: &mut R, task: ObjsafeTask, spawned_at: &'static Location<'static>) -> Box<dyn Observer<Value = Box<dyn Any + Send>>> {
+    crate::logging::info!("spawned future: {label}", label=task.label());
+    let task_id = task.task_id();
+    let detach = DETACHING.with(|detaching| detaching.get() == Some(task_id));
+    if detach {
+        DETACHING.with(|detaching| detaching.set(None));
+    }
+    let (spawned, observer) = task.spawn_objsafe(runtime);
+    let label = spawned.label().to_string();
+    let poll_after = spawned.poll_after();
+    let observer: Box<dyn Observer<Value = Box<dyn Any + Send>>> = if detach {
+        observer.detach();
+        Box::new(DetachedObserver(task_id))
+    } else {
+        Box::new(observer)
+    };
+    runtime.run_spawned(&label, poll_after, spawned_at, crate::logging::instrument!(spawned, &label));
+    observer
+}
+
+/**
+Spawns each of `tasks` onto `runtime`, logging once for the whole batch.
+*/
+fn spawn_all<R, F, Notifier>(runtime: &mut R, tasks: impl IntoIterator<Item = Task<F, Notifier>>, spawned_at: &'static Location<'static>) -> Vec<TypedObserver<F::Output, Infallible>>
+where
+    R: RunSpawned,
+    F: Future + Send + 'static,
+    Notifier: ObserverNotified<F::Output> + Send,
+    F::Output: Send,
+{
+    let tasks: Vec<_> = tasks.into_iter().collect();
+    crate::logging::info!("spawned {count} futures", count=tasks.len());
+    tasks.into_iter().map(|task| start(runtime, task, spawned_at)).collect()
+}
+
+/**
+Implements [SomeExecutor] for a [RunSpawned] runtime, spawning through [start] and [start_objsafe].
+*/
+macro_rules! spawn_through_start {
+    ([$($generics:tt)*] $runtime:ty) => {
+        impl<$($generics)*> SomeExecutorExt for $runtime {}
+
+        impl<$($generics)*> SomeExecutor for $runtime {
+            type ExecutorNotifier = Infallible;
+
+            #[track_caller]
+            fn spawn<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&mut self, task: Task<F, Notifier>) -> impl Observer<Value=F::Output>
+            where
+                Self: Sized,
+                F::Output: Send,
+            {
+                crate::logging::info!("spawned future: {label}", label=task.label());
+                start(self, task, Location::caller())
+            }
+
+            #[track_caller]
+            fn spawn_async<'s, F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&'s mut self, task: Task<F, Notifier>) -> impl Future<Output=impl Observer<Value=F::Output>> + Send + 's
+            where
+                Self: Sized,
+                F::Output: Send + Unpin,
+            {
+                let spawned_at = Location::caller();
+                async move {
+                    crate::logging::info!("spawned future: {label}", label=task.label());
+                    start(self, task, spawned_at)
+                }
+            }
+
+            #[track_caller]
+            fn spawn_objsafe(&mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Observer<Value = Box<dyn Any + Send>>> {
+                start_objsafe(self, task, Location::caller())
+            }
+
+            #[track_caller]
+            fn spawn_objsafe_async<'s>(&'s mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Future<Output=Box<dyn Observer<Value=Box<dyn Any + Send>>>> + 's> {
+                let spawned_at = Location::caller();
+                Box::new(async move {
+                    start_objsafe(self, task, spawned_at)
+                })
+            }
+
+            fn clone_box(&self) -> Box<DynExecutor> {
+                Box::new(self.clone())
+            }
+
+            fn executor_notifier(&mut self) -> Option<Self::ExecutorNotifier> {
+                None
+            }
+        }
+    };
+    ($runtime:ty) => {
+        spawn_through_start!([] $runtime);
+    };
+}
 
 /**
 A runtime based on [crate::spin_on]
+
+Spawning a task spins it to completion before returning.  [BackgroundSpinRuntime] instead spins each task on a
+thread of its own, so that spawn returns at once.
 */
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SpinRuntime;
@@ -20,70 +183,85 @@ impl SpinRuntime {
     pub const fn new() -> Self {
         Self
     }
-}
 
-impl SomeExecutorExt for SpinRuntime {}
-impl SomeExecutor for SpinRuntime {
-    type ExecutorNotifier = Infallible;
-
-    fn spawn<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output>>(&mut self, task: Task<F, Notifier>) -> impl Observer<Value=F::Output>
+    /**
+    Spawns each of `tasks`, logging once for the whole batch.
+    */
+    #[track_caller]
+    pub fn spawn_all<F, Notifier>(&mut self, tasks: impl IntoIterator<Item = Task<F, Notifier>>) -> Vec<TypedObserver<F::Output, Infallible>>
     where
-        Self: Sized,
+        F: Future + Send + 'static,
+        Notifier: ObserverNotified<F::Output> + Send,
+        F::Output: Send,
     {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
-        while task.poll_after() > crate::sys::time::Instant::now() {
-            std::hint::spin_loop()
-        }
-        let (spawned, observer) = task.spawn(self);
-        crate::spin_on(spawned);
-        observer
+        spawn_all(self, tasks, Location::caller())
     }
 
+    /**
+    Reports the polls, wakes and completion of each task spawned through the runtime to `hooks`.  See
+    [crate::hooks].
+    */
+    pub fn with_hooks(self, hooks: Arc<dyn ExecutorHooks>) -> HookedRuntime<Self> {
+        HookedRuntime { runtime: self, hooks }
+    }
+}
 
-    fn spawn_async<'s, F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&'s mut self, task: Task<F, Notifier>) -> impl Future<Output=impl Observer<Value=F::Output>> + Send + 's
-    where
-        Self: Sized,
-        F::Output: Send + Unpin,
-    {
-        async move {
-            logwise::info_sync!("spawned future: {label}", label=task.label());
-            let (spawned, observer) = task.spawn(self);
-            while spawned.poll_after() > crate::sys::time::Instant::now() {
-                std::hint::spin_loop()
-            }
-            crate::spin_on(spawned);
-            observer
-        }
+impl RunSpawned for SpinRuntime {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F) {
+        let task = crate::debug::Registration::new(label, spawned_at).track(task);
+        crate::clock::spin_until(poll_after);
+        crate::spin_on_nested(task, spawned_at);
     }
+}
 
+spawn_through_start!(SpinRuntime);
 
-    fn spawn_objsafe(&mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<(dyn Observer<Value = Box<(dyn Any + Send + 'static)>> + 'static)> {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
+/**
+A runtime that spins each task with [crate::spin_on] on a new thread, returning from spawn without waiting for the
+task.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BackgroundSpinRuntime;
 
-        let (spawned, observer) = task.spawn_objsafe(self);
-        while spawned.poll_after() > crate::sys::time::Instant::now() {
-            std::hint::spin_loop()
-        }
-        crate::spin_on(spawned);
-        Box::new(observer)
+impl BackgroundSpinRuntime {
+    pub const fn new() -> Self {
+        Self
     }
 
-    fn spawn_objsafe_async<'s>(&'s mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Future<Output=Box<dyn Observer<Value=Box<dyn Any + Send>>>> + 's> {
-        Box::new(async {
-            Self::spawn_objsafe(self, task)
-        })
+    /**
+    Spawns each of `tasks`, logging once for the whole batch.
+    */
+    #[track_caller]
+    pub fn spawn_all<F, Notifier>(&mut self, tasks: impl IntoIterator<Item = Task<F, Notifier>>) -> Vec<TypedObserver<F::Output, Infallible>>
+    where
+        F: Future + Send + 'static,
+        Notifier: ObserverNotified<F::Output> + Send,
+        F::Output: Send,
+    {
+        spawn_all(self, tasks, Location::caller())
     }
 
-
-    fn clone_box(&self) -> Box<DynExecutor> {
-        Box::new(*self)
+    /**
+    Reports the polls, wakes and completion of each task spawned through the runtime to `hooks`.  See
+    [crate::hooks].
+    */
+    pub fn with_hooks(self, hooks: Arc<dyn ExecutorHooks>) -> HookedRuntime<Self> {
+        HookedRuntime { runtime: self, hooks }
     }
+}
 
-    fn executor_notifier(&mut self) -> Option<Self::ExecutorNotifier> {
-        None
+impl RunSpawned for BackgroundSpinRuntime {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F) {
+        let task = crate::debug::Registration::new(label, spawned_at).track(task);
+        crate::sys::thread::spawn(move || {
+            crate::clock::spin_until(poll_after);
+            crate::spin_on(task);
+        });
     }
 }
 
+spawn_through_start!(BackgroundSpinRuntime);
+
 /**
 A runtime based on [crate::sleep_on]
 */
@@ -93,68 +271,297 @@ impl SleepRuntime {
     pub const fn new() -> Self {
         Self
     }
+
+    /**
+    Spawns each of `tasks`, logging once for the whole batch.
+    */
+    #[track_caller]
+    pub fn spawn_all<F, Notifier>(&mut self, tasks: impl IntoIterator<Item = Task<F, Notifier>>) -> Vec<TypedObserver<F::Output, Infallible>>
+    where
+        F: Future + Send + 'static,
+        Notifier: ObserverNotified<F::Output> + Send,
+        F::Output: Send,
+    {
+        spawn_all(self, tasks, Location::caller())
+    }
+
+    /**
+    Reports the polls, wakes and completion of each task spawned through the runtime to `hooks`.  See
+    [crate::hooks].
+    */
+    pub fn with_hooks(self, hooks: Arc<dyn ExecutorHooks>) -> HookedRuntime<Self> {
+        HookedRuntime { runtime: self, hooks }
+    }
 }
-impl SomeExecutorExt for SleepRuntime {
 
+impl RunSpawned for SleepRuntime {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F) {
+        let task = crate::debug::Registration::new(label, spawned_at).track(task);
+        crate::clock::sleep_until(poll_after);
+        crate::sleep_on_nested(task, spawned_at);
+    }
 }
 
-impl SomeExecutor for SleepRuntime {
-    type ExecutorNotifier = Infallible;
+spawn_through_start!(SleepRuntime);
+
+
+/**
+A runtime based on [crate::spawn_on]
+
+Each task runs on a thread named after its label, so debugger thread lists and the default panic message
+(`thread 'fetch user 3' panicked at ...`) say which task is which.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpawnRuntime;
+
+impl SpawnRuntime {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /**
+    A runtime that also counts its running tasks, so that [TrackedSpawnRuntime::wait_idle] can wait for them.
+    */
+    pub fn tracked() -> TrackedSpawnRuntime {
+        TrackedSpawnRuntime::new()
+    }
 
-    fn spawn<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output>>(&mut self, task: Task<F, Notifier>) -> impl Observer<Value=F::Output>
+    /**
+    Spawns each of `tasks`, logging once for the whole batch.
+    */
+    #[track_caller]
+    pub fn spawn_all<F, Notifier>(&mut self, tasks: impl IntoIterator<Item = Task<F, Notifier>>) -> Vec<TypedObserver<F::Output, Infallible>>
     where
-        Self: Sized,
+        F: Future + Send + 'static,
+        Notifier: ObserverNotified<F::Output> + Send,
         F::Output: Send,
     {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
-        let (spawned, observer) = task.spawn(self);
-        let now = crate::sys::time::Instant::now();
-        if spawned.poll_after() > now {
-            let dur = now.duration_since(spawned.poll_after());
-            std::thread::sleep(dur);
+        spawn_all(self, tasks, Location::caller())
+    }
+
+    /**
+    Reports the polls, wakes and completion of each task spawned through the runtime to `hooks`.  See
+    [crate::hooks].
+    */
+    pub fn with_hooks(self, hooks: Arc<dyn ExecutorHooks>) -> HookedRuntime<Self> {
+        HookedRuntime { runtime: self, hooks }
+    }
+}
+
+impl RunSpawned for SpawnRuntime {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F) {
+        let task = crate::debug::Registration::new(label, spawned_at).track(task);
+        spawn_named(label.to_string(), move || {
+            crate::clock::sleep_until(poll_after);
+            crate::sleep_on(task);
+        });
+    }
+}
+
+spawn_through_start!(SpawnRuntime);
+
+/**
+A [SpawnRuntime] that counts its running tasks.
+
+Clones of a runtime, including those its tasks spawn siblings onto, count their running tasks together, so that
+[TrackedSpawnRuntime::wait_idle] can wait for fire-and-forget work to finish instead of guessing with a sleep:
+
+```
+use std::time::Duration;
+use some_executor::task::Task;
+
+let mut runtime = test_executors::aruntime::SpawnRuntime::tracked();
+let task = Task::<_, std::convert::Infallible>::without_notifications("background".to_string(), async {}, Default::default());
+for observer in runtime.spawn_all([task]) {
+    observer.detach();
+}
+runtime.wait_idle(Duration::from_secs(5)).unwrap();
+```
+*/
+#[derive(Debug, Clone)]
+pub struct TrackedSpawnRuntime {
+    running: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl TrackedSpawnRuntime {
+    /**
+    A runtime with no tasks running yet, whose clones share its count.
+    */
+    pub fn new() -> Self {
+        TrackedSpawnRuntime { running: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)) }
+    }
+
+    /**
+    Blocks until every task spawned through this runtime or its clones has finished, or until `timeout` elapses.
+    */
+    pub fn wait_idle(&self, timeout: std::time::Duration) -> Result<(), crate::error::IdleTimeout> {
+        let start = crate::sys::time::Instant::now();
+        loop {
+            let running = self.running.load(std::sync::atomic::Ordering::Acquire);
+            if running == 0 {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(crate::error::IdleTimeout::new(timeout, running));
+            }
+            crate::sys::sleep(std::time::Duration::from_millis(1));
         }
-        crate::sleep_on(spawned);
-        observer
     }
 
+    /**
+    Spawns each of `tasks`, logging once for the whole batch.
+    */
+    #[track_caller]
+    pub fn spawn_all<F, Notifier>(&mut self, tasks: impl IntoIterator<Item = Task<F, Notifier>>) -> Vec<TypedObserver<F::Output, Infallible>>
+    where
+        F: Future + Send + 'static,
+        Notifier: ObserverNotified<F::Output> + Send,
+        F::Output: Send,
+    {
+        spawn_all(self, tasks, Location::caller())
+    }
+
+    /**
+    Reports the polls, wakes and completion of each task spawned through the runtime to `hooks`.  See
+    [crate::hooks].
+    */
+    pub fn with_hooks(self, hooks: Arc<dyn ExecutorHooks>) -> HookedRuntime<Self> {
+        HookedRuntime { runtime: self, hooks }
+    }
+}
+
+impl RunSpawned for TrackedSpawnRuntime {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F) {
+        struct Finished(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+        impl Drop for Finished {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, std::sync::atomic::Ordering::Release);
+            }
+        }
+        self.running.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+        let finished = Finished(self.running.clone());
+        //the task counts as running until it is dropped, whether it finished or not
+        SpawnRuntime.run_spawned(label, poll_after, spawned_at, async move {
+            let _finished = finished;
+            task.await
+        });
+    }
+}
+
+spawn_through_start!(TrackedSpawnRuntime);
+
+/**
+Runs `run` on a new thread named after the task `label`.
+*/
+fn spawn_named(label: String, run: impl FnOnce() + Send + 'static) {
+    //thread names cannot contain NUL
+    crate::sys::thread::Builder::new()
+        .name(label.replace('\0', " "))
+        .spawn(run)
+        .expect("Cant spawn thread");
+}
+
+/**
+A shareable handle to a runtime, for spawning through `&self`.
+
+The [SomeExecutor] methods take `&mut self`, so a runtime cannot be shared between test helpers directly.  A
+handle is [Clone], [Send] and [Sync], and every clone spawns onto the same runtime, which it keeps behind a lock.
+
+The lock is held while the runtime spawns, so a task that spawns onto the same handle from inside a spawn that
+runs it to completion, as [SpinRuntime] and [SleepRuntime] do, deadlocks.  [SpawnRuntime] and
+[BackgroundSpinRuntime] return from spawn at once.
+*/
+#[derive(Debug, Default)]
+pub struct SpawnRuntimeHandle<R = SpawnRuntime>(std::sync::Arc<std::sync::Mutex<R>>);
+
+impl<R> SpawnRuntimeHandle<R> {
+    pub fn new(runtime: R) -> Self {
+        SpawnRuntimeHandle(std::sync::Arc::new(std::sync::Mutex::new(runtime)))
+    }
+}
+
+impl<R: SomeExecutor> SpawnRuntimeHandle<R> {
+    /**
+    Spawns `task` onto the runtime.
+    */
+    #[track_caller]
+    pub fn spawn<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&self, task: Task<F, Notifier>) -> CurrentObserver<F::Output>
+    where
+        F::Output: Send + Unpin,
+    {
+        CurrentObserver(self.spawn_objsafe(task.into_objsafe()), std::marker::PhantomData)
+    }
+
+    /**
+    Spawns a type-erased `task` onto the runtime.
+    */
+    #[track_caller]
+    pub fn spawn_objsafe(&self, task: ObjsafeTask) -> Box<dyn Observer<Value = Box<dyn Any + Send>>> {
+        //a panic while spawning leaves the runtime as usable as it was
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).spawn_objsafe(task)
+    }
+
+    /**
+    Spawns each of `tasks` onto the runtime, taking the lock once for the whole batch.
+    */
+    #[track_caller]
+    pub fn spawn_all<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&self, tasks: impl IntoIterator<Item = Task<F, Notifier>>) -> Vec<CurrentObserver<F::Output>>
+    where
+        F::Output: Send + Unpin,
+    {
+        let mut runtime = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        //a loop rather than map, so that the tasks are reported as spawned by our caller
+        let mut observers = Vec::new();
+        for task in tasks {
+            observers.push(CurrentObserver(runtime.spawn_objsafe(task.into_objsafe()), std::marker::PhantomData));
+        }
+        observers
+    }
+}
+
+impl<R> Clone for SpawnRuntimeHandle<R> {
+    fn clone(&self) -> Self {
+        SpawnRuntimeHandle(self.0.clone())
+    }
+}
+
+impl<R: SomeExecutor + Send + 'static> SomeExecutorExt for SpawnRuntimeHandle<R> {}
+
+impl<R: SomeExecutor + Send + 'static> SomeExecutor for SpawnRuntimeHandle<R> {
+    type ExecutorNotifier = Infallible;
+
+    fn spawn<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&mut self, task: Task<F, Notifier>) -> impl Observer<Value=F::Output>
+    where
+        Self: Sized,
+        F::Output: Send + Unpin,
+    {
+        SpawnRuntimeHandle::spawn(self, task)
+    }
+
+    //spelled out as in the trait, which an `async fn` would not
+    #[allow(clippy::manual_async_fn)]
     fn spawn_async<'s, F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&'s mut self, task: Task<F, Notifier>) -> impl Future<Output=impl Observer<Value=F::Output>> + Send + 's
     where
         Self: Sized,
         F::Output: Send + Unpin,
     {
         async move {
-            logwise::info_sync!("spawned future: {label}", label=task.label());
-            let (spawned, observer) = task.spawn(self);
-            let now = crate::sys::time::Instant::now();
-            if spawned.poll_after() > now {
-                let dur = spawned.poll_after() - now;
-                std::thread::sleep(dur);
-            }
-            crate::sleep_on(spawned);
-            observer
+            SpawnRuntimeHandle::spawn(self, task)
         }
     }
 
-    fn spawn_objsafe(&mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<(dyn Observer<Value = Box<(dyn Any + Send + 'static)>> + 'static)> {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
-        let (spawned, observer) = task.spawn_objsafe(self);
-        let now = crate::sys::time::Instant::now();
-        if spawned.poll_after() > now {
-            let dur = now.duration_since(spawned.poll_after());
-            std::thread::sleep(dur);
-        }
-        crate::sleep_on(spawned);
-        Box::new(observer)
+    fn spawn_objsafe(&mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Observer<Value = Box<dyn Any + Send>>> {
+        SpawnRuntimeHandle::spawn_objsafe(self, task)
     }
 
     fn spawn_objsafe_async<'s>(&'s mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Future<Output=Box<dyn Observer<Value=Box<dyn Any + Send>>>> + 's> {
         Box::new(async {
-            Self::spawn_objsafe(self, task)
+            SpawnRuntimeHandle::spawn_objsafe(self, task)
         })
     }
 
     fn clone_box(&self) -> Box<DynExecutor> {
-        Box::new(*self)
+        Box::new(self.clone())
     }
 
     fn executor_notifier(&mut self) -> Option<Self::ExecutorNotifier> {
@@ -162,22 +569,75 @@ impl SomeExecutor for SleepRuntime {
     }
 }
 
-
 /**
-A runtime based on [crate::spawn_on]
+A runtime that accepts tasks but never polls them, for testing code whose executor drops its work.
+
+By default the tasks are kept, so their observers stay pending, until [NullRuntime::discard] or until the runtime
+is dropped, at which point they are cancelled.  A runtime created with [NullRuntime::refusing] drops each task as
+soon as it is spawned instead.
+
+```
+use some_executor::SomeExecutor;
+use some_executor::observer::{Observation, Observer};
+use some_executor::task::Task;
+
+let mut runtime = test_executors::aruntime::NullRuntime::new();
+let task = Task::<_, std::convert::Infallible>::without_notifications("ignored".to_string(), async { 1 }, Default::default());
+let observer = runtime.spawn(task);
+assert_eq!(observer.observe(), Observation::Pending);
+drop(runtime);
+assert_eq!(observer.observe(), Observation::Cancelled);
+```
 */
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct SpawnRuntime;
-impl SpawnRuntime {
+pub struct NullRuntime {
+    refusing: bool,
+    held: std::sync::Mutex<Vec<Box<dyn Send>>>,
+}
+
+impl NullRuntime {
+    /**
+    A runtime that holds each task it is spawned, without polling it, until the runtime is dropped.
+    */
     pub const fn new() -> Self {
-        Self
+        NullRuntime { refusing: false, held: std::sync::Mutex::new(Vec::new()) }
+    }
+
+    /**
+    A runtime that drops each task as soon as it is spawned, so that its observer is cancelled at once.
+    */
+    pub const fn refusing() -> Self {
+        NullRuntime { refusing: true, held: std::sync::Mutex::new(Vec::new()) }
+    }
+
+    /**
+    How many tasks the runtime is holding without polling.
+    */
+    pub fn held(&self) -> usize {
+        self.held.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /**
+    Drops the tasks the runtime is holding, cancelling them.
+    */
+    pub fn discard(&mut self) {
+        let tasks = std::mem::take(&mut *self.held.lock().unwrap_or_else(|e| e.into_inner()));
+        crate::logging::info!("discarding {count} futures", count=tasks.len());
+        drop(tasks);
+    }
+
+    fn accept(&mut self, label: &str, spawned: Box<dyn Send>) {
+        if self.refusing {
+            crate::logging::info!("refused future: {label}", label=label);
+        } else {
+            crate::logging::info!("holding future: {label}", label=label);
+            self.held.lock().unwrap_or_else(|e| e.into_inner()).push(spawned);
+        }
     }
-}
-impl SomeExecutorExt for SpawnRuntime {
 }
 
+impl SomeExecutorExt for NullRuntime {}
 
-impl SomeExecutor for SpawnRuntime {
+impl SomeExecutor for NullRuntime {
     type ExecutorNotifier = Infallible;
 
     fn spawn<F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&mut self, task: Task<F, Notifier>) -> impl Observer<Value=F::Output>
@@ -185,58 +645,39 @@ impl SomeExecutor for SpawnRuntime {
         Self: Sized,
         F::Output: Send,
     {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
         let (spawned, observer) = task.spawn(self);
-        std::thread::spawn(move || {
-            if spawned.poll_after() > crate::sys::time::Instant::now() {
-                let dur = crate::sys::time::Instant::now().duration_since(spawned.poll_after());
-                std::thread::sleep(dur);
-            }
-            crate::sleep_on(spawned);
-        });
+        let label = spawned.label().to_string();
+        self.accept(&label, Box::new(spawned));
         observer
     }
 
+    //spelled out as in the trait, which an `async fn` would not
+    #[allow(clippy::manual_async_fn)]
     fn spawn_async<'s, F: Future + Send + 'static, Notifier: ObserverNotified<F::Output> + Send>(&'s mut self, task: Task<F, Notifier>) -> impl Future<Output=impl Observer<Value=F::Output>> + Send + 's
     where
         Self: Sized,
         F::Output: Send + Unpin,
     {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
         async move {
-            let (spawned, observer) = task.spawn(self);
-            std::thread::spawn(move || {
-                if spawned.poll_after() > crate::sys::time::Instant::now() {
-                    let dur = spawned.poll_after() - crate::sys::time::Instant::now();
-                    std::thread::sleep(dur);
-                }
-                crate::sleep_on(spawned);
-            });
-            observer
+            self.spawn(task)
         }
     }
 
-    fn spawn_objsafe(&mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<(dyn Observer<Value = Box<(dyn Any + Send + 'static)>> + 'static)> {
-        logwise::info_sync!("spawned future: {label}", label=task.label());
+    fn spawn_objsafe(&mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Observer<Value = Box<dyn Any + Send>>> {
         let (spawned, observer) = task.spawn_objsafe(self);
-        std::thread::spawn(move || {
-            if spawned.poll_after() > crate::sys::time::Instant::now() {
-                let dur = crate::sys::time::Instant::now().duration_since(spawned.poll_after());
-                std::thread::sleep(dur);
-            }
-            crate::sleep_on(spawned);
-        });
+        let label = spawned.label().to_string();
+        self.accept(&label, Box::new(spawned));
         Box::new(observer)
     }
 
     fn spawn_objsafe_async<'s>(&'s mut self, task: Task<Pin<Box<dyn Future<Output=Box<dyn Any + 'static + Send>> + 'static + Send>>, Box<dyn ObserverNotified<dyn Any + Send> + Send>>) -> Box<dyn Future<Output=Box<dyn Observer<Value=Box<dyn Any + Send>>>> + 's> {
         Box::new(async {
-            Self::spawn_objsafe(self, task)
+            self.spawn_objsafe(task)
         })
     }
 
     fn clone_box(&self) -> Box<DynExecutor> {
-        Box::new(*self)
+        Box::new(self.clone())
     }
 
     fn executor_notifier(&mut self) -> Option<Self::ExecutorNotifier> {
@@ -244,6 +685,88 @@ impl SomeExecutor for SpawnRuntime {
     }
 }
 
+/**
+A runtime that polls each task exactly once, while spawning it, for asserting that code completes without
+waiting.
+
+A task that is not ready after its one poll is dropped, so its observer reports it as cancelled, and its label is
+added to [InlineRuntime::incomplete], which clones of the runtime share.
+
+```
+use some_executor::SomeExecutor;
+use some_executor::observer::{Observation, Observer};
+use some_executor::task::Task;
+
+let mut runtime = test_executors::aruntime::InlineRuntime::new();
+let task = Task::<_, std::convert::Infallible>::without_notifications("sync".to_string(), async { 1 }, Default::default());
+assert_eq!(runtime.spawn(task).observe(), Observation::Ready(1));
+let task = Task::<_, std::convert::Infallible>::without_notifications("awaits".to_string(), test_executors::pend_forever::PendForever, Default::default());
+assert_eq!(runtime.spawn(task).observe(), Observation::Cancelled);
+assert_eq!(runtime.incomplete(), ["awaits"]);
+```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct InlineRuntime {
+    incomplete: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl InlineRuntime {
+    /**
+    A runtime with no incomplete tasks yet.
+    */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    The labels of the tasks that were not ready after their poll, in the order they were spawned.
+    */
+    pub fn incomplete(&self) -> Vec<String> {
+        self.incomplete.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl RunSpawned for InlineRuntime {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, _spawned_at: &'static Location<'static>, task: F) {
+        crate::clock::sleep_until(poll_after);
+        //the task is dropped before spawn returns, so it is never listed by running_tasks
+        if crate::poll_once_pin(task).is_pending() {
+            crate::logging::warning!("future was not ready after one poll: {label}", label=label);
+            self.incomplete.lock().unwrap_or_else(|e| e.into_inner()).push(label.to_string());
+        }
+    }
+}
+
+spawn_through_start!(InlineRuntime);
+
+/**
+A runtime whose tasks are reported to an [ExecutorHooks] object, named by their label.  Returned by the
+`with_hooks` method of the other runtimes in this module.
+*/
+#[derive(Clone)]
+pub struct HookedRuntime<R> {
+    runtime: R,
+    hooks: Arc<dyn ExecutorHooks>,
+}
+
+impl<R> HookedRuntime<R> {
+    /**
+    The runtime running the tasks.
+    */
+    pub fn runtime(&self) -> &R {
+        &self.runtime
+    }
+}
+
+impl<R: RunSpawned> RunSpawned for HookedRuntime<R> {
+    fn run_spawned<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, poll_after: Instant, spawned_at: &'static Location<'static>, task: F) {
+        let task = crate::hooks::hooked_named(task, self.hooks.clone(), label);
+        self.runtime.run_spawned(label, poll_after, spawned_at, task);
+    }
+}
+
+spawn_through_start!([R: RunSpawned] HookedRuntime<R>);
+
 //boilerplate
 
 impl Display for SpinRuntime {
@@ -252,6 +775,12 @@ impl Display for SpinRuntime {
     }
 }
 
+impl Display for BackgroundSpinRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BackgroundSpinRuntime")
+    }
+}
+
 impl Display for SleepRuntime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "SleepRuntime")
@@ -264,12 +793,63 @@ impl Display for SpawnRuntime {
     }
 }
 
+impl Display for TrackedSpawnRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TrackedSpawnRuntime")
+    }
+}
+
+impl<R: Display> Display for HookedRuntime<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} with hooks", self.runtime)
+    }
+}
+
+impl<R: std::fmt::Debug> std::fmt::Debug for HookedRuntime<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookedRuntime").field("runtime", &self.runtime).finish_non_exhaustive()
+    }
+}
+
+impl Display for NullRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NullRuntime")
+    }
+}
+
+impl Display for InlineRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InlineRuntime")
+    }
+}
+
+impl std::fmt::Debug for NullRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NullRuntime").field("refusing", &self.refusing).field("held", &self.held()).finish()
+    }
+}
+
+//A clone starts with no tasks of its own: each spawned task keeps a clone of its runtime, for spawning siblings,
+//so sharing the held tasks between clones would keep them alive forever.  They are never polled, so they never
+//spawn onto the clone either.
+impl Clone for NullRuntime {
+    fn clone(&self) -> Self {
+        NullRuntime { refusing: self.refusing, held: std::sync::Mutex::new(Vec::new()) }
+    }
+}
+
 impl Default for SpinRuntime {
     fn default() -> Self {
         Self::new()
     }
 }
 
+impl Default for BackgroundSpinRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Default for SleepRuntime {
     fn default() -> Self {
         Self::new()
@@ -282,22 +862,290 @@ impl Default for SpawnRuntime {
     }
 }
 
+impl Default for TrackedSpawnRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for NullRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /**
 Sets a truntime as the global runtime.
 */
 pub fn set_global_test_runtime() {
-    let as_dyn = Box::new(SpawnRuntime) as Box<DynExecutor>;
+    let as_dyn = Box::new(SpawnRuntime::new()) as Box<DynExecutor>;
     some_executor::global_executor::set_global_executor(as_dyn)
 }
+
+static INSTALL_GLOBAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/**
+Sets `runtime` as the global runtime, unless a global runtime was already set.
+
+Returns whether `runtime` was installed.  some_executor's global runtime can only be set once per process, so
+unlike [set_global_test_runtime] this can be called from every test that needs a global runtime.
+*/
+pub fn set_global_runtime_if_unset(runtime: Box<DynExecutor>) -> bool {
+    let _guard = INSTALL_GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+    if some_executor::global_executor::global_executor(|e| e.is_some()) {
+        false
+    } else {
+        some_executor::global_executor::set_global_executor(runtime);
+        true
+    }
+}
+/**
+A handle to the runtime running the current task, for spawning sibling tasks onto it.  See [current].
+*/
+pub struct Current(Box<DynExecutor>);
+
+impl Current {
+    /**
+    Spawns `future` onto the runtime as a task labeled `label`.
+
+    Dropping the returned observer cancels the task; see [Self::spawn_detached] to let it run regardless.
+    */
+    #[track_caller]
+    pub fn spawn<F: Future + Send + 'static>(&mut self, label: &str, future: F) -> CurrentObserver<F::Output>
+    where
+        F::Output: Send + Unpin,
+    {
+        let task = Task::<_, Infallible>::without_notifications(label.to_string(), future, Configuration::default());
+        CurrentObserver(self.0.spawn_objsafe(task.into_objsafe()), std::marker::PhantomData)
+    }
+
+    /**
+    Spawns `future` onto the runtime as a task labeled `label`, and lets it run to completion.
+    */
+    #[track_caller]
+    pub fn spawn_detached<F: Future<Output = ()> + Send + 'static>(&mut self, label: &str, future: F) {
+        let task = Task::<_, Infallible>::without_notifications(label.to_string(), future, Configuration::default()).into_objsafe();
+        DETACHING.with(|detaching| detaching.set(Some(task.task_id())));
+        let observer = self.0.spawn_objsafe(task);
+        if DETACHING.with(|detaching| detaching.take()).is_some() {
+            /*
+            A runtime from outside this crate, which did not detach the task.  Its type-erased observer cancels
+            the task when dropped and cannot be detached, so it is leaked instead.
+             */
+            std::mem::forget(observer);
+        }
+    }
+
+    /**
+    The underlying executor.
+    */
+    pub fn into_executor(self) -> Box<DynExecutor> {
+        self.0
+    }
+}
+
+/**
+Observes a task spawned through [Current::spawn] or [SpawnRuntimeHandle::spawn].
+*/
+pub struct CurrentObserver<T>(Box<dyn Observer<Value = Box<dyn Any + Send>>>, std::marker::PhantomData<T>);
+
+impl<T: 'static> Observer for CurrentObserver<T> {
+    type Value = T;
+
+    fn observe(&self) -> Observation<T> {
+        crate::objsafe::typed_observation(self.0.observe())
+    }
+
+    fn task_id(&self) -> &TaskID {
+        self.0.task_id()
+    }
+}
+
+impl std::fmt::Debug for Current {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Current").finish()
+    }
+}
+
+/**
+Returns a handle to the runtime running the current task.
+
+Inside a task spawned onto one of this crate's runtimes (or any other some_executor runtime), this is that
+runtime, so that code which "spawns onto the current executor" can be tested.  Elsewhere, it falls back to the
+thread's executor and then to the global executor, as some_executor's `current_executor` does, and is `None`
+if neither is set.
+*/
+pub fn current() -> Option<Current> {
+    some_executor::current_executor::current_executor().map(Current)
+}
+
 #[cfg(test)]
 mod test {
     #[test]
     fn assert_send_sync() {
         fn assert_send_sync<T: Send + Sync>() {}
         assert_send_sync::<super::SpinRuntime>();
+        assert_send_sync::<super::BackgroundSpinRuntime>();
         assert_send_sync::<super::SleepRuntime>();
         assert_send_sync::<super::SpawnRuntime>();
+        assert_send_sync::<super::TrackedSpawnRuntime>();
+        assert_send_sync::<super::HookedRuntime<super::SpawnRuntime>>();
+        assert_send_sync::<super::SpawnRuntimeHandle>();
+        assert_send_sync::<super::NullRuntime>();
+        assert_send_sync::<super::InlineRuntime>();
+    }
+
+    #[test]
+    fn spawns_sibling_on_current() {
+        use some_executor::SomeExecutor;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("parent".to_string(), async move {
+            let mut current = super::current().expect("inside a task");
+            current.spawn_detached("child", async move { sender.send("from child").unwrap() });
+        }, Default::default());
+        let _observer = super::SpawnRuntime::new().spawn(task);
+        assert_eq!(receiver.recv().unwrap(), "from child");
     }
-}
 
+    #[test]
+    fn names_thread_after_task() {
+        use some_executor::SomeExecutor;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("fetch user 3".to_string(), async move {
+            sender.send(std::thread::current().name().map(str::to_string)).unwrap();
+        }, Default::default());
+        let _observer = super::SpawnRuntime::new().spawn(task);
+        assert_eq!(receiver.recv().unwrap().as_deref(), Some("fetch user 3"));
+    }
+
+    #[test]
+    fn background_spin_returns_promptly() {
+        use some_executor::SomeExecutor;
+        use some_executor::observer::{Observation, Observer};
+        let (sender, receiver) = std::sync::mpsc::channel::<u8>();
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("background".to_string(), async move {
+            //only completes once the caller has regained control
+            loop {
+                if let Ok(value) = receiver.try_recv() {
+                    return value;
+                }
+                std::future::poll_fn(|_| std::task::Poll::Ready(())).await;
+            }
+        }, Default::default());
+        let observer = super::BackgroundSpinRuntime::new().spawn(task);
+        sender.send(5).unwrap();
+        loop {
+            match observer.observe() {
+                Observation::Ready(value) => break assert_eq!(value, 5),
+                Observation::Pending => std::thread::yield_now(),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+    }
 
+    #[test]
+    fn waits_for_poll_after() {
+        use some_executor::SomeExecutor;
+        use some_executor::observer::{Observation, Observer};
+        let delay = std::time::Duration::from_millis(20);
+        let runtimes: [Box<some_executor::DynExecutor>; 2] = [Box::new(super::SpinRuntime::new()), Box::new(super::SleepRuntime::new())];
+        for mut runtime in runtimes {
+            let poll_after = crate::sys::time::Instant::now() + delay;
+            let configuration = some_executor::task::ConfigurationBuilder::new().poll_after(poll_after).build();
+            let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("delayed".to_string(), async { 1 }, configuration);
+            //polling before poll_after would trip some_executor's assertion
+            let observer = runtime.spawn_objsafe(task.into_objsafe());
+            assert!(crate::sys::time::Instant::now() >= poll_after);
+            assert!(matches!(observer.observe(), Observation::Ready(_)));
+        }
+    }
+
+    #[test]
+    fn handle_spawns_from_helpers() {
+        use some_executor::observer::{Observation, Observer};
+        fn helper(handle: &super::SpawnRuntimeHandle, value: u8) -> u8 {
+            let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("helper".to_string(), async move { value }, Default::default());
+            let observer = handle.spawn(task);
+            loop {
+                match observer.observe() {
+                    Observation::Ready(value) => return value,
+                    Observation::Pending => std::thread::yield_now(),
+                    other => panic!("unexpected {other:?}"),
+                }
+            }
+        }
+        let handle = super::SpawnRuntimeHandle::default();
+        let other = handle.clone();
+        let from_thread = std::thread::spawn(move || helper(&other, 2));
+        assert_eq!(helper(&handle, 1), 1);
+        assert_eq!(from_thread.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn spawns_batches() {
+        use some_executor::observer::{Observation, Observer};
+        let tasks = || (0..10u32).map(|i| some_executor::task::Task::<_, std::convert::Infallible>::without_notifications(format!("task {i}"), async move { i }, Default::default()));
+        let observers = super::SleepRuntime::new().spawn_all(tasks());
+        let values: Vec<_> = observers.iter().map(|o| o.observe()).collect();
+        assert_eq!(values, (0..10).map(Observation::Ready).collect::<Vec<_>>());
+        let observers = super::SpawnRuntimeHandle::new(super::SpawnRuntime::new()).spawn_all(tasks());
+        assert_eq!(observers.len(), 10);
+        for (i, observer) in (0..).zip(observers) {
+            loop {
+                match observer.observe() {
+                    Observation::Ready(value) => break assert_eq!(value, i),
+                    Observation::Pending => std::thread::yield_now(),
+                    other => panic!("unexpected {other:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn null_runtime_cancels_on_drop() {
+        use some_executor::SomeExecutor;
+        use some_executor::observer::{Observation, Observer};
+        use crate::observation::ObservationExt;
+        let task = |label: &str| some_executor::task::Task::<_, std::convert::Infallible>::without_notifications(label.to_string(), async { 1 }, Default::default());
+        let mut runtime = super::NullRuntime::new();
+        let held = runtime.spawn(task("held"));
+        let objsafe = runtime.spawn_objsafe(task("objsafe").into_objsafe());
+        assert_eq!(runtime.held(), 2);
+        assert_eq!(held.observe(), Observation::Pending);
+        runtime.discard();
+        held.observe().expect_cancelled();
+        assert!(matches!(objsafe.observe(), Observation::Cancelled));
+        super::NullRuntime::refusing().spawn(task("refused")).observe().expect_cancelled();
+    }
+
+    #[test]
+    fn inline_runtime_polls_once() {
+        use some_executor::SomeExecutor;
+        use some_executor::observer::Observer;
+        use crate::observation::ObservationExt;
+        let mut runtime = super::InlineRuntime::new();
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("ready".to_string(), async { 2 }, Default::default());
+        assert_eq!(runtime.spawn(task).observe().expect_ready(), 2);
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("yields".to_string(), crate::future_util::yield_now(), Default::default());
+        let mut clone = runtime.clone();
+        clone.spawn_objsafe(task.into_objsafe()).observe().expect_cancelled();
+        assert_eq!(runtime.incomplete(), ["yields"]);
+    }
+
+    #[test]
+    fn waits_for_detached_tasks() {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let mut runtime = super::SpawnRuntime::tracked();
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("fire and forget".to_string(), async move {
+            crate::spawn_blocking(move || receiver.recv()).await.unwrap();
+        }, Default::default());
+        for observer in runtime.spawn_all([task]) {
+            observer.detach();
+        }
+        let err = runtime.wait_idle(std::time::Duration::from_millis(5)).unwrap_err();
+        assert_eq!(err.running(), 1);
+        sender.send(()).unwrap();
+        runtime.clone().wait_idle(std::time::Duration::from_secs(10)).unwrap();
+        super::TrackedSpawnRuntime::new().wait_idle(std::time::Duration::ZERO).unwrap();
+    }
+}
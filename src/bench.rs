@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Quick micro-benchmarks of futures, driven by [crate::spin_on].
+
+[Bench] runs a future-producing closure for a number of warmup iterations, then a number of measured ones,
+timing every `poll`.  The resulting [BenchReport] gives the mean time per iteration and the distribution of poll
+latency.  [crate::async_bench] generates a benchmark function from an async fn using this module.
+
+This is meant for a quick look at how a future behaves, not as a replacement for a statistics-heavy harness.
+*/
+
+use std::fmt::Display;
+use std::future::Future;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use crate::sys::time::Instant;
+
+/**
+The number of measured iterations used unless [Bench::iterations] says otherwise.
+*/
+pub const DEFAULT_ITERATIONS: u32 = 100;
+
+/**
+The number of warmup iterations used unless [Bench::warmup] says otherwise.
+*/
+pub const DEFAULT_WARMUP: u32 = 10;
+
+/**
+A benchmark of an async body.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Bench {
+    name: String,
+    iterations: u32,
+    warmup: u32,
+}
+
+impl Bench {
+    /**
+    Creates a benchmark named `name`, running [DEFAULT_WARMUP] warmup and [DEFAULT_ITERATIONS] measured iterations.
+    */
+    pub fn new(name: impl Into<String>) -> Self {
+        Bench {
+            name: name.into(),
+            iterations: DEFAULT_ITERATIONS,
+            warmup: DEFAULT_WARMUP,
+        }
+    }
+
+    /**
+    Sets the number of measured iterations.  At least one iteration is always measured.
+    */
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /**
+    Sets the number of iterations run, unmeasured, before measuring.
+    */
+    pub fn warmup(mut self, warmup: u32) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /**
+    Runs the benchmark, creating a future with `body` for each iteration.
+    */
+    pub fn run<T: Fn() -> F, F: Future>(&self, body: T) -> BenchReport {
+        crate::logging::info!("warming up bench {name}", name = self.name.as_str());
+        let mut discarded = Vec::new();
+        for _ in 0..self.warmup {
+            timed_spin_on(body(), &mut discarded);
+            discarded.clear();
+        }
+        crate::logging::info!("measuring bench {name}", name = self.name.as_str());
+        let mut polls = Vec::new();
+        let start = Instant::now();
+        for _ in 0..self.iterations {
+            timed_spin_on(body(), &mut polls);
+        }
+        let elapsed = start.elapsed();
+        polls.sort_unstable();
+        BenchReport {
+            name: self.name.clone(),
+            iterations: self.iterations,
+            elapsed,
+            polls,
+        }
+    }
+}
+
+/**
+Like [crate::spin_on], but records how long each poll took.
+*/
+fn timed_spin_on<F: Future>(future: F, polls: &mut Vec<Duration>) -> F::Output {
+    let waker = crate::noop_waker::noop_waker();
+    let mut context = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
+    loop {
+        let start = Instant::now();
+        let poll = future.as_mut().poll(&mut context);
+        polls.push(start.elapsed());
+        if let Poll::Ready(val) = poll {
+            return val;
+        }
+        crate::sys::spin_loop();
+    }
+}
+
+/**
+The measurements from [Bench::run].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BenchReport {
+    name: String,
+    iterations: u32,
+    elapsed: Duration,
+    //sorted
+    polls: Vec<Duration>,
+}
+
+impl BenchReport {
+    /**
+    The name of the benchmark.
+    */
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /**
+    How many iterations were measured.
+    */
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /**
+    How many times the futures were polled, over all measured iterations.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls.len() as u64
+    }
+
+    /**
+    The mean time to run one iteration to completion.
+    */
+    pub fn mean_iteration(&self) -> Duration {
+        self.elapsed / self.iterations
+    }
+
+    /**
+    The mean time spent inside one call to `poll`.
+    */
+    pub fn mean_poll(&self) -> Duration {
+        self.polls.iter().sum::<Duration>() / self.polls.len() as u32
+    }
+
+    /**
+    The poll latency below which `percentile` percent of polls fell, e.g. `poll_percentile(99.0)`.
+
+    # Panics
+    If `percentile` is not within `0.0..=100.0`.
+    */
+    pub fn poll_percentile(&self, percentile: f64) -> Duration {
+        assert!((0.0..=100.0).contains(&percentile), "percentile {percentile} is out of range");
+        let index = ((self.polls.len() - 1) as f64 * percentile / 100.0).round() as usize;
+        self.polls[index]
+    }
+
+    /**
+    The slowest poll.
+    */
+    pub fn max_poll(&self) -> Duration {
+        self.poll_percentile(100.0)
+    }
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bench {}: {} iterations, {:?}/iter; poll latency mean {:?}, p50 {:?}, p99 {:?}, max {:?} ({} polls)",
+               self.name, self.iterations, self.mean_iteration(), self.mean_poll(), self.poll_percentile(50.0),
+               self.poll_percentile(99.0), self.max_poll(), self.polls())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bench;
+
+    #[test]
+    fn counts_polls() {
+        let report = Bench::new("yield_once").warmup(2).iterations(5).run(|| {
+            let mut yielded = false;
+            std::future::poll_fn(move |_| {
+                if yielded {
+                    std::task::Poll::Ready(())
+                } else {
+                    yielded = true;
+                    std::task::Poll::Pending
+                }
+            })
+        });
+        assert_eq!(report.iterations(), 5);
+        assert_eq!(report.polls(), 10);
+        assert!(report.poll_percentile(50.0) <= report.max_poll());
+    }
+
+    #[test]
+    fn at_least_one_iteration() {
+        let report = Bench::new("ready").iterations(0).run(|| async {});
+        assert_eq!(report.iterations(), 1);
+        assert_eq!(report.polls(), 1);
+        assert!(report.to_string().starts_with("bench ready: 1 iterations"));
+    }
+}
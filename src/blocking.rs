@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Choosing how to block on a future, in code or from the environment.
+
+[BlockingExecutor] is implemented by the crate's polling strategies: [Spin] ([crate::spin_on]), [Sleep]
+([crate::sleep_on]) and [Adaptive] (spinning briefly, then parking, with [crate::sleep_on_with]).  Test helpers
+that block through [default_executor] instead of calling one of those directly can be switched to another
+strategy with the [EXECUTOR_VAR] environment variable, which is handy when a hang only shows up on CI:
+
+```
+use test_executors::blocking::{default_executor, BlockingExecutor};
+assert_eq!(default_executor().block_on(async { 3 }), 3);
+```
+
+```text
+TEST_EXECUTOR=spin cargo test
+```
+
+An `#[async_test]` that names no `executor` blocks through [default_executor] too.  [crate::spin_on],
+[crate::sleep_on] and the other executors always block as their names say, whatever [EXECUTOR_VAR] is set to.
+*/
+
+use std::fmt::Display;
+use std::future::IntoFuture;
+use std::str::FromStr;
+use crate::ParkPolicy;
+use crate::error::UnknownStrategy;
+
+/**
+Blocks the calling thread until a future is ready.
+*/
+pub trait BlockingExecutor {
+    /**
+    Blocks until `future` is ready, returning its output.
+    */
+    fn block_on<F: IntoFuture>(&self, future: F) -> F::Output;
+}
+
+/**
+Blocks with [crate::spin_on].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Spin;
+
+impl BlockingExecutor for Spin {
+    fn block_on<F: IntoFuture>(&self, future: F) -> F::Output {
+        crate::spin_on(future)
+    }
+}
+
+/**
+Blocks with [crate::sleep_on].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Sleep;
+
+impl BlockingExecutor for Sleep {
+    fn block_on<F: IntoFuture>(&self, future: F) -> F::Output {
+        crate::sleep_on(future)
+    }
+}
+
+/**
+Blocks with [crate::sleep_on_with], checking for a wake `spins` times before parking the thread.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Adaptive {
+    pub spins: u32,
+}
+
+impl Adaptive {
+    /**
+    The spins of [Adaptive::default], unless [crate::config] says otherwise.
+    */
+    pub const DEFAULT_SPINS: u32 = 1_000;
+}
+
+impl Default for Adaptive {
+    /**
+    Spins as many times as [crate::config] says, [Adaptive::DEFAULT_SPINS] unless configured otherwise.
+    */
+    fn default() -> Self {
+        Adaptive { spins: crate::config::current().spins() }
+    }
+}
+
+impl BlockingExecutor for Adaptive {
+    fn block_on<F: IntoFuture>(&self, future: F) -> F::Output {
+        crate::sleep_on_with(future, ParkPolicy::SpinThenPark { spins: self.spins })
+    }
+}
+
+/**
+One of the strategies, as chosen by [default_executor].
+
+`Sleep` waits as the configured [crate::config::Config::with_park_policy] says, and `Adaptive` spins as many
+times as [crate::config::Config::with_spins] says.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Strategy {
+    Spin,
+    #[default]
+    Sleep,
+    Adaptive,
+}
+
+impl BlockingExecutor for Strategy {
+    fn block_on<F: IntoFuture>(&self, future: F) -> F::Output {
+        match self {
+            Strategy::Spin => Spin.block_on(future),
+            Strategy::Sleep => match crate::config::current().park_policy() {
+                ParkPolicy::Park => Sleep.block_on(future),
+                policy => crate::sleep_on_with(future, policy),
+            },
+            Strategy::Adaptive => Adaptive::default().block_on(future),
+        }
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Strategy::Spin => "spin",
+            Strategy::Sleep => "sleep",
+            Strategy::Adaptive => "adaptive",
+        })
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = UnknownStrategy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "spin" => Ok(Strategy::Spin),
+            "sleep" => Ok(Strategy::Sleep),
+            "adaptive" => Ok(Strategy::Adaptive),
+            _ => Err(UnknownStrategy::new(s)),
+        }
+    }
+}
+
+pub use crate::config::EXECUTOR_VAR;
+
+/**
+The strategy named by [EXECUTOR_VAR], or as configured by [crate::config], or [Strategy::Sleep].
+
+# Panics
+If [EXECUTOR_VAR] is set to anything else, so that a typo does not silently test the wrong strategy.
+*/
+pub fn default_executor() -> Strategy {
+    crate::config::current().executor()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockingExecutor, Strategy};
+
+    #[test]
+    fn every_strategy_blocks() {
+        for name in ["spin", "Sleep", " adaptive "] {
+            let strategy: Strategy = name.parse().unwrap();
+            strategy.block_on(crate::future_util::yield_now());
+            assert_eq!(strategy.to_string().parse::<Strategy>(), Ok(strategy));
+        }
+        assert_eq!("park".parse::<Strategy>().unwrap_err().to_string(), "unknown executor `park`; expected `spin`, `sleep` or `adaptive`");
+    }
+}
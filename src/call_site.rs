@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Where the executor driving the current thread was called from, for diagnostics.
+
+The blocking executors are `#[track_caller]`, and record their caller here while they poll, so that a panic or
+deadlock inside a future can be traced back to the test code that started driving it.
+*/
+
+use std::fmt::Display;
+use std::panic::Location;
+
+/**
+An executor entry point, and the code that called it.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct CallSite {
+    pub(crate) executor: &'static str,
+    pub(crate) location: &'static Location<'static>,
+}
+
+impl Display for CallSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} called at {}", self.executor, self.location)
+    }
+}
+
+/*
+loom and shuttle run their model threads on a single OS thread, so a thread-local cannot tell them apart.
+ */
+#[cfg(not(any(feature = "loom", feature = "shuttle")))]
+std::thread_local! {
+    static CURRENT: std::cell::Cell<Option<CallSite>> = const { std::cell::Cell::new(None) };
+}
+
+/**
+The innermost executor driving a future on this thread, if any.
+*/
+pub(crate) fn current() -> Option<CallSite> {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    return CURRENT.try_with(|current| current.get()).ok().flatten();
+    #[cfg(any(feature = "loom", feature = "shuttle"))]
+    None
+}
+
+/**
+Records an executor's call site as [current] until dropped.
+*/
+pub(crate) struct Entered {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    prior: Option<CallSite>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+        let _ = CURRENT.try_with(|current| current.set(self.prior));
+    }
+}
+
+/**
+Records that `executor` was called from `location`, until the returned guard is dropped.
+*/
+pub(crate) fn enter(executor: &'static str, location: &'static Location<'static>) -> Entered {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    {
+        let prior = CURRENT.with(|current| current.replace(Some(CallSite { executor, location })));
+        Entered { prior }
+    }
+    #[cfg(any(feature = "loom", feature = "shuttle"))]
+    {
+        let _ = (executor, location);
+        Entered {}
+    }
+}
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Waiting for a task's `poll_after`, for the runtimes in [crate::aruntime].
+
+some_executor asserts that a task is not polled before its `poll_after` instant by the real clock, so the delay
+must really be waited out; these helpers do so, treating a deadline that has already passed as no delay.
+*/
+
+use std::time::Duration;
+use crate::sys::time::Instant;
+
+/**
+Sleeps the current thread until `deadline`, which may already have passed.
+*/
+pub(crate) fn sleep_until(deadline: Instant) {
+    let delay = deadline.saturating_duration_since(Instant::now());
+    if delay > Duration::ZERO {
+        crate::logging::trace!("sleeping {delay:?} until poll_after", delay = delay);
+        crate::sys::sleep(delay);
+    }
+}
+
+/**
+Busy-waits until `deadline`, which may already have passed.
+*/
+pub(crate) fn spin_until(deadline: Instant) {
+    while deadline > Instant::now() {
+        crate::sys::spin_loop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::sys::time::Instant;
+
+    #[test]
+    fn sleeps_until_deadline() {
+        let deadline = Instant::now() + Duration::from_millis(20);
+        super::sleep_until(deadline);
+        assert!(Instant::now() >= deadline);
+        //already passed
+        super::sleep_until(deadline);
+        super::spin_until(deadline);
+    }
+}
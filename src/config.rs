@@ -0,0 +1,457 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+The defaults the executors use when a test does not choose for itself.
+
+Each setting can be set for a run from the environment, or for the process from code by installing a [Config];
+an installed configuration wins over the environment, which wins over the built-in defaults:
+
+| Setting | Variable | Default | Used by |
+|---|---|---|---|
+| [Config::with_default_timeout] | `TEST_EXECUTORS_DEFAULT_TIMEOUT` | 60 seconds | `#[async_test]` without `timeout_ms` |
+| [Config::with_executor] | `TEST_EXECUTOR` | `sleep` | [crate::blocking::default_executor] |
+| [Config::with_spins] | `TEST_EXECUTORS_SPINS` | 1000 | the `adaptive` strategy |
+| [Config::with_park_policy] | `TEST_EXECUTORS_PARK_TIMEOUT` | park until woken | the `sleep` strategy |
+| [Config::with_chaos_seed] | `TEST_EXECUTORS_CHAOS_SEED` | every seed | `#[async_test(chaos_seeds = N)]` |
+| [Config::with_log_level] | `TEST_EXECUTORS_LOG` | `trace` | the executors' own instrumentation |
+| [Config::with_update_snapshots] | `TEST_EXECUTORS_UPDATE_SNAPSHOTS` | `0` | [crate::assert_schedule_snapshot] |
+| [Config::with_task_tracking] | `TEST_EXECUTORS_TRACK_TASKS` | `1` | [crate::debug] |
+
+Durations are written as seconds, or a number followed by `s` or `ms`, and the timeout can also be `none`.  The
+log level is `trace`, `info`, `warn` or `off`; messages below it are not sent to logwise, tracing or
+[crate::log_capture] at all.  A chaos seed runs a chaos test under that seed only, to reproduce a failure.
+Updating snapshots and tracking tasks are `1` or `0`.
+
+```
+use std::time::Duration;
+use test_executors::config::Config;
+
+Config::from_env().with_default_timeout(Some(Duration::from_secs(5))).install();
+assert_eq!(test_executors::config::current().default_timeout(), Some(Duration::from_secs(5)));
+```
+
+The environment is read once, the first time a setting is needed.  An invalid value panics then, naming the
+variable, rather than being ignored.  Logging, which can happen inside a wake or a drop, never panics: until a
+setting is read otherwise, it falls back to the defaults when the environment is invalid, and so does the check
+for whether to track a task that is being spawned.
+*/
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+use crate::ParkPolicy;
+use crate::blocking::Strategy;
+use crate::error::ConfigError;
+use crate::log_capture::Level;
+
+/**
+The timeout of an `#[async_test]` that sets none, unless configured otherwise.
+*/
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/**
+The environment variable that sets [Config::with_default_timeout]: a number of seconds, a number followed by `s` or
+`ms`, or `none` for no default timeout.
+*/
+pub const DEFAULT_TIMEOUT_VAR: &str = "TEST_EXECUTORS_DEFAULT_TIMEOUT";
+
+/**
+The environment variable that sets [Config::with_executor]: `spin`, `sleep` or `adaptive`.
+*/
+pub const EXECUTOR_VAR: &str = "TEST_EXECUTOR";
+
+/**
+The environment variable that sets [Config::with_spins].
+*/
+pub const SPINS_VAR: &str = "TEST_EXECUTORS_SPINS";
+
+/**
+The environment variable that sets [Config::with_park_policy] to park for at most the given duration.
+*/
+pub const PARK_TIMEOUT_VAR: &str = "TEST_EXECUTORS_PARK_TIMEOUT";
+
+/**
+The environment variable that sets [Config::with_chaos_seed].
+*/
+pub const CHAOS_SEED_VAR: &str = "TEST_EXECUTORS_CHAOS_SEED";
+
+/**
+The environment variable that sets [Config::with_log_level]: `trace`, `info`, `warn` or `off`.
+*/
+pub const LOG_VAR: &str = "TEST_EXECUTORS_LOG";
+
+/**
+The environment variable that sets [Config::with_update_snapshots]: `1` or `0`.
+*/
+pub const UPDATE_SNAPSHOTS_VAR: &str = "TEST_EXECUTORS_UPDATE_SNAPSHOTS";
+
+/**
+The environment variable that sets [Config::with_task_tracking]: `1` or `0`.
+*/
+pub const TRACK_TASKS_VAR: &str = "TEST_EXECUTORS_TRACK_TASKS";
+
+/**
+A set of defaults for the executors.  See the [module documentation](self).
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Config {
+    default_timeout: Option<Duration>,
+    executor: Strategy,
+    spins: u32,
+    park_policy: ParkPolicy,
+    chaos_seed: Option<u64>,
+    log_level: Option<Level>,
+    update_snapshots: bool,
+    task_tracking: bool,
+}
+
+impl Config {
+    /**
+    The built-in defaults, ignoring the environment.
+    */
+    pub const fn new() -> Self {
+        Config {
+            default_timeout: Some(DEFAULT_TIMEOUT),
+            executor: Strategy::Sleep,
+            spins: crate::blocking::Adaptive::DEFAULT_SPINS,
+            park_policy: ParkPolicy::Park,
+            chaos_seed: None,
+            log_level: Some(Level::Trace),
+            update_snapshots: false,
+            task_tracking: true,
+        }
+    }
+
+    /**
+    The built-in defaults, overridden by any of the variables that are set.
+
+    # Panics
+    If a variable is set to a value it cannot hold.
+    */
+    pub fn from_env() -> Self {
+        Self::try_from_env().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /**
+    Like [Config::from_env], but returns a [ConfigError] for an invalid value.
+    */
+    pub fn try_from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|var| std::env::var(var).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        //each value, with the error to return if it does not parse
+        let read = |var: &'static str, expected: &'static str| {
+            lookup(var).map(|value| (value.clone(), ConfigError::new(var, value, expected)))
+        };
+        let mut config = Config::new();
+        if let Some((value, err)) = read(DEFAULT_TIMEOUT_VAR, "seconds, `<N>s`, `<N>ms` or `none`") {
+            config.default_timeout = parse_timeout(&value).ok_or(err)?;
+        }
+        if let Some((value, err)) = read(EXECUTOR_VAR, "`spin`, `sleep` or `adaptive`") {
+            config.executor = value.parse().map_err(|_| err)?;
+        }
+        if let Some((value, err)) = read(SPINS_VAR, "a number of spins") {
+            config.spins = value.trim().parse().map_err(|_| err)?;
+        }
+        if let Some((value, err)) = read(PARK_TIMEOUT_VAR, "seconds, `<N>s` or `<N>ms`") {
+            config.park_policy = ParkPolicy::ParkTimeout(parse_timeout(&value).flatten().ok_or(err)?);
+        }
+        if let Some((value, err)) = read(CHAOS_SEED_VAR, "a seed") {
+            config.chaos_seed = Some(value.trim().parse().map_err(|_| err)?);
+        }
+        if let Some((value, err)) = read(LOG_VAR, "`trace`, `info`, `warn` or `off`") {
+            config.log_level = parse_level(&value).ok_or(err)?;
+        }
+        if let Some((value, err)) = read(UPDATE_SNAPSHOTS_VAR, "`1` or `0`") {
+            config.update_snapshots = parse_flag(&value).ok_or(err)?;
+        }
+        if let Some((value, err)) = read(TRACK_TASKS_VAR, "`1` or `0`") {
+            config.task_tracking = parse_flag(&value).ok_or(err)?;
+        }
+        Ok(config)
+    }
+
+    /**
+    Sets the timeout of an `#[async_test]` that sets none, or `None` for no timeout.
+    */
+    pub fn with_default_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /**
+    Sets the strategy [crate::blocking::default_executor] returns.
+    */
+    pub fn with_executor(mut self, executor: Strategy) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /**
+    Sets how many times the `adaptive` strategy checks for a wake before parking.
+    */
+    pub fn with_spins(mut self, spins: u32) -> Self {
+        self.spins = spins;
+        self
+    }
+
+    /**
+    Sets how the `sleep` strategy waits between polls.
+    */
+    pub fn with_park_policy(mut self, policy: ParkPolicy) -> Self {
+        self.park_policy = policy;
+        self
+    }
+
+    /**
+    Sets the only seed chaos tests run under, instead of each of their seeds.
+    */
+    pub fn with_chaos_seed(mut self, seed: Option<u64>) -> Self {
+        self.chaos_seed = seed;
+        self
+    }
+
+    /**
+    Sets the least severe level of the executors' messages that is logged, or `None` to log nothing.
+    */
+    pub fn with_log_level(mut self, level: Option<Level>) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /**
+    Sets whether [crate::assert_schedule_snapshot] overwrites its snapshots instead of comparing against them.
+    */
+    pub fn with_update_snapshots(mut self, update: bool) -> Self {
+        self.update_snapshots = update;
+        self
+    }
+
+    /**
+    Sets whether spawned tasks are registered with [crate::debug], so that they are listed by
+    [crate::debug::running_tasks] and checked by [crate::debug::assert_all_tasks_complete_guard].
+
+    Registering costs a lock and an allocation per spawn, and an atomic store and a clock read per poll.
+    */
+    pub fn with_task_tracking(mut self, track: bool) -> Self {
+        self.task_tracking = track;
+        self
+    }
+
+    /**
+    Makes this the configuration for the rest of the process, overriding the environment.
+    */
+    pub fn install(self) {
+        *INSTALLED.write().unwrap_or_else(|e| e.into_inner()) = Some(self);
+        LOG_LEVEL.store(encode_level(self.log_level), Ordering::Relaxed);
+        TASK_TRACKING.store(encode_flag(self.task_tracking), Ordering::Relaxed);
+    }
+
+    /**
+    The timeout of an `#[async_test]` that sets none, or `None` for no timeout.
+    */
+    pub fn default_timeout(&self) -> Option<Duration> {
+        self.default_timeout
+    }
+
+    /**
+    The strategy [crate::blocking::default_executor] returns.
+    */
+    pub fn executor(&self) -> Strategy {
+        self.executor
+    }
+
+    /**
+    How many times the `adaptive` strategy checks for a wake before parking.
+    */
+    pub fn spins(&self) -> u32 {
+        self.spins
+    }
+
+    /**
+    How the `sleep` strategy waits between polls.
+    */
+    pub fn park_policy(&self) -> ParkPolicy {
+        self.park_policy
+    }
+
+    /**
+    The only seed chaos tests run under, if one is set.
+    */
+    pub fn chaos_seed(&self) -> Option<u64> {
+        self.chaos_seed
+    }
+
+    /**
+    The least severe level of the executors' messages that is logged, or `None` if nothing is.
+    */
+    pub fn log_level(&self) -> Option<Level> {
+        self.log_level
+    }
+
+    /**
+    Whether [crate::assert_schedule_snapshot] overwrites its snapshots instead of comparing against them.
+    */
+    pub fn update_snapshots(&self) -> bool {
+        self.update_snapshots
+    }
+
+    /**
+    Whether spawned tasks are registered with [crate::debug].
+    */
+    pub fn task_tracking(&self) -> bool {
+        self.task_tracking
+    }
+}
+
+/**
+The configuration in effect: the one installed with [Config::install], or failing that [Config::from_env].
+*/
+pub fn current() -> Config {
+    let installed = *INSTALLED.read().unwrap_or_else(|e| e.into_inner());
+    installed.unwrap_or_else(|| *FROM_ENV.get_or_init(Config::from_env))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static INSTALLED: RwLock<Option<Config>> = RwLock::new(None);
+static FROM_ENV: OnceLock<Config> = OnceLock::new();
+
+//the log level, checked on every message, so kept apart from the rest: 0 until known, then encode_level
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(0);
+//likewise whether to track tasks, checked on every spawn: 0 until known, then encode_flag
+static TASK_TRACKING: AtomicU8 = AtomicU8::new(0);
+
+/**
+The configuration in effect, like [current], except that an invalid environment is left for an explicit read to
+report instead of panicking.
+*/
+fn lenient() -> Config {
+    let installed = *INSTALLED.read().unwrap_or_else(|e| e.into_inner());
+    installed.or_else(|| FROM_ENV.get().copied()).unwrap_or_else(|| Config::try_from_env().unwrap_or_default())
+}
+
+fn encode_level(level: Option<Level>) -> u8 {
+    match level {
+        None => 1,
+        Some(Level::Trace) => 2,
+        Some(Level::Info) => 3,
+        Some(Level::Warning) => 4,
+    }
+}
+
+/**
+Whether the executors' messages at `level` are logged at all.
+*/
+#[cfg(any(feature = "logwise", feature = "tracing", feature = "log-capture"))]
+pub(crate) fn log_enabled(level: Level) -> bool {
+    let mut encoded = LOG_LEVEL.load(Ordering::Relaxed);
+    if encoded == 0 {
+        encoded = encode_level(lenient().log_level());
+        LOG_LEVEL.store(encoded, Ordering::Relaxed);
+    }
+    encoded != 1 && encode_level(Some(level)) >= encoded
+}
+
+fn encode_flag(flag: bool) -> u8 {
+    if flag { 2 } else { 1 }
+}
+
+/**
+Whether a task being spawned should be registered with [crate::debug].
+*/
+pub(crate) fn task_tracking() -> bool {
+    let mut encoded = TASK_TRACKING.load(Ordering::Relaxed);
+    if encoded == 0 {
+        encoded = encode_flag(lenient().task_tracking());
+        TASK_TRACKING.store(encoded, Ordering::Relaxed);
+    }
+    encoded == 2
+}
+
+/**
+Parses a timeout, returning `Some(None)` for `none`.
+*/
+fn parse_timeout(value: &str) -> Option<Option<Duration>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        Some(None)
+    } else if let Some(millis) = value.strip_suffix("ms") {
+        millis.parse().ok().map(|millis| Some(Duration::from_millis(millis)))
+    } else {
+        value.strip_suffix('s').unwrap_or(value).parse().ok().map(|secs| Some(Duration::from_secs(secs)))
+    }
+}
+
+/**
+Parses a log level, returning `Some(None)` for `off`.
+*/
+fn parse_level(value: &str) -> Option<Option<Level>> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "trace" => Some(Some(Level::Trace)),
+        "info" => Some(Some(Level::Info)),
+        "warn" | "warning" => Some(Some(Level::Warning)),
+        "off" | "none" => Some(None),
+        _ => None,
+    }
+}
+
+/**
+Parses a switch.
+*/
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{parse_timeout, Config};
+    use crate::ParkPolicy;
+    use crate::blocking::Strategy;
+    use crate::log_capture::Level;
+
+    #[test]
+    fn parses_default_timeout() {
+        assert_eq!(parse_timeout("30"), Some(Some(Duration::from_secs(30))));
+        assert_eq!(parse_timeout("5s"), Some(Some(Duration::from_secs(5))));
+        assert_eq!(parse_timeout("250ms"), Some(Some(Duration::from_millis(250))));
+        assert_eq!(parse_timeout(" None "), Some(None));
+        assert_eq!(parse_timeout("soon"), None);
+    }
+
+    #[test]
+    fn reads_variables() {
+        let vars = [
+            ("TEST_EXECUTORS_DEFAULT_TIMEOUT", "none"),
+            ("TEST_EXECUTOR", "adaptive"),
+            ("TEST_EXECUTORS_SPINS", "10"),
+            ("TEST_EXECUTORS_PARK_TIMEOUT", "5ms"),
+            ("TEST_EXECUTORS_CHAOS_SEED", "7"),
+            ("TEST_EXECUTORS_LOG", "warn"),
+            ("TEST_EXECUTORS_UPDATE_SNAPSHOTS", "1"),
+            ("TEST_EXECUTORS_TRACK_TASKS", "0"),
+        ];
+        let lookup = |var: &str| vars.iter().find(|(name, _)| *name == var).map(|(_, value)| value.to_string());
+        let expected = Config::new()
+            .with_default_timeout(None)
+            .with_executor(Strategy::Adaptive)
+            .with_spins(10)
+            .with_park_policy(ParkPolicy::ParkTimeout(Duration::from_millis(5)))
+            .with_chaos_seed(Some(7))
+            .with_log_level(Some(Level::Warning))
+            .with_update_snapshots(true)
+            .with_task_tracking(false);
+        assert_eq!(Config::from_lookup(lookup), Ok(expected));
+        let err = Config::from_lookup(|var| (var == "TEST_EXECUTORS_SPINS").then(|| "lots".to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "TEST_EXECUTORS_SPINS is `lots`; expected a number of spins");
+    }
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Cooperative poll budgets, for finding tasks that starve their siblings.
+
+On a single-threaded runtime, a task only lets the other tasks run when it returns `Pending`.  A task that awaits
+a chain of futures which are always ready (a channel that always has a message, say) never does, and its siblings
+silently stop making progress.
+
+[crate::current_thread::CurrentThreadRuntime::poll_budget] gives every poll of a task a budget.  Each
+[consume_budget] during that poll spends one unit, and a poll that overspends panics, naming the task.  Put
+`consume_budget().await` in the loops or leaf futures you suspect.  Outside a runtime with a budget,
+consume_budget does nothing.
+
+A budget catches a task that hogs the thread; a scheduler can also starve a task by never picking it.  With
+[crate::current_thread::CurrentThreadRuntime::detect_starvation] (or the same method on the other single-threaded
+runtimes), a task that stays woken while the runtime polls others more than a limit is reported as
+[crate::error::Starved], as an [OnStarvation] says.
+*/
+
+use std::cell::Cell;
+use crate::error::BudgetExceeded;
+
+/**
+What a runtime detecting starvation does about a starved task.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OnStarvation {
+    /// Log a warning, once each time the task is starved.
+    Warn,
+    /// Panic, failing the test.
+    Panic,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Budget {
+    task: usize,
+    limit: u32,
+    spent: u32,
+}
+
+thread_local! {
+    static BUDGET: Cell<Option<Budget>> = const { Cell::new(None) };
+}
+
+/**
+Gives the poll of `task` a fresh budget until dropped, restoring the previous one (for nested runtimes).
+*/
+pub(crate) struct BudgetGuard(Option<Budget>);
+
+impl BudgetGuard {
+    pub(crate) fn enter(task: usize, limit: u32) -> Self {
+        BudgetGuard(BUDGET.with(|budget| budget.replace(Some(Budget { task, limit, spent: 0 }))))
+    }
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        BUDGET.with(|budget| budget.set(self.0));
+    }
+}
+
+/**
+Spends one unit of the current task's poll budget.
+
+# Panics
+With a [BudgetExceeded] message, if the current poll has already spent its whole budget, that is, the task has
+gone that many steps without returning `Pending`.
+*/
+pub async fn consume_budget() {
+    BUDGET.with(|budget| {
+        if let Some(mut current) = budget.get() {
+            current.spent += 1;
+            if current.spent > current.limit {
+                panic!("{}", BudgetExceeded::new(crate::scheduler::label(current.task), current.limit));
+            }
+            budget.set(Some(current));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::consume_budget;
+
+    #[test]
+    fn unbudgeted_is_free() {
+        crate::spin_on(async {
+            for _ in 0..1_000 {
+                consume_budget().await;
+            }
+        });
+    }
+}
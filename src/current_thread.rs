@@ -0,0 +1,306 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A cooperative, single-threaded runtime.
+
+[CurrentThreadRuntime::block_on] drives a future on the current thread together with the sibling tasks spawned
+while it runs, via [crate::spawn_local_task].  Tasks are polled in the order they were woken, and need not be
+`Send`, so actor-like code that spawns children as it processes messages can be tested without threads.
+
+With [CurrentThreadRuntime::round_robin], tasks are instead polled in a fixed rotation by id, independent of the
+order of their wakes: after task `n`, the runtime polls the woken task with the next higher id, wrapping around to
+the lowest.  The future passed to `block_on` is task 0, and spawned tasks are numbered from 1 in the order they
+were spawned, so the polling order, and any output a test asserts on, is the same on every platform and however
+wakes from other threads race.
+
+For a runtime that varies the order instead, see [crate::deterministic::DeterministicRuntime].  To find tasks that
+never return `Pending` and so starve their siblings, see [CurrentThreadRuntime::poll_budget].  To assert on what the
+runtime did, see [CurrentThreadRuntime::record_events].
+*/
+
+use std::collections::VecDeque;
+use std::future::IntoFuture;
+use std::sync::Arc;
+use crate::coop::OnStarvation;
+use crate::error::Deadlock;
+use crate::event_log::EventLog;
+use crate::hooks::ExecutorHooks;
+use crate::scheduler::Scheduler;
+
+/**
+A single-threaded runtime that polls woken tasks in the order they were woken.
+*/
+pub struct CurrentThreadRuntime {
+    scheduler: Scheduler,
+    budget: Option<u32>,
+    round_robin: bool,
+}
+
+impl CurrentThreadRuntime {
+    pub fn new() -> Self {
+        CurrentThreadRuntime {
+            scheduler: Scheduler::new(),
+            budget: None,
+            round_robin: false,
+        }
+    }
+
+    /**
+    Gives every poll of a task a budget of `budget` calls to [crate::coop::consume_budget], panicking if a task
+    spends more before returning `Pending`.
+    */
+    pub fn poll_budget(mut self, budget: u32) -> Self {
+        self.budget = Some(budget);
+        self.scheduler.set_budget(self.budget);
+        self
+    }
+
+    /**
+    Detects a task that stays woken while the runtime polls other tasks more than `polls` times, and reports it
+    as [crate::error::Starved], as `action` says.
+    */
+    pub fn detect_starvation(mut self, polls: u64, action: OnStarvation) -> Self {
+        self.scheduler.set_starvation(Some((polls, action)));
+        self
+    }
+
+    /**
+    Reports each poll, wake and completion of a task to `hooks`.
+    */
+    pub fn with_hooks(mut self, hooks: Arc<dyn ExecutorHooks>) -> Self {
+        self.scheduler.set_hooks(Some(hooks));
+        self
+    }
+
+    /**
+    Polls woken tasks in strict round-robin order by id, rather than in the order they were woken.
+    */
+    pub fn round_robin(mut self) -> Self {
+        self.round_robin = true;
+        self
+    }
+
+    /**
+    Records an [crate::event_log::Event] whenever a task is spawned, polled, woken or completed, until the
+    events are taken with [CurrentThreadRuntime::take_events].
+    */
+    pub fn record_events(mut self) -> Self {
+        self.scheduler.set_recording(true);
+        self
+    }
+
+    /**
+    Removes and returns the events recorded so far.  The log is empty unless [CurrentThreadRuntime::record_events]
+    was called.
+    */
+    pub fn take_events(&mut self) -> EventLog {
+        EventLog::new(self.scheduler.take_events())
+    }
+
+    /**
+    Runs `future`, and any tasks spawned onto this runtime, until `future` completes.
+
+    Tasks that are still running when `future` completes are dropped.
+    */
+    pub fn block_on<F: IntoFuture>(&mut self, future: F) -> F::Output {
+        let mut policy = Policy::new(self.round_robin);
+        self.scheduler.block_on(future.into_future(), &mut |ready| policy.pick(ready))
+    }
+
+    /**
+    Like [CurrentThreadRuntime::block_on], but returns a [Deadlock] instead of waiting forever once every task,
+    including `future`, is waiting and none can be woken, because no copy of any of their wakers is held.
+
+    A task that holds another's waker (awaiting its join handle, say) counts as able to wake it, even if that task
+    itself can never run again, so not every deadlock is detected.
+    */
+    #[track_caller]
+    pub fn try_block_on<F: IntoFuture>(&mut self, future: F) -> Result<F::Output, Deadlock> {
+        let location = std::panic::Location::caller();
+        let mut policy = Policy::new(self.round_robin);
+        self.scheduler.try_block_on(future.into_future(), &mut |ready| policy.pick(ready), true)
+            .map_err(|deadlock| deadlock.called_at(Some(location)))
+    }
+}
+
+/**
+Picks the next task to poll: the first woken, or the next in the rotation.
+*/
+struct Policy {
+    //the task polled last, if round robin
+    round_robin: Option<usize>,
+}
+
+impl Policy {
+    fn new(round_robin: bool) -> Self {
+        //every id is at most usize::MAX, so the first pick wraps around to the lowest
+        Policy { round_robin: round_robin.then_some(usize::MAX) }
+    }
+
+    fn pick(&mut self, ready: &VecDeque<usize>) -> Option<usize> {
+        let Some(last) = self.round_robin.as_mut() else {
+            return Some(0);
+        };
+        let (index, &id) = ready.iter().enumerate().min_by_key(|&(_, &id)| (id <= *last, id))?;
+        *last = id;
+        Some(index)
+    }
+}
+
+impl Default for CurrentThreadRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for CurrentThreadRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurrentThreadRuntime")
+            .field("budget", &self.budget)
+            .field("round_robin", &self.round_robin)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::CurrentThreadRuntime;
+
+    #[test]
+    fn actor_spawns_children() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let actor_log = log.clone();
+        let total = CurrentThreadRuntime::new().block_on(async move {
+            let mut children = Vec::new();
+            for message in 1..=3 {
+                let log = actor_log.clone();
+                children.push(crate::spawn_local_task(async move {
+                    log.borrow_mut().push(message);
+                    message * 10
+                }));
+            }
+            let mut total = 0;
+            for child in children {
+                total += child.await;
+            }
+            total
+        });
+        assert_eq!(total, 60);
+        assert_eq!(*log.borrow(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn records_events() {
+        use crate::event_log::{Event, PollResult};
+        let mut runtime = CurrentThreadRuntime::new().record_events();
+        runtime.block_on(async {
+            let child = crate::spawn_local_task(async {});
+            child.await;
+        });
+        let log = runtime.take_events();
+        assert_eq!(log.events(), [
+            Event::spawned("main"),
+            Event::polled("main", PollResult::Pending),
+            Event::spawned("task 1"),
+            //the child wakes main during its poll
+            Event::woken("main"),
+            Event::polled("task 1", PollResult::Ready),
+            Event::completed("task 1"),
+            Event::polled("main", PollResult::Ready),
+            Event::completed("main"),
+        ]);
+        assert!(log.happened_before(&Event::completed("task 1"), &Event::completed("main")));
+        assert_eq!(log.polls_of("main"), 2);
+        assert!(runtime.take_events().events().is_empty());
+    }
+
+    #[test]
+    fn detects_deadlock() {
+        let line = line!() + 1;
+        let deadlock = CurrentThreadRuntime::new().try_block_on(async {
+            let _sibling = crate::spawn_local_task(std::future::pending::<()>());
+            std::future::pending::<()>().await
+        }).unwrap_err();
+        assert_eq!(deadlock.tasks(), ["main", "task 1"]);
+        assert_eq!(deadlock.polls(), 2);
+        assert_eq!(deadlock.location().map(|location| (location.file(), location.line())), Some((file!(), line)));
+        assert_eq!(CurrentThreadRuntime::new().try_block_on(async { crate::spawn_local_task(async { 3 }).await }), Ok(3));
+    }
+
+    #[test]
+    fn round_robin_ignores_wake_order() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let run = |runtime: CurrentThreadRuntime| {
+            order.borrow_mut().clear();
+            let mut runtime = runtime;
+            let log = order.clone();
+            runtime.block_on(async move {
+                let mut senders = Vec::new();
+                let mut children = Vec::new();
+                for id in 1..=3 {
+                    let (sender, receiver) = crate::local::channel::<()>();
+                    let log = log.clone();
+                    senders.push(sender);
+                    children.push(crate::spawn_local_task(async move {
+                        receiver.await;
+                        log.borrow_mut().push(id);
+                    }));
+                }
+                //twice, so that under either order every child has been polled and is waiting
+                crate::future_util::yield_now().await;
+                crate::future_util::yield_now().await;
+                //wake them in reverse
+                for sender in senders.into_iter().rev() {
+                    sender.send(());
+                }
+                for child in children {
+                    child.await;
+                }
+            });
+            order.borrow().clone()
+        };
+        assert_eq!(run(CurrentThreadRuntime::new()), [3, 2, 1]);
+        assert_eq!(run(CurrentThreadRuntime::new().round_robin()), [1, 2, 3]);
+    }
+
+    #[test]
+    fn fifo_does_not_starve_yielding_tasks() {
+        CurrentThreadRuntime::new().detect_starvation(2, crate::coop::OnStarvation::Panic).block_on(async {
+            let siblings: Vec<_> = (0..2).map(|_| crate::spawn_local_task(async {
+                for _ in 0..10 {
+                    crate::future_util::yield_now().await;
+                }
+            })).collect();
+            for sibling in siblings {
+                sibling.await;
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "spent its poll budget of 8")]
+    fn flags_task_that_never_yields() {
+        CurrentThreadRuntime::new().poll_budget(8).block_on(async {
+            let sibling = crate::spawn_local_task(async {});
+            //always ready, so the sibling never gets to run
+            while !sibling.is_finished() {
+                crate::coop::consume_budget().await;
+            }
+        });
+    }
+
+    #[test]
+    fn budget_resets_on_pending() {
+        CurrentThreadRuntime::new().poll_budget(8).block_on(async {
+            let sibling = crate::spawn_local_task(async {});
+            for _ in 0..8 {
+                crate::coop::consume_budget().await;
+            }
+            sibling.await;
+            for _ in 0..8 {
+                crate::coop::consume_budget().await;
+            }
+        });
+    }
+}
@@ -0,0 +1,365 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A snapshot of the tasks that are still alive, for working out why a test hangs.
+
+Tasks spawned through the runtimes in [crate::aruntime], [crate::priority::PriorityRuntime] and [crate::spawn_on]
+are registered from the moment they are spawned until they complete or are dropped, unless
+[crate::config::Config::with_task_tracking] turns this off.  [running_tasks] lists them, with their label, whether
+they are being polled, when they were last polled and where they were spawned:
+
+```
+use std::time::Duration;
+use some_executor::SomeExecutor;
+use some_executor::task::{Configuration, Task};
+
+let task = Task::<_, std::convert::Infallible>::without_notifications("stuck".to_string(), async {
+    std::thread::sleep(Duration::from_millis(100));
+}, Configuration::default());
+let _observer = test_executors::aruntime::SpawnRuntime::new().spawn(task);
+std::thread::sleep(Duration::from_millis(20));
+let tasks = test_executors::debug::running_tasks();
+assert!(tasks.iter().any(|task| task.label() == "stuck"));
+for task in tasks {
+    eprintln!("{task}");
+}
+```
+
+An `#[async_test]` that times out prints this list after the timeout message.  The tasks of other tests running
+in the same process are listed too.
+
+[assert_all_tasks_complete_guard] fails a test that leaves tasks running when it ends:
+
+```
+use some_executor::SomeExecutor;
+use some_executor::task::{Configuration, Task};
+
+let guard = test_executors::debug::assert_all_tasks_complete_guard();
+let task = Task::<_, std::convert::Infallible>::without_notifications("finishes".to_string(), async {}, Configuration::default());
+test_executors::observation::resolve_observer(test_executors::aruntime::SpawnRuntime::new().spawn(task));
+//checks that "finishes" is gone
+drop(guard);
+```
+*/
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::ThreadId;
+use std::time::Duration;
+use crate::sys::time::Instant;
+
+/**
+Where a task is in its life, as of a [TaskSnapshot].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TaskState {
+    /// Spawned, but not polled yet.
+    NotStarted,
+    /// Being polled right now.
+    Polling,
+    /// Returned `Pending` from its last poll, and waiting to be woken.
+    Pending,
+}
+
+impl Display for TaskState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TaskState::NotStarted => "not started",
+            TaskState::Polling => "polling",
+            TaskState::Pending => "pending",
+        })
+    }
+}
+
+/**
+A task that was still alive when [running_tasks] was called.
+*/
+#[derive(Debug, Clone)]
+pub struct TaskSnapshot {
+    label: String,
+    state: TaskState,
+    last_poll: Option<Instant>,
+    spawned_at: &'static Location<'static>,
+}
+
+impl TaskSnapshot {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn state(&self) -> TaskState {
+        self.state
+    }
+
+    /**
+    When the task's last poll began, if it has been polled.
+    */
+    pub fn last_poll(&self) -> Option<Instant> {
+        self.last_poll
+    }
+
+    /**
+    Where the task was spawned.
+    */
+    pub fn spawned_at(&self) -> &'static Location<'static> {
+        self.spawned_at
+    }
+}
+
+impl Display for TaskSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` ({}", self.label, self.state)?;
+        if let Some(last_poll) = self.last_poll {
+            write!(f, ", last polled {:?} ago", last_poll.elapsed())?;
+        }
+        write!(f, "), spawned at {}", self.spawned_at)
+    }
+}
+
+/**
+A task's entry, shared between the registry and the task's [Registration], so that polls update it with atomic
+stores instead of taking a lock.
+*/
+struct Entry {
+    label: String,
+    spawned_at: &'static Location<'static>,
+    //one of the STATE_ constants
+    state: AtomicU8,
+    //when the last poll began, as nanoseconds since EPOCH plus one, or 0 before the first poll
+    last_poll: AtomicU64,
+}
+
+const STATE_NOT_STARTED: u8 = 0;
+const STATE_POLLING: u8 = 1;
+const STATE_PENDING: u8 = 2;
+
+//the instant that Entry::last_poll counts from
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+impl Entry {
+    fn snapshot(&self) -> TaskSnapshot {
+        let state = match self.state.load(Ordering::Relaxed) {
+            STATE_NOT_STARTED => TaskState::NotStarted,
+            STATE_POLLING => TaskState::Polling,
+            _ => TaskState::Pending,
+        };
+        let last_poll = match self.last_poll.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(*EPOCH.get_or_init(Instant::now) + Duration::from_nanos(nanos - 1)),
+        };
+        TaskSnapshot { label: self.label.clone(), state, last_poll, spawned_at: self.spawned_at }
+    }
+
+    fn begin_poll(&self) {
+        let since_epoch = Instant::now().duration_since(*EPOCH.get_or_init(Instant::now));
+        self.last_poll.store(u64::try_from(since_epoch.as_nanos()).unwrap_or(u64::MAX - 1) + 1, Ordering::Relaxed);
+        self.state.store(STATE_POLLING, Ordering::Relaxed);
+    }
+
+    fn end_poll(&self) {
+        self.state.store(STATE_PENDING, Ordering::Relaxed);
+    }
+}
+
+struct Registry {
+    next_id: u64,
+    //keyed by the order the tasks were spawned, with the thread that spawned them
+    tasks: BTreeMap<u64, (ThreadId, Arc<Entry>)>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry { next_id: 0, tasks: BTreeMap::new() });
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    //a panic while the lock is held cannot leave the map inconsistent
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/**
+The tasks spawned through this crate's runtimes that have neither completed nor been dropped, oldest first.
+
+Empty when [crate::config::Config::with_task_tracking] is off.
+*/
+pub fn running_tasks() -> Vec<TaskSnapshot> {
+    registry().tasks.values().map(|(_, entry)| entry.snapshot()).collect()
+}
+
+/**
+How long a [TaskLeakGuard] waits for tasks to finish before failing, since a task is still listed briefly after
+its observer or join handle has its output.
+*/
+const LEAK_GRACE: Duration = Duration::from_millis(100);
+
+/**
+Fails the test, when dropped, if tasks spawned on this thread since it was created are still running.
+
+Returned by [assert_all_tasks_complete_guard].
+*/
+#[derive(Debug)]
+#[must_use = "the guard checks for running tasks when it is dropped"]
+pub struct TaskLeakGuard {
+    first_id: u64,
+    thread: ThreadId,
+    grace: Duration,
+}
+
+impl TaskLeakGuard {
+    /**
+    Waits up to `grace` for the tasks to finish, instead of 100ms, before failing.
+    */
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    fn leaked(&self) -> Vec<TaskSnapshot> {
+        registry().tasks.range(self.first_id..)
+            .filter(|(_, (thread, _))| *thread == self.thread)
+            .map(|(_, (_, entry))| entry.snapshot())
+            .collect()
+    }
+}
+
+impl Drop for TaskLeakGuard {
+    fn drop(&mut self) {
+        //the test is already failing, and a second panic would abort
+        if std::thread::panicking() {
+            return;
+        }
+        let deadline = Instant::now() + self.grace;
+        let mut leaked = self.leaked();
+        while !leaked.is_empty() && Instant::now() < deadline {
+            crate::sys::sleep(Duration::from_millis(1));
+            leaked = self.leaked();
+        }
+        if !leaked.is_empty() {
+            let mut message = match leaked.len() {
+                1 => "1 task spawned during the test is still running:".to_string(),
+                count => format!("{count} tasks spawned during the test are still running:"),
+            };
+            for task in leaked {
+                message.push_str(&format!("\n  {task}"));
+            }
+            panic!("{message}");
+        }
+    }
+}
+
+/**
+Returns a guard that fails the test, when it is dropped at the end of the test, if tasks spawned through this
+crate's runtimes since then have not finished.
+
+Only tasks spawned from the calling thread are checked, so that the tasks of tests running in parallel are not
+blamed on this one.  Tasks spawned from inside those tasks, on other threads, are not checked either, and
+nothing is checked when [crate::config::Config::with_task_tracking] is off.
+*/
+pub fn assert_all_tasks_complete_guard() -> TaskLeakGuard {
+    TaskLeakGuard { first_id: registry().next_id, thread: std::thread::current().id(), grace: LEAK_GRACE }
+}
+
+/**
+A task's entry in [running_tasks], removed when this is dropped, or nothing if tasks are not being tracked.
+*/
+pub(crate) struct Registration {
+    entry: Option<(u64, Arc<Entry>)>,
+}
+
+impl Registration {
+    pub(crate) fn new(label: &str, spawned_at: &'static Location<'static>) -> Self {
+        if !crate::config::task_tracking() {
+            return Registration { entry: None };
+        }
+        EPOCH.get_or_init(Instant::now);
+        let entry = Arc::new(Entry {
+            label: label.to_string(),
+            spawned_at,
+            state: AtomicU8::new(STATE_NOT_STARTED),
+            last_poll: AtomicU64::new(0),
+        });
+        let mut registry = registry();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.tasks.insert(id, (std::thread::current().id(), entry.clone()));
+        Registration { entry: Some((id, entry)) }
+    }
+
+    /**
+    Wraps the task's future, so that its polls are reflected in its entry, which lasts until the future completes or
+    is dropped.
+    */
+    pub(crate) async fn track<F: Future>(self, future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        std::future::poll_fn(|cx| {
+            let entry = self.entry.as_ref().map(|(_, entry)| entry);
+            if let Some(entry) = entry {
+                entry.begin_poll();
+            }
+            let poll = future.as_mut().poll(cx);
+            if let Some(entry) = entry {
+                entry.end_poll();
+            }
+            poll
+        }).await
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some((id, _)) = &self.entry {
+            registry().tasks.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+    use super::{assert_all_tasks_complete_guard, running_tasks, Registration, TaskState};
+
+    fn find(label: &str) -> Option<super::TaskSnapshot> {
+        running_tasks().into_iter().find(|task| task.label() == label)
+    }
+
+    #[test]
+    fn lists_task_until_dropped() {
+        let spawned_at = Location::caller();
+        let mut tracked = Box::pin(Registration::new("debug::lists_task_until_dropped", spawned_at).track(crate::future_util::yield_now()));
+        let task = find("debug::lists_task_until_dropped").expect("listed once spawned");
+        assert_eq!(task.state(), TaskState::NotStarted);
+        assert_eq!(task.spawned_at(), spawned_at);
+        assert!(crate::poll_once(tracked.as_mut()).is_pending());
+        let task = find("debug::lists_task_until_dropped").expect("listed while pending");
+        assert_eq!(task.state(), TaskState::Pending);
+        assert!(task.last_poll().is_some());
+        drop(tracked);
+        assert!(find("debug::lists_task_until_dropped").is_none());
+    }
+
+    #[test]
+    fn reports_where_runtime_task_was_spawned() {
+        use some_executor::SomeExecutor;
+        use some_executor::task::{Configuration, Task};
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let task = Task::<_, std::convert::Infallible>::without_notifications("debug::reports_where".to_string(), async move {
+            crate::spawn_blocking(move || receiver.recv()).await.unwrap();
+        }, Configuration::default());
+        let line = line!() + 1;
+        let _observer = crate::aruntime::SpawnRuntime::new().spawn(task);
+        let task = find("debug::reports_where").expect("listed while running");
+        assert_eq!((task.spawned_at().file(), task.spawned_at().line()), (file!(), line));
+        sender.send(()).unwrap();
+    }
+
+    #[test]
+    fn guard_fails_on_running_task() {
+        let guard = assert_all_tasks_complete_guard().with_grace(std::time::Duration::from_millis(5));
+        let tracked = Registration::new("debug::guard_fails", Location::caller()).track(crate::pend_forever::PendForever);
+        let panic = std::panic::catch_unwind(move || drop(guard)).unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("1 task spawned during the test is still running:\n  `debug::guard_fails` (not started)"), "{message}");
+        drop(tracked);
+        drop(assert_all_tasks_complete_guard());
+    }
+}
@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A single-threaded runtime whose scheduling is decided by a seed.
+
+[DeterministicRuntime::block_on] drives a future together with any tasks [spawn]ed while it runs.  Whenever more
+than one of them has been woken, the next one to poll is picked by a pseudo-random number generator seeded with
+the runtime's seed.  Running a test under many seeds therefore explores many interleavings of its tasks, and
+running it again under a failing seed reproduces the interleaving (as long as the test itself is deterministic,
+and is not woken from other threads).
+
+`#[async_test(chaos_seeds = N)]` runs a test under seeds `0..N` and reports which seed failed.
+
+When a test is woken from other threads, the seed alone does not pin down the interleaving, since which tasks are
+woken by the time the runtime picks one depends on timing.  [DeterministicRuntime::block_on_recording] records
+the order in which tasks were polled as a [Trace], which can be printed (from CI, say), parsed back, and given to
+[DeterministicRuntime::replay] to poll the tasks in exactly that order again.
+*/
+
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::coop::OnStarvation;
+use crate::event_log::{EventLog, TaskId};
+use crate::hooks::ExecutorHooks;
+use crate::rng::Rng;
+use crate::scheduler::Scheduler;
+
+/**
+Spawns `future` onto the [DeterministicRuntime] that is running on this thread, returning its id.
+
+The task runs until it completes or until [DeterministicRuntime::block_on] returns, whichever happens first.
+
+# Panics
+If no DeterministicRuntime is running on this thread.
+*/
+pub fn spawn<F: Future<Output = ()> + 'static>(future: F) -> TaskId {
+    match crate::scheduler::spawn_current(Box::pin(future)) {
+        Ok(id) => id,
+        Err(_) => panic!("deterministic::spawn called outside of DeterministicRuntime::block_on"),
+    }
+}
+
+/**
+A single-threaded runtime that polls woken tasks in an order decided by its seed.
+
+Tasks need not be `Send`, and the runtime itself is not `Send`.
+*/
+pub struct DeterministicRuntime {
+    seed: u64,
+    rng: Rng,
+    scheduler: Scheduler,
+}
+
+impl DeterministicRuntime {
+    /**
+    Creates a runtime that schedules according to `seed`.
+    */
+    pub fn new(seed: u64) -> Self {
+        DeterministicRuntime {
+            seed,
+            rng: Rng::new(seed),
+            scheduler: Scheduler::new(),
+        }
+    }
+
+    /**
+    Detects a task that stays woken while the runtime polls other tasks more than `polls` times, and reports it
+    as [crate::error::Starved], as `action` says.
+    */
+    pub fn detect_starvation(mut self, polls: u64, action: OnStarvation) -> Self {
+        self.scheduler.set_starvation(Some((polls, action)));
+        self
+    }
+
+    /**
+    Reports each poll, wake and completion of a task to `hooks`.
+    */
+    pub fn with_hooks(mut self, hooks: Arc<dyn ExecutorHooks>) -> Self {
+        self.scheduler.set_hooks(Some(hooks));
+        self
+    }
+
+    /**
+    Records an [crate::event_log::Event] whenever a task is spawned, polled, woken or completed, until the
+    events are taken with [DeterministicRuntime::take_events].
+    */
+    pub fn record_events(mut self) -> Self {
+        self.scheduler.set_recording(true);
+        self
+    }
+
+    /**
+    Removes and returns the events recorded so far.  The log is empty unless [DeterministicRuntime::record_events]
+    was called.
+    */
+    pub fn take_events(&mut self) -> EventLog {
+        EventLog::new(self.scheduler.take_events())
+    }
+
+    /**
+    The seed this runtime was created with.
+    */
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /**
+    Runs `future`, and any tasks it [spawn]s, until `future` completes.
+
+    Tasks that are still running when `future` completes are dropped.
+    */
+    pub fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+        let rng = &mut self.rng;
+        self.scheduler.block_on(future, &mut |ready| Some(rng.below(ready.len())))
+    }
+
+    /**
+    Like [DeterministicRuntime::block_on], but also returns the order in which the tasks were polled.
+    */
+    pub fn block_on_recording<F: Future>(&mut self, future: F) -> (F::Output, Trace) {
+        let rng = &mut self.rng;
+        let mut polls = Vec::new();
+        let output = self.scheduler.block_on(future, &mut |ready| {
+            let index = rng.below(ready.len());
+            polls.push(ready[index]);
+            Some(index)
+        });
+        (output, Trace { polls })
+    }
+
+    /**
+    Runs `future`, and any tasks it [spawn]s, polling them in the order recorded in `trace`, until `future`
+    completes.
+
+    When the next task in the trace has not been woken yet, the runtime waits for it, so wakes from other
+    threads are replayed in their recorded order too.  The test must otherwise behave as it did while recording:
+    if the task the trace expects is never woken, `replay` waits forever.
+
+    # Panics
+    If the run outlasts the trace.
+    */
+    pub fn replay<F: Future>(trace: &Trace, future: F) -> F::Output {
+        let mut polls = trace.polls.iter();
+        let mut next = None;
+        Scheduler::new().block_on(future, &mut |ready| {
+            let expected = *next.get_or_insert_with(|| *polls.next()
+                .unwrap_or_else(|| panic!("replay diverged: the run continued after the {} polls in its trace", trace.polls.len())));
+            let index = ready.iter().position(|&id| id == expected)?;
+            next = None;
+            Some(index)
+        })
+    }
+}
+
+/**
+The order in which a [DeterministicRuntime] polled its tasks, by task id.
+
+The future passed to the runtime has id 0, and spawned tasks are numbered from 1 in the order they were spawned.
+A trace is displayed as the ids separated by spaces, and can be parsed from that form.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Trace {
+    polls: Vec<usize>,
+}
+
+impl Trace {
+    /**
+    The ids of the tasks polled, in order.
+    */
+    pub fn polls(&self) -> &[usize] {
+        &self.polls
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, id) in self.polls.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{id}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let polls = s.split_whitespace().map(str::parse).collect::<Result<_, _>>()?;
+        Ok(Trace { polls })
+    }
+}
+
+impl std::fmt::Debug for DeterministicRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeterministicRuntime")
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use super::{spawn, DeterministicRuntime, Trace};
+
+    /**
+    Pending once, waking itself, so that other tasks get a chance to run.
+    */
+    async fn yield_now() {
+        let mut yielded = false;
+        std::future::poll_fn(move |cx| {
+            if yielded {
+                std::task::Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                std::task::Poll::Pending
+            }
+        }).await
+    }
+
+    fn interleaving(seed: u64) -> Vec<u32> {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let finished = Rc::new(Cell::new(0));
+        let main_order = order.clone();
+        DeterministicRuntime::new(seed).block_on(async move {
+            for task in 0..4 {
+                let order = main_order.clone();
+                let finished = finished.clone();
+                spawn(async move {
+                    for _ in 0..3 {
+                        order.borrow_mut().push(task);
+                        yield_now().await;
+                    }
+                    finished.set(finished.get() + 1);
+                });
+            }
+            while finished.get() < 4 {
+                yield_now().await;
+            }
+        });
+        Rc::try_unwrap(order).unwrap().into_inner()
+    }
+
+    #[test]
+    fn same_seed_same_schedule() {
+        assert_eq!(interleaving(7), interleaving(7));
+        assert_eq!(interleaving(7).len(), 12);
+    }
+
+    #[test]
+    fn replays_recorded_trace() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let run = |order: Rc<RefCell<Vec<u32>>>| async move {
+            let finished = Rc::new(Cell::new(0));
+            for task in 0..3 {
+                let order = order.clone();
+                let finished = finished.clone();
+                spawn(async move {
+                    //woken from another thread, at a time the seed cannot control
+                    let (sender, receiver) = std::sync::mpsc::channel();
+                    let mut thread = None;
+                    std::future::poll_fn(|cx| {
+                        if receiver.try_recv().is_ok() {
+                            return std::task::Poll::Ready(());
+                        }
+                        if thread.is_none() {
+                            let sender = sender.clone();
+                            let waker = cx.waker().clone();
+                            thread = Some(std::thread::spawn(move || {
+                                sender.send(()).unwrap();
+                                waker.wake();
+                            }));
+                        }
+                        std::task::Poll::Pending
+                    }).await;
+                    order.borrow_mut().push(task);
+                    finished.set(finished.get() + 1);
+                });
+            }
+            while finished.get() < 3 {
+                yield_now().await;
+            }
+        };
+        let ((), trace) = DeterministicRuntime::new(3).block_on_recording(run(order.clone()));
+        let recorded = order.take();
+        let parsed: Trace = trace.to_string().parse().unwrap();
+        assert_eq!(parsed, trace);
+        for _ in 0..4 {
+            DeterministicRuntime::replay(&parsed, run(order.clone()));
+            assert_eq!(order.take(), recorded);
+        }
+    }
+
+    #[test]
+    fn seeds_vary_schedule() {
+        let first = interleaving(0);
+        assert!((1..16).any(|seed| interleaving(seed) != first));
+    }
+}
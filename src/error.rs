@@ -0,0 +1,506 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+The errors returned by this crate's fallible APIs.
+
+Each carries the context needed to debug the failure (how long a future ran, how many times it was polled, which
+task was involved), both through accessors, for tests that assert on them, and in its `Display` message, for tests
+that just `unwrap`.
+*/
+
+use std::fmt::Display;
+use std::time::Duration;
+use some_executor::observer::Observation;
+
+pub use crate::lost_wake::LostWake;
+
+/**
+A future did not complete within its timeout.
+
+Returned by [crate::watchdog] and [crate::time::timeout].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimeoutError {
+    timeout: Duration,
+    elapsed: Duration,
+    polls: u64,
+    task: Option<String>,
+    called_at: Option<&'static std::panic::Location<'static>>,
+}
+
+impl TimeoutError {
+    pub(crate) fn new(timeout: Duration, elapsed: Duration, polls: u64) -> Self {
+        TimeoutError { timeout, elapsed, polls, task: None, called_at: None }
+    }
+
+    /**
+    Records where the executor that gave up was called.
+    */
+    pub(crate) fn called_at(mut self, location: Option<&'static std::panic::Location<'static>>) -> Self {
+        self.called_at = location;
+        self
+    }
+
+    /**
+    Names the task that timed out.
+    */
+    pub fn with_task(mut self, task: impl Into<String>) -> Self {
+        self.task = Some(task.into());
+        self
+    }
+
+    /**
+    The timeout that was exceeded.
+    */
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /**
+    How long the future ran before we gave up on it.
+    */
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /**
+    How many times the future was polled.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /**
+    The label of the task that timed out, if known.
+    */
+    pub fn task(&self) -> Option<&str> {
+        self.task.as_deref()
+    }
+
+    /**
+    Where the executor that gave up, such as [crate::watchdog::sleep_on_timeout], was called, if known.
+    */
+    pub fn location(&self) -> Option<&'static std::panic::Location<'static>> {
+        self.called_at
+    }
+}
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.task {
+            Some(task) => write!(f, "task `{task}`")?,
+            None => write!(f, "future")?,
+        }
+        write!(f, " did not complete within {:?} (gave up after {:?} and {} polls)", self.timeout, self.elapsed, self.polls)?;
+        if self.polls == 1 {
+            write!(f, "; it was never woken after its first poll")?;
+        }
+        if let Some(location) = self.called_at {
+            write!(f, "; waited for at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/**
+A task spent its whole [crate::coop] poll budget without returning `Pending`.
+
+[crate::coop::consume_budget] panics with this error's message.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BudgetExceeded {
+    task: String,
+    limit: u32,
+}
+
+impl BudgetExceeded {
+    pub(crate) fn new(task: impl Into<String>, limit: u32) -> Self {
+        BudgetExceeded { task: task.into(), limit }
+    }
+
+    /**
+    The label of the task, as in [crate::event_log].
+    */
+    pub fn task(&self) -> &str {
+        &self.task
+    }
+
+    /**
+    The budget the task was given for each poll.
+    */
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+}
+
+impl Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} spent its poll budget of {} without returning Pending, starving the other tasks on this runtime", self.task, self.limit)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/**
+A woken task went unpolled while the runtime polled other tasks many times.
+
+Returned as the panic message, or logged as a warning, by a runtime with starvation detection, such as
+[crate::current_thread::CurrentThreadRuntime::detect_starvation].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Starved {
+    task: String,
+    polls: u64,
+}
+
+impl Starved {
+    pub(crate) fn new(task: impl Into<String>, polls: u64) -> Self {
+        Starved { task: task.into(), polls }
+    }
+
+    /**
+    The label of the task: as in [crate::event_log], or the label it was spawned with on a
+    [crate::priority::PriorityRuntime].
+    */
+    pub fn task(&self) -> &str {
+        &self.task
+    }
+
+    /**
+    How many polls of other tasks the runtime made while this one was woken.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+}
+
+impl Display for Starved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} was woken but not polled while the runtime polled other tasks {} times", self.task, self.polls)
+    }
+}
+
+impl std::error::Error for Starved {}
+
+/**
+The thread for a task could not be spawned.
+
+Returned by [crate::try_spawn_on_with].
+*/
+#[derive(Debug)]
+pub struct SpawnError {
+    task: String,
+    source: std::io::Error,
+}
+
+impl SpawnError {
+    pub(crate) fn new(task: impl Into<String>, source: std::io::Error) -> Self {
+        SpawnError { task: task.into(), source }
+    }
+
+    /**
+    The label of the task, which is also the name of its thread.
+    */
+    pub fn task(&self) -> &str {
+        &self.task
+    }
+}
+
+impl Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "can't spawn a thread for task `{}`: {}", self.task, self.source)
+    }
+}
+
+impl std::error::Error for SpawnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/**
+Every task on a runtime is waiting, and none of them can be woken, because no copy of any of their wakers is
+still held.
+
+Returned by [crate::current_thread::CurrentThreadRuntime::try_block_on].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Deadlock {
+    tasks: Vec<String>,
+    polls: u64,
+    elapsed: Duration,
+    called_at: Option<&'static std::panic::Location<'static>>,
+}
+
+impl Deadlock {
+    pub(crate) fn new(tasks: Vec<String>, polls: u64, elapsed: Duration) -> Self {
+        Deadlock { tasks, polls, elapsed, called_at: None }
+    }
+
+    /**
+    Records where the runtime that deadlocked was called.
+    */
+    pub(crate) fn called_at(mut self, location: Option<&'static std::panic::Location<'static>>) -> Self {
+        self.called_at = location;
+        self
+    }
+
+    /**
+    The labels of the waiting tasks, as in [crate::event_log].
+    */
+    pub fn tasks(&self) -> &[String] {
+        &self.tasks
+    }
+
+    /**
+    How many polls the runtime made, across all its tasks, before it deadlocked.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /**
+    How long the runtime ran before it deadlocked.
+    */
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /**
+    Where the runtime, as in [crate::current_thread::CurrentThreadRuntime::try_block_on], was called, if known.
+    */
+    pub fn location(&self) -> Option<&'static std::panic::Location<'static>> {
+        self.called_at
+    }
+}
+
+impl Display for Deadlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadlock after {:?} and {} polls: {} can never be woken, because every copy of their wakers was dropped",
+               self.elapsed, self.polls, self.tasks.join(", "))?;
+        if let Some(location) = self.called_at {
+            write!(f, "; waited for at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Deadlock {}
+
+/**
+A name that is not one of the [crate::blocking::Strategy] names, `spin`, `sleep` or `adaptive`.
+
+Returned by parsing a [crate::blocking::Strategy].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnknownStrategy {
+    name: String,
+}
+
+impl UnknownStrategy {
+    pub(crate) fn new(name: impl Into<String>) -> Self {
+        UnknownStrategy { name: name.into() }
+    }
+
+    /**
+    The name that was parsed.
+    */
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Display for UnknownStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown executor `{}`; expected `spin`, `sleep` or `adaptive`", self.name)
+    }
+}
+
+impl std::error::Error for UnknownStrategy {}
+
+/**
+An environment variable read by [crate::config] holds a value it cannot have.
+
+Returned by [crate::config::Config::try_from_env].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConfigError {
+    var: &'static str,
+    value: String,
+    expected: &'static str,
+}
+
+impl ConfigError {
+    pub(crate) fn new(var: &'static str, value: impl Into<String>, expected: &'static str) -> Self {
+        ConfigError { var, value: value.into(), expected }
+    }
+
+    /**
+    The name of the variable.
+    */
+    pub fn var(&self) -> &str {
+        self.var
+    }
+
+    /**
+    The variable's value.
+    */
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is `{}`; expected {}", self.var, self.value, self.expected)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/**
+A runtime was still running tasks when [crate::aruntime::TrackedSpawnRuntime::wait_idle] gave up on it.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdleTimeout {
+    timeout: Duration,
+    running: usize,
+}
+
+impl IdleTimeout {
+    pub(crate) fn new(timeout: Duration, running: usize) -> Self {
+        IdleTimeout { timeout, running }
+    }
+
+    /**
+    How long we waited.
+    */
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /**
+    How many tasks were still running.
+    */
+    pub fn running(&self) -> usize {
+        self.running
+    }
+}
+
+impl Display for IdleTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} tasks were still running after {:?}", self.running, self.timeout)
+    }
+}
+
+impl std::error::Error for IdleTimeout {}
+
+/**
+The state checked by [crate::retry::run_until] did not satisfy its predicate before the timeout.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Unsatisfied<T> {
+    timeout: Duration,
+    checks: u32,
+    last: Option<T>,
+}
+
+impl<T> Unsatisfied<T> {
+    pub(crate) fn new(timeout: Duration, checks: u32, last: Option<T>) -> Self {
+        Unsatisfied { timeout, checks, last }
+    }
+
+    /**
+    The timeout that was exceeded.
+    */
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /**
+    How many times the state was checked.
+    */
+    pub fn checks(&self) -> u32 {
+        self.checks
+    }
+
+    /**
+    The last state checked, or `None` if the first check did not finish before the timeout.
+    */
+    pub fn last(&self) -> Option<&T> {
+        self.last.as_ref()
+    }
+
+    pub fn into_last(self) -> Option<T> {
+        self.last
+    }
+}
+
+impl<T: std::fmt::Debug> Display for Unsatisfied<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "condition not satisfied within {:?} after {} checks", self.timeout, self.checks)?;
+        match &self.last {
+            Some(last) => write!(f, "; last state: {last:?}"),
+            None => write!(f, "; the first check did not finish"),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for Unsatisfied<T> {}
+
+/**
+A task observation that did not hold the task's output.
+
+Returned by [crate::observation::ObservationExt::into_result].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NotReady {
+    observation: Observation<()>,
+}
+
+impl NotReady {
+    pub(crate) fn new(observation: Observation<()>) -> Self {
+        NotReady { observation }
+    }
+
+    /**
+    What was observed instead of the output: `Pending`, `Done` or `Cancelled`.
+    */
+    pub fn observation(&self) -> &Observation<()> {
+        &self.observation
+    }
+
+    /**
+    Whether the task was cancelled.
+    */
+    pub fn is_cancelled(&self) -> bool {
+        self.observation == Observation::Cancelled
+    }
+}
+
+impl Display for NotReady {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.observation {
+            Observation::Pending => "task has not finished",
+            Observation::Done => "task output was already observed",
+            Observation::Cancelled => "task was cancelled before it completed",
+            Observation::Ready(()) => "task is ready",
+        })
+    }
+}
+
+impl std::error::Error for NotReady {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::TimeoutError;
+
+    #[test]
+    fn timeout_names_task() {
+        let err = TimeoutError::new(Duration::from_millis(5), Duration::from_millis(6), 1).with_task("fetch");
+        assert_eq!(err.task(), Some("fetch"));
+        assert_eq!(err.to_string(), "task `fetch` did not complete within 5ms (gave up after 6ms and 1 polls); it was never woken after its first poll");
+    }
+}
@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A log of what a single-threaded runtime did, for asserting on scheduling behavior.
+
+With [crate::current_thread::CurrentThreadRuntime::record_events] (or the same method of
+[crate::deterministic::DeterministicRuntime]), the runtime records an [Event] whenever a task
+is spawned, polled, woken or completed.  [crate::current_thread::CurrentThreadRuntime::take_events] returns them
+as an [EventLog], whose matchers check for events, or for events in a particular order.
+
+Tasks are labeled by their [TaskId]: `main` (the future passed to `block_on`) and `task N` (the Nth task spawned,
+counting from 1).  Ids are assigned in the order tasks are spawned, starting again each time the runtime runs a
+future, so they are the same from one run of a test to the next.  [crate::spawn_local_task] and
+[crate::deterministic::spawn] return the id of the task they spawn, which can stand in for its label.
+
+```
+use test_executors::current_thread::CurrentThreadRuntime;
+use test_executors::event_log::Event;
+let mut runtime = CurrentThreadRuntime::new().record_events();
+runtime.block_on(async {
+    test_executors::spawn_local_task(async {}).await;
+});
+let log = runtime.take_events();
+assert!(log.contains_sequence(&[Event::spawned("task 1"), Event::completed("task 1"), Event::completed("main")]));
+```
+
+The log also records when each event happened, and how long each poll took.  [EventLog::write_chrome_trace] saves
+this timeline in the Trace Event format read by `about:tracing` and [Perfetto](https://ui.perfetto.dev), with a
+track per task, to see where a slow test spends its time.
+
+# Snapshots
+
+[assert_schedule_snapshot!](crate::assert_schedule_snapshot) compares a log, one event per line and without its
+timings, against a snapshot stored in `snapshots/<name>.schedule` in the crate under test, so that a change to
+the order in which a deterministic runtime polls its tasks fails the test:
+
+```no_run
+use test_executors::current_thread::CurrentThreadRuntime;
+let mut runtime = CurrentThreadRuntime::new().record_events();
+runtime.block_on(async {
+    test_executors::spawn_local_task(async {}).await;
+});
+test_executors::assert_schedule_snapshot!(runtime.take_events(), "spawn_and_await");
+```
+
+Run the tests with `TEST_EXECUTORS_UPDATE_SNAPSHOTS=1` (see [crate::config]) to write the snapshots, the first
+time or after an intended change, and review them like any other change.
+*/
+
+use std::fmt::Display;
+use std::path::Path;
+use std::time::Duration;
+use crate::config::UPDATE_SNAPSHOTS_VAR;
+
+/**
+A task of a single-threaded runtime: the future passed to `block_on`, or the Nth task spawned while it ran.
+
+Only the runtimes that keep their own task list assign these: [crate::current_thread::CurrentThreadRuntime],
+[crate::deterministic::DeterministicRuntime] and [crate::priority::PriorityRuntime].  The runtimes in
+[crate::aruntime], including their objsafe spawns, and [crate::spawn_on] run each task on its own, and
+identify it only by some_executor's `TaskID` or not at all; their observers and handles carry no [TaskId].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    /**
+    The future passed to `block_on`.
+    */
+    pub const MAIN: TaskId = TaskId(0);
+
+    pub(crate) fn new(id: usize) -> Self {
+        TaskId(id as u64)
+    }
+
+    /**
+    0 for [TaskId::MAIN], and N for the Nth task spawned.
+    */
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for TaskId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if *self == TaskId::MAIN {
+            f.write_str("main")
+        } else {
+            write!(f, "task {}", self.0)
+        }
+    }
+}
+
+impl From<TaskId> for String {
+    fn from(id: TaskId) -> Self {
+        id.to_string()
+    }
+}
+
+/**
+What a task returned from `poll`.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PollResult {
+    Ready,
+    Pending,
+}
+
+/**
+Something a runtime did with one of its tasks.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// The task was added to the runtime.
+    Spawned { label: String },
+    /// The task was polled.
+    Polled { label: String, result: PollResult },
+    /// The task's waker was called, possibly from another thread.
+    Woken { label: String },
+    /// The task returned `Ready`.
+    Completed { label: String },
+}
+
+impl Event {
+    pub fn spawned(label: impl Into<String>) -> Self {
+        Event::Spawned { label: label.into() }
+    }
+
+    pub fn polled(label: impl Into<String>, result: PollResult) -> Self {
+        Event::Polled { label: label.into(), result }
+    }
+
+    pub fn woken(label: impl Into<String>) -> Self {
+        Event::Woken { label: label.into() }
+    }
+
+    pub fn completed(label: impl Into<String>) -> Self {
+        Event::Completed { label: label.into() }
+    }
+
+    /**
+    The label of the task this event is about.
+    */
+    pub fn label(&self) -> &str {
+        match self {
+            Event::Spawned { label } | Event::Polled { label, .. } | Event::Woken { label } | Event::Completed { label } => label,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Spawned { label } => write!(f, "spawned {label}"),
+            Event::Polled { label, result } => write!(f, "polled {label}: {result:?}"),
+            Event::Woken { label } => write!(f, "woken {label}"),
+            Event::Completed { label } => write!(f, "completed {label}"),
+        }
+    }
+}
+
+/**
+An event as recorded by the scheduler.
+*/
+pub(crate) struct Entry {
+    pub(crate) event: Event,
+    pub(crate) task: usize,
+    //since recording started
+    pub(crate) at: Duration,
+    //zero, except for polls
+    pub(crate) duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Timing {
+    task: usize,
+    at: Duration,
+    duration: Duration,
+}
+
+/**
+The events recorded by a runtime, in the order they happened.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct EventLog {
+    events: Vec<Event>,
+    //one per event
+    timings: Vec<Timing>,
+}
+
+impl EventLog {
+    pub(crate) fn new(entries: Vec<Entry>) -> Self {
+        let (events, timings) = entries.into_iter()
+            .map(|entry| (entry.event, Timing { task: entry.task, at: entry.at, duration: entry.duration }))
+            .unzip();
+        EventLog { events, timings }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /**
+    The events, each with the id of the task it is about.
+    */
+    pub fn iter(&self) -> impl Iterator<Item = (TaskId, &Event)> {
+        self.timings.iter().map(|timing| TaskId::new(timing.task)).zip(&self.events)
+    }
+
+    /**
+    Whether `event` was recorded.
+    */
+    pub fn contains(&self, event: &Event) -> bool {
+        self.events.contains(event)
+    }
+
+    /**
+    Whether `events` were all recorded in this order, though not necessarily next to each other.
+    */
+    pub fn contains_sequence(&self, events: &[Event]) -> bool {
+        let mut expected = events.iter().peekable();
+        for event in &self.events {
+            if expected.peek() == Some(&event) {
+                expected.next();
+            }
+        }
+        expected.peek().is_none()
+    }
+
+    /**
+    Whether `first` was recorded, and recorded before any `second`.
+    */
+    pub fn happened_before(&self, first: &Event, second: &Event) -> bool {
+        match self.events.iter().position(|e| e == first) {
+            Some(index) => !self.events[..index].contains(second),
+            None => false,
+        }
+    }
+
+    /**
+    How many times the task labeled `label` was polled.
+    */
+    pub fn polls_of(&self, label: &str) -> usize {
+        self.events.iter().filter(|e| matches!(e, Event::Polled { .. }) && e.label() == label).count()
+    }
+
+    /**
+    The timeline as JSON in the Trace Event format: polls are spans, and the other events instants, on a track per
+    task.
+    */
+    pub fn to_chrome_trace(&self) -> String {
+        let mut tasks: Vec<(usize, &str)> = Vec::new();
+        let mut trace_events = Vec::new();
+        for (event, timing) in self.events.iter().zip(&self.timings) {
+            if !tasks.iter().any(|(task, _)| *task == timing.task) {
+                tasks.push((timing.task, event.label()));
+            }
+            let (name, phase) = match event {
+                Event::Spawned { .. } => ("spawned", "\"ph\":\"i\",\"s\":\"t\"".to_string()),
+                Event::Polled { result, .. } => (match result {
+                    PollResult::Ready => "poll (Ready)",
+                    PollResult::Pending => "poll (Pending)",
+                }, format!("\"ph\":\"X\",\"dur\":{}", micros(timing.duration))),
+                Event::Woken { .. } => ("woken", "\"ph\":\"i\",\"s\":\"t\"".to_string()),
+                Event::Completed { .. } => ("completed", "\"ph\":\"i\",\"s\":\"t\"".to_string()),
+            };
+            trace_events.push(format!("{{\"name\":\"{name}\",{phase},\"ts\":{},\"pid\":1,\"tid\":{}}}", micros(timing.at), timing.task));
+        }
+        //labels are generated by the runtime, so need no escaping
+        for (task, label) in tasks {
+            trace_events.push(format!("{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{task},\"args\":{{\"name\":\"{label}\"}}}}"));
+        }
+        format!("{{\"traceEvents\":[{}]}}", trace_events.join(","))
+    }
+
+    /**
+    Writes [EventLog::to_chrome_trace] to the file at `path`, to be opened in `about:tracing` or Perfetto.
+    */
+    pub fn write_chrome_trace(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_chrome_trace())
+    }
+}
+
+/**
+A duration in the (fractional) microseconds used by the Trace Event format.
+*/
+fn micros(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1_000_000.0)
+}
+
+impl Display for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{event}")?;
+        }
+        Ok(())
+    }
+}
+
+/**
+Compares `log` against the snapshot `name` stored at `path`, or overwrites the snapshot if `update`.
+*/
+#[track_caller]
+pub(crate) fn assert_snapshot(log: &EventLog, name: &str, path: &Path, update: bool) {
+    let schedule = log.to_string();
+    if update {
+        let written = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(path, &schedule));
+        if let Err(e) = written {
+            panic!("could not write snapshot `{name}` to {}: {e}", path.display());
+        }
+        crate::logging::info!("updated schedule snapshot {name}", name=name);
+        return;
+    }
+    let snapshot = match std::fs::read_to_string(path) {
+        Ok(snapshot) => snapshot,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            panic!("no snapshot `{name}` at {}; rerun with {UPDATE_SNAPSHOTS_VAR}=1 to record it", path.display())
+        }
+        Err(e) => panic!("could not read snapshot `{name}` from {}: {e}", path.display()),
+    };
+    //by line, so a checkout with CRLF line endings still matches
+    let (expected, actual): (Vec<_>, Vec<_>) = (snapshot.lines().collect(), schedule.lines().collect());
+    if expected == actual {
+        return;
+    }
+    let index = expected.iter().zip(&actual).position(|(e, a)| e != a).unwrap_or(expected.len().min(actual.len()));
+    panic!(
+        "schedule does not match snapshot `{name}` ({}) from event {}\n snapshot: {}\n schedule: {}\nrerun with {UPDATE_SNAPSHOTS_VAR}=1 to update it",
+        path.display(),
+        index + 1,
+        expected.get(index).unwrap_or(&"(end)"),
+        actual.get(index).unwrap_or(&"(end)"),
+    );
+}
+
+/**
+Asserts that an [EventLog] matches the snapshot of that name, stored in `snapshots/<name>.schedule` in the
+crate under test.  See the [module documentation](crate::event_log#snapshots).
+
+# Panics
+If the events differ from the snapshot, naming the first that differs, or if there is no snapshot yet.  With
+[crate::config::Config::with_update_snapshots], the snapshot is written instead.
+*/
+#[macro_export]
+macro_rules! assert_schedule_snapshot {
+    ($log:expr, $name:expr $(,)?) => {
+        $crate::macro_support::assert_schedule_snapshot(&$log, ::core::env!("CARGO_MANIFEST_DIR"), $name)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::current_thread::CurrentThreadRuntime;
+
+    #[test]
+    fn chrome_trace_has_a_track_per_task() {
+        let mut runtime = CurrentThreadRuntime::new().record_events();
+        runtime.block_on(async {
+            crate::spawn_local_task(async {}).await;
+        });
+        let trace = runtime.take_events().to_chrome_trace();
+        assert!(trace.starts_with("{\"traceEvents\":["));
+        assert!(trace.contains("\"name\":\"poll (Pending)\",\"ph\":\"X\""));
+        assert!(trace.contains("\"tid\":1,\"args\":{\"name\":\"task 1\"}"));
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 3);
+    }
+
+    #[test]
+    fn matches_schedule_snapshot() {
+        let mut runtime = crate::deterministic::DeterministicRuntime::new(3).record_events();
+        runtime.block_on(async {
+            let done = std::rc::Rc::new(std::cell::Cell::new(0));
+            for _ in 0..2 {
+                let done = done.clone();
+                crate::deterministic::spawn(async move {
+                    crate::future_util::yield_now().await;
+                    done.set(done.get() + 1);
+                });
+            }
+            while done.get() < 2 {
+                crate::future_util::yield_now().await;
+            }
+        });
+        let log = runtime.take_events();
+        crate::assert_schedule_snapshot!(log, "deterministic_seed_3");
+
+        let path = std::env::temp_dir().join(format!("test_executors_snapshot_{}", std::process::id())).join("log.schedule");
+        super::assert_snapshot(&log, "log", &path, true);
+        super::assert_snapshot(&log, "log", &path, false);
+        let other = CurrentThreadRuntime::new().record_events().take_events();
+        let panic = std::panic::catch_unwind(|| super::assert_snapshot(&other, "log", &path, false)).unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.contains("from event 1\n snapshot: spawned main\n schedule: (end)"), "{message}");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn task_ids_are_stable_across_runs() {
+        use super::{Event, TaskId};
+        let mut runtime = CurrentThreadRuntime::new().record_events();
+        for _ in 0..2 {
+            let ids = runtime.block_on(async {
+                let first = crate::spawn_local_task(async {});
+                let second = crate::spawn_local_task(async {});
+                let ids = [first.task_id(), second.task_id()];
+                first.await;
+                second.await;
+                ids
+            });
+            assert_eq!(ids, [Some(TaskId::new(1)), Some(TaskId::new(2))]);
+            let log = runtime.take_events();
+            assert!(log.happened_before(&Event::spawned(TaskId::new(1)), &Event::spawned(TaskId::new(2))));
+            assert!(log.iter().all(|(id, event)| event.label() == id.to_string()));
+            assert_eq!(log.iter().next(), Some((TaskId::MAIN, &Event::spawned("main"))));
+        }
+        assert_eq!(crate::spawn_local_with_handle(async {}).task_id(), None);
+    }
+}
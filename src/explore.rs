@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Schedule exploration for async tests, built on [shuttle](https://crates.io/crates/shuttle).
+
+Each function runs the test body under [crate::sleep_on] many times, with shuttle choosing a different
+interleaving of the threads involved on each iteration.  With the `shuttle` feature, [crate::spawn_on] and the
+runtimes in [crate::aruntime] spawn shuttle threads, so tasks they run participate in the exploration.
+
+When an iteration fails, shuttle prints an encoded schedule.  Pass it to [replay] to reproduce the failure
+deterministically.
+
+# Example
+```no_run
+test_executors::explore::check_random(|| async {
+    //inside the test, use shuttle's primitives so shuttle can schedule around them
+    let (sender, receiver) = shuttle::sync::mpsc::channel();
+    test_executors::spawn_on("sender", async move {
+        sender.send(1).unwrap();
+    });
+    assert_eq!(receiver.recv().unwrap(), 1);
+}, 100);
+```
+*/
+
+use std::future::Future;
+
+/**
+Runs `test` for `iterations` iterations, each under a uniformly random schedule.
+*/
+pub fn check_random<T, F>(test: T, iterations: usize)
+where
+    T: Fn() -> F + Send + Sync + 'static,
+    F: Future<Output = ()>,
+{
+    ::shuttle::check_random(move || crate::sleep_on(test()), iterations)
+}
+
+/**
+Runs `test` for `iterations` iterations using shuttle's probabilistic concurrency testing (PCT) scheduler,
+which is biased towards finding bugs requiring at most `depth` preemptions.
+*/
+pub fn check_pct<T, F>(test: T, iterations: usize, depth: usize)
+where
+    T: Fn() -> F + Send + Sync + 'static,
+    F: Future<Output = ()>,
+{
+    ::shuttle::check_pct(move || crate::sleep_on(test()), iterations, depth)
+}
+
+/**
+Runs `test` once, under the schedule printed by a failing [check_random] or [check_pct] run.
+*/
+pub fn replay<T, F>(test: T, schedule: &str)
+where
+    T: Fn() -> F + Send + Sync + 'static,
+    F: Future<Output = ()>,
+{
+    ::shuttle::replay(move || crate::sleep_on(test()), schedule)
+}
@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Tiny building blocks for hand-written poll tests, without depending on the futures crate.
+
+Along with [crate::pend_forever], these cover the futures most tests need: one that is ready immediately
+([ready]), one implemented by a closure ([poll_fn]), one that runs a closure when first polled ([lazy]), and
+one that lets other work run before continuing ([yield_now], or [yield_to_event_loop] in browser tests).
+[select2!](crate::select2) races two futures and [join!](crate::join) awaits several at once.
+*/
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+pub use core::future::{ready, Ready, poll_fn, PollFn};
+
+/**
+A future that runs a closure when it is polled.  See [lazy].
+*/
+#[derive(Debug, Clone)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Lazy<F>(Option<F>);
+
+//the closure is never pinned
+impl<F> Unpin for Lazy<F> {}
+
+/**
+Creates a future that calls `f` the first time it is polled, and completes with its result.
+
+Unlike an `async` block, `f` gets the [Context], so it can inspect or clone the waker.
+
+# Example
+```
+use test_executors::future_util::lazy;
+let future = lazy(|cx| cx.waker().will_wake(cx.waker()));
+assert!(test_executors::spin_on(future));
+```
+*/
+pub fn lazy<F: FnOnce(&mut Context<'_>) -> T, T>(f: F) -> Lazy<F> {
+    Lazy(Some(f))
+}
+
+impl<F: FnOnce(&mut Context<'_>) -> T, T> Future for Lazy<F> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let f = self.get_mut().0.take().expect("Lazy polled after completion");
+        Poll::Ready(f(cx))
+    }
+}
+
+/**
+A future that returns `Pending` once, waking itself, and then completes.  See [yield_now].
+*/
+#[derive(Debug, Clone)]
+#[must_use = "futures do nothing unless polled"]
+pub struct YieldNow(bool);
+
+/**
+Creates a future that yields to the executor once, so that other tasks get a turn before it completes.
+
+# Example
+```
+use test_executors::future_util::yield_now;
+let mut future = std::pin::pin!(yield_now());
+assert!(test_executors::poll_once(future.as_mut()).is_pending());
+assert!(test_executors::poll_once(future.as_mut()).is_ready());
+```
+*/
+pub fn yield_now() -> YieldNow {
+    YieldNow(false)
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let yielded = &mut self.get_mut().0;
+        if *yielded {
+            Poll::Ready(())
+        } else {
+            *yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/**
+Lets the JavaScript event loop run before continuing.
+
+On wasm32-unknown-unknown this awaits a `setTimeout(0)`, so that pending microtasks, DOM events and other
+callbacks are processed between a browser test's assertions.  Elsewhere there is no event loop, and this is
+[yield_now].
+*/
+pub async fn yield_to_event_loop() {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        //setTimeout never rejects
+        let _ = wasm_bindgen_futures::JsFuture::from(crate::js::timeout_promise(0)).await;
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    yield_now().await
+}
+
+/**
+The output of [select2!](crate::select2): which of the two futures completed first, and its output.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/**
+Awaits two futures, evaluating to the output of whichever completes first, as an [Either].  The other future is
+dropped.
+
+Each poll polls the first future, then the second if the first is pending, so when both are ready the first
+wins, and a first future that is always ready starves the second.  There is no attempt at fairness.
+
+Must be used inside an async context.
+
+```
+use test_executors::future_util::Either;
+use test_executors::pend_forever::PendForever;
+test_executors::spin_on(async {
+    let winner = test_executors::select2!(PendForever, async { 2 });
+    assert_eq!(winner, Either::<(), _>::Right(2));
+});
+```
+*/
+#[macro_export]
+macro_rules! select2 {
+    ($left:expr, $right:expr $(,)?) => {{
+        let mut left = ::core::pin::pin!(::core::future::IntoFuture::into_future($left));
+        let mut right = ::core::pin::pin!(::core::future::IntoFuture::into_future($right));
+        ::core::future::poll_fn(|cx| {
+            if let ::core::task::Poll::Ready(output) = ::core::future::Future::poll(left.as_mut(), cx) {
+                return ::core::task::Poll::Ready($crate::future_util::Either::Left(output));
+            }
+            if let ::core::task::Poll::Ready(output) = ::core::future::Future::poll(right.as_mut(), cx) {
+                return ::core::task::Poll::Ready($crate::future_util::Either::Right(output));
+            }
+            ::core::task::Poll::Pending
+        }).await
+    }};
+}
+
+/**
+Awaits all of the futures, concurrently on the current task, evaluating to a tuple of their outputs.
+
+Each poll polls every future that has not completed yet, in order, whichever one was woken.  That is wasteful for
+many futures, but simple, and fine for the handful a test joins.
+
+Must be used inside an async context.
+
+```
+use test_executors::future_util::yield_now;
+test_executors::spin_on(async {
+    let (a, (), c) = test_executors::join!(async { 1 }, yield_now(), async { "c" });
+    assert_eq!((a, c), (1, "c"));
+});
+```
+*/
+#[macro_export]
+macro_rules! join {
+    ($($future:expr),+ $(,)?) => {
+        $crate::__join!(@pinned [] $($future,)+)
+    };
+}
+
+/**
+Implements [join!](crate::join).
+
+Each recursion names its future `future`, which hygiene keeps distinct from the others.
+*/
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __join {
+    (@pinned [$($pinned:ident)*] $future:expr, $($rest:expr,)*) => {{
+        let mut future = (::core::pin::pin!(::core::future::IntoFuture::into_future($future)), ::core::option::Option::None);
+        $crate::__join!(@pinned [$($pinned)* future] $($rest,)*)
+    }};
+    (@pinned [$($pinned:ident)*]) => {
+        ::core::future::poll_fn(|cx| {
+            let mut ready = true;
+            $(
+                if $pinned.1.is_none() {
+                    match ::core::future::Future::poll($pinned.0.as_mut(), cx) {
+                        ::core::task::Poll::Ready(output) => $pinned.1 = ::core::option::Option::Some(output),
+                        ::core::task::Poll::Pending => ready = false,
+                    }
+                }
+            )*
+            if ready {
+                ::core::task::Poll::Ready(($($pinned.1.take().expect("joined future completed"),)*))
+            } else {
+                ::core::task::Poll::Pending
+            }
+        }).await
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{yield_now, Either};
+
+    #[test]
+    fn joins_and_selects() {
+        let joined = crate::spin_on(async {
+            crate::join!(async { yield_now().await; 1 }, async { 2 }, yield_now())
+        });
+        assert_eq!(joined, (1, 2, ()));
+        let selected = crate::spin_on(async {
+            crate::select2!(async { yield_now().await; 1 }, async { 2 })
+        });
+        assert_eq!(selected, Either::Right(2));
+        //the first future wins a tie
+        assert_eq!(crate::spin_on(async { crate::select2!(async { 1 }, async { 2 }) }), Either::<_, u8>::Left(1));
+    }
+}
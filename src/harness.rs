@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Runs async tests in a custom test binary built on [libtest-mimic](https://crates.io/crates/libtest-mimic).
+
+The built-in test harness knows nothing about futures, so `#[async_test]` can only configure each test on its
+own.  A `harness = false` test target can instead collect its async tests into a [Harness], which applies a
+default timeout and executor to every test (overridable per [AsyncTest]), and runs them in parallel according to
+`--test-threads` or [Harness::test_threads].  The usual libtest command line (filters, `--ignored`, `--list`, ...)
+is handled by libtest-mimic.
+
+For the common case, [crate::harness_main] generates `main` from a list of async fns.
+
+```toml
+[[test]]
+name = "integration"
+harness = false
+```
+
+```rust,ignore
+// tests/integration.rs
+async fn connects() {
+    // ...
+}
+
+async fn reconnects() -> Result<(), std::io::Error> {
+    // ...
+    Ok(())
+}
+
+test_executors::harness_main!(connects, reconnects);
+```
+
+Requires the `harness` feature.
+*/
+
+use std::future::Future;
+use std::time::Duration;
+use libtest_mimic::{Arguments, Failed, Trial};
+use crate::macro_support::TestOutcome;
+
+/**
+How a test in a [Harness] is driven.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Executor {
+    /// [crate::spin_on], or [crate::watchdog::spin_on_timeout] when the test has a timeout.
+    Spin,
+    /// [crate::sleep_on], or [crate::watchdog::sleep_on_timeout] when the test has a timeout.
+    #[default]
+    Sleep,
+}
+
+type Runner = Box<dyn FnOnce(Option<Duration>, Executor) -> Result<(), Failed> + Send>;
+
+/**
+An async test to be run by a [Harness].
+*/
+pub struct AsyncTest {
+    name: String,
+    timeout: Option<Duration>,
+    executor: Option<Executor>,
+    ignored: bool,
+    runner: Runner,
+}
+
+impl AsyncTest {
+    /**
+    Creates a test named `name`, whose body is the future returned by `test`.
+
+    Like `#[test]` functions, the body may return `()` or `Result<(), E>` where `E: Debug`.  Each test runs on a
+    thread of libtest-mimic's, so `test` must be `Send`, but the future it returns need not be.
+    */
+    pub fn new<T, F>(name: impl Into<String>, test: T) -> Self
+    where
+        T: FnOnce() -> F + Send + 'static,
+        F: Future,
+        F::Output: TestOutcome,
+    {
+        let name = name.into();
+        let test_name = name.clone();
+        AsyncTest {
+            name,
+            timeout: None,
+            executor: None,
+            ignored: false,
+            runner: Box::new(move |timeout, executor| run(&test_name, test(), timeout, executor)),
+        }
+    }
+
+    /**
+    Fails the test if it does not complete within `timeout`, instead of the harness's default.
+    */
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /**
+    Drives the test with `executor`, instead of the harness's default.
+    */
+    pub fn executor(mut self, executor: Executor) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+
+    /**
+    Marks the test as ignored, so that it only runs with `--ignored` or `--include-ignored`.
+    */
+    pub fn ignored(mut self, ignored: bool) -> Self {
+        self.ignored = ignored;
+        self
+    }
+}
+
+fn run<F: Future>(name: &str, future: F, timeout: Option<Duration>, executor: Executor) -> Result<(), Failed>
+where
+    F::Output: TestOutcome,
+{
+    crate::logging::info!("running async test {name}", name = name);
+    let output = match (executor, timeout) {
+        (Executor::Spin, None) => crate::spin_on(future),
+        (Executor::Sleep, None) => crate::sleep_on(future),
+        (Executor::Spin, Some(timeout)) => crate::watchdog::spin_on_timeout_at(future, timeout, None)
+            .map_err(|e| format!("async test `{name}` timed out: {e}"))?,
+        (Executor::Sleep, Some(timeout)) => crate::watchdog::sleep_on_timeout_at(future, timeout, None, crate::ParkPolicy::Park)
+            .map_err(|e| format!("async test `{name}` timed out: {e}"))?,
+    };
+    match output.failure() {
+        None => Ok(()),
+        Some(description) => Err(description.into()),
+    }
+}
+
+/**
+A collection of async tests, run as a libtest-mimic test binary.
+*/
+pub struct Harness {
+    arguments: Arguments,
+    timeout: Option<Duration>,
+    executor: Executor,
+    tests: Vec<AsyncTest>,
+}
+
+impl Harness {
+    /**
+    Creates an empty harness configured by the process's command line.
+    */
+    pub fn new() -> Self {
+        Harness {
+            arguments: Arguments::from_args(),
+            timeout: None,
+            executor: Executor::default(),
+            tests: Vec::new(),
+        }
+    }
+
+    /**
+    Sets the timeout of tests that do not set their own.  By default, tests have no timeout.
+    */
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /**
+    Sets the executor of tests that do not set their own.  By default, tests use [Executor::Sleep].
+    */
+    pub fn executor(mut self, executor: Executor) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /**
+    Runs at most `threads` tests at once, unless `--test-threads` was passed on the command line.
+    */
+    pub fn test_threads(mut self, threads: usize) -> Self {
+        if self.arguments.test_threads.is_none() {
+            self.arguments.test_threads = Some(threads);
+        }
+        self
+    }
+
+    /**
+    Adds a test.
+    */
+    pub fn test(mut self, test: AsyncTest) -> Self {
+        self.tests.push(test);
+        self
+    }
+
+    /**
+    Runs the tests, then exits the process with libtest's exit code.
+    */
+    pub fn run(self) -> ! {
+        let timeout = self.timeout;
+        let executor = self.executor;
+        let trials = self.tests.into_iter().map(|test| {
+            let test_timeout = test.timeout.or(timeout);
+            let test_executor = test.executor.unwrap_or(executor);
+            let runner = test.runner;
+            Trial::test(test.name, move || runner(test_timeout, test_executor))
+                .with_ignored_flag(test.ignored)
+        }).collect();
+        libtest_mimic::run(&self.arguments, trials).exit()
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+Generates a `main` that runs the listed async fns with a default [harness::Harness](crate::harness::Harness).
+
+Each test is named after its path.  For per-test timeouts or executors, build the harness by hand instead.
+*/
+#[macro_export]
+macro_rules! harness_main {
+    ($($test:path),* $(,)?) => {
+        fn main() {
+            $crate::harness::Harness::new()
+                $(.test($crate::harness::AsyncTest::new(stringify!($test), || $test())))*
+                .run()
+        }
+    };
+}
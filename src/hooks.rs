@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Observing what an executor does, without changing it.
+
+An [ExecutorHooks] object is told before and after each poll of a task, whenever the task is woken, and when it
+completes.  Pass one to [crate::spin_on_with_hooks] or [crate::sleep_on_with_hooks], or to
+[crate::current_thread::CurrentThreadRuntime::with_hooks] or [crate::deterministic::DeterministicRuntime::with_hooks],
+to count polls, log, or assert on the schedule:
+
+```
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use test_executors::hooks::ExecutorHooks;
+
+#[derive(Default)]
+struct CountPolls(AtomicUsize);
+impl ExecutorHooks for CountPolls {
+    fn before_poll(&self, _task: &str) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+let hooks = Arc::new(CountPolls::default());
+test_executors::sleep_on_with_hooks(test_executors::future_util::yield_now(), hooks.clone());
+assert_eq!(hooks.0.load(Ordering::Relaxed), 2);
+```
+
+Tasks are named as in an [crate::event_log::EventLog]: the future passed to the executor is `main`, and tasks
+spawned onto a runtime are `task 1`, `task 2` and so on.  The runtimes in [crate::aruntime], which take hooks
+through `with_hooks` as well, name each task by its label instead.  `on_wake` may be called from any thread, and
+while the task is being polled.
+*/
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Wake, Waker};
+
+/**
+Callbacks an executor makes as it runs tasks.  Every method does nothing by default.
+*/
+pub trait ExecutorHooks: Send + Sync {
+    /**
+    Called just before `task` is polled.
+    */
+    fn before_poll(&self, task: &str) {
+        let _ = task;
+    }
+
+    /**
+    Called just after `task` is polled, with whether the poll returned `Ready`.
+    */
+    fn after_poll(&self, task: &str, ready: bool) {
+        let _ = (task, ready);
+    }
+
+    /**
+    Called when a waker for `task` is woken.
+    */
+    fn on_wake(&self, task: &str) {
+        let _ = task;
+    }
+
+    /**
+    Called after `task` has completed, following its last `after_poll`.
+    */
+    fn on_complete(&self, task: &str) {
+        let _ = task;
+    }
+}
+
+/**
+The name of the future passed to an executor.
+*/
+const MAIN: &str = "main";
+
+struct HookedWaker {
+    outer: Mutex<Waker>,
+    hooks: Arc<dyn ExecutorHooks>,
+    task: Arc<str>,
+}
+
+impl Wake for HookedWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.hooks.on_wake(&self.task);
+        self.outer.lock().unwrap().wake_by_ref();
+    }
+}
+
+/**
+Wraps a future so that its polls and wakes, as `main`, are reported to `hooks`, whatever drives it.
+*/
+pub(crate) fn hooked<F: Future>(future: F, hooks: Arc<dyn ExecutorHooks>) -> impl Future<Output = F::Output> {
+    hooked_named(future, hooks, MAIN)
+}
+
+/**
+Like [hooked], reporting the future as `task` instead of `main`.
+*/
+pub(crate) fn hooked_named<F: Future>(future: F, hooks: Arc<dyn ExecutorHooks>, task: &str) -> impl Future<Output = F::Output> {
+    run_hooked(future, hooks, task.into())
+}
+
+//the future is kept inline in the returned one, rather than boxed, as it may already be a task's only allocation
+async fn run_hooked<F: Future>(future: F, hooks: Arc<dyn ExecutorHooks>, task: Arc<str>) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut waker: Option<Arc<HookedWaker>> = None;
+    std::future::poll_fn(|cx| {
+        let waker = match &waker {
+            Some(waker) => {
+                let mut outer = waker.outer.lock().unwrap();
+                if !outer.will_wake(cx.waker()) {
+                    *outer = cx.waker().clone();
+                }
+                drop(outer);
+                waker.clone()
+            }
+            None => waker.insert(Arc::new(HookedWaker { outer: Mutex::new(cx.waker().clone()), hooks: hooks.clone(), task: task.clone() })).clone(),
+        };
+        hooks.before_poll(&task);
+        let poll = future.as_mut().poll(&mut Context::from_waker(&Waker::from(waker)));
+        hooks.after_poll(&task, poll.is_ready());
+        if poll.is_ready() {
+            hooks.on_complete(&task);
+        }
+        poll
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::ExecutorHooks;
+
+    #[derive(Default)]
+    struct Log(Mutex<Vec<String>>);
+
+    impl ExecutorHooks for Log {
+        fn before_poll(&self, task: &str) {
+            self.0.lock().unwrap().push(format!("poll {task}"));
+        }
+        fn after_poll(&self, task: &str, ready: bool) {
+            self.0.lock().unwrap().push(format!("polled {task} {}", if ready { "ready" } else { "pending" }));
+        }
+        fn on_wake(&self, task: &str) {
+            self.0.lock().unwrap().push(format!("woke {task}"));
+        }
+        fn on_complete(&self, task: &str) {
+            self.0.lock().unwrap().push(format!("completed {task}"));
+        }
+    }
+
+    #[test]
+    fn spin_on_reports_main() {
+        let log = Arc::new(Log::default());
+        crate::spin_on_with_hooks(crate::future_util::yield_now(), log.clone());
+        assert_eq!(*log.0.lock().unwrap(), ["poll main", "woke main", "polled main pending", "poll main", "polled main ready", "completed main"]);
+    }
+
+    #[test]
+    fn runtime_reports_spawned_tasks() {
+        let log = Arc::new(Log::default());
+        crate::current_thread::CurrentThreadRuntime::new().with_hooks(log.clone()).block_on(async {
+            crate::spawn_local_task(async {}).await;
+        });
+        assert_eq!(*log.0.lock().unwrap(), [
+            "poll main", "polled main pending",
+            "poll task 1", "woke main", "polled task 1 ready", "completed task 1",
+            "poll main", "polled main ready", "completed main",
+        ]);
+    }
+
+    #[test]
+    fn aruntime_reports_task_label() {
+        use some_executor::SomeExecutor;
+        let log = Arc::new(Log::default());
+        let task = some_executor::task::Task::<_, std::convert::Infallible>::without_notifications("fetch".to_string(), async {}, Default::default());
+        let _observer = crate::aruntime::SpinRuntime::new().with_hooks(log.clone()).spawn(task);
+        assert_eq!(*log.0.lock().unwrap(), ["poll fetch", "polled fetch ready", "completed fetch"]);
+    }
+}
@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+JavaScript bindings for wasm32-unknown-unknown.
+*/
+
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    pub fn set_timeout(handler: &js_sys::Function, timeout: i32) -> JsValue;
+}
+
+/**
+A Promise that `setTimeout` resolves after `millis` milliseconds.
+*/
+pub(crate) fn timeout_promise(millis: i32) -> js_sys::Promise {
+    js_sys::Promise::new(&mut |resolve, _reject| {
+        set_timeout(&resolve, millis);
+    })
+}
@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Simulating a slow scheduler.
+
+On a test machine, a woken task is usually polled within microseconds, so code that races a wake against
+something else (a timeout, a second thread, a cancellation) tends to pass even when it only works because wakes
+are instantaneous.  [with_latency] wraps a future so that each of its polls is delayed, as if the executor were
+busy with other tasks, and each wake is delivered late, as if the waking thread were descheduled.  It works on any
+executor; [sleep_on_with_latency] is the shorthand for [crate::sleep_on].
+
+```
+use std::time::Duration;
+use test_executors::latency::{Latency, sleep_on_with_latency};
+let latency = Latency::new()
+    .before_poll(Duration::from_millis(1))
+    .before_wake(Duration::from_millis(2))
+    .seeded(7);
+assert_eq!(sleep_on_with_latency(async { 1 + 1 }, latency), 2);
+```
+
+Without a seed every delay is the configured duration.  With [Latency::seeded], each is drawn instead between zero
+and the configured duration, in a sequence decided by the seed.  The sequence is reproducible, though which delay
+goes to which poll or wake depends on the order they happen in, which can vary when the future is woken from other
+threads.
+
+Natively, late wakes are delivered by the timer thread of [crate::time]; on wasm32-unknown-unknown, by
+`setTimeout`.
+*/
+
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+use crate::rng::Rng;
+use crate::time::Sleep;
+
+/**
+How much latency [with_latency] injects.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Latency {
+    before_poll: Duration,
+    before_wake: Duration,
+    seed: Option<u64>,
+}
+
+impl Latency {
+    /**
+    No latency, until configured.
+    */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Delays each poll of the future by `delay`.
+    */
+    pub fn before_poll(mut self, delay: Duration) -> Self {
+        self.before_poll = delay;
+        self
+    }
+
+    /**
+    Delays delivering each wake of the future by `delay`.
+    */
+    pub fn before_wake(mut self, delay: Duration) -> Self {
+        self.before_wake = delay;
+        self
+    }
+
+    /**
+    Draws each delay between zero and its configured duration, from a sequence decided by `seed`.
+    */
+    pub fn seeded(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /**
+    The seed, if the delays are drawn from one.
+    */
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+}
+
+struct Delays {
+    latency: Latency,
+    //None unless seeded
+    rng: Mutex<Option<Rng>>,
+}
+
+impl Delays {
+    fn draw(&self, max: Duration) -> Duration {
+        match self.rng.lock().unwrap().as_mut() {
+            Some(rng) if max > Duration::ZERO => rng.duration_up_to(max),
+            _ => max,
+        }
+    }
+}
+
+/**
+The waker the wrapped future is polled with, which wakes the outer task late.
+*/
+struct LateWaker {
+    outer: Mutex<Waker>,
+    delays: Arc<Delays>,
+}
+
+impl Wake for LateWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let delay = self.delays.draw(self.delays.latency.before_wake);
+        let outer = self.outer.lock().unwrap().clone();
+        if delay == Duration::ZERO {
+            outer.wake();
+            return;
+        }
+        crate::logging::trace!("delaying wake by {delay:?}", delay = delay);
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        crate::timer::wake_at(crate::sys::time::Instant::now() + delay, outer);
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        wasm_bindgen_futures::spawn_local(async move {
+            crate::time::sleep(delay).await;
+            outer.wake();
+        });
+    }
+}
+
+/**
+Wraps `future` so that its polls and wakes are delayed according to `latency`.
+*/
+pub fn with_latency<F: IntoFuture>(future: F, latency: Latency) -> WithLatency<F::IntoFuture> {
+    WithLatency {
+        future: Box::pin(future.into_future()),
+        delays: Arc::new(Delays { latency, rng: Mutex::new(latency.seed.map(Rng::new)) }),
+        delay: None,
+        waker: None,
+    }
+}
+
+/**
+Blocks the calling thread until a future is ready, like [crate::sleep_on], delaying its polls and wakes according
+to `latency`.
+*/
+pub fn sleep_on_with_latency<F: IntoFuture>(future: F, latency: Latency) -> F::Output {
+    crate::sleep_on(with_latency(future, latency))
+}
+
+/**
+The future returned by [with_latency].
+*/
+#[must_use = "futures do nothing unless polled"]
+pub struct WithLatency<F> {
+    future: Pin<Box<F>>,
+    delays: Arc<Delays>,
+    //the delay before the next poll of `future`, once started
+    delay: Option<Sleep>,
+    waker: Option<Arc<LateWaker>>,
+}
+
+impl<F: Future> Future for WithLatency<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        if this.delay.is_none() {
+            let delay = this.delays.draw(this.delays.latency.before_poll);
+            if delay > Duration::ZERO {
+                crate::logging::trace!("delaying poll by {delay:?}", delay = delay);
+                this.delay = Some(crate::time::sleep(delay));
+            }
+        }
+        if let Some(delay) = this.delay.as_mut() {
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.delay = None;
+        }
+        let waker = match &this.waker {
+            Some(waker) => {
+                let mut outer = waker.outer.lock().unwrap();
+                if !outer.will_wake(cx.waker()) {
+                    *outer = cx.waker().clone();
+                }
+                drop(outer);
+                waker.clone()
+            }
+            None => this.waker.insert(Arc::new(LateWaker { outer: Mutex::new(cx.waker().clone()), delays: this.delays.clone() })).clone(),
+        };
+        this.future.as_mut().poll(&mut Context::from_waker(&Waker::from(waker)))
+    }
+}
+
+impl<F> std::fmt::Debug for WithLatency<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithLatency").field("latency", &self.delays.latency).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::sys::time::Instant;
+    use super::{sleep_on_with_latency, Latency};
+
+    #[test]
+    fn delays_polls_and_wakes() {
+        let start = Instant::now();
+        let latency = Latency::new().before_poll(Duration::from_millis(5)).before_wake(Duration::from_millis(10));
+        //polled twice, and woken once in between
+        sleep_on_with_latency(crate::future_util::yield_now(), latency);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn seeded_delays_repeat() {
+        let delays = |seed| {
+            let delays = super::Delays { latency: Latency::new(), rng: std::sync::Mutex::new(Some(crate::rng::Rng::new(seed))) };
+            (0..8).map(|_| delays.draw(Duration::from_millis(3))).collect::<Vec<_>>()
+        };
+        assert_eq!(delays(1), delays(1));
+        assert_ne!(delays(1), delays(2));
+        assert!(delays(3).iter().all(|delay| *delay <= Duration::from_millis(3)));
+    }
+}
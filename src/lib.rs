@@ -1,41 +1,304 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "std", deny(unsafe_code))]
 /*!
 This crate provides extremely simple, yet useful, async executors.  They are primarily useful for writing unit tests
 without bringing in a full-blown executor such as [tokio](https://tokio.rs).
 
 The executors are:
 * spin_on: polls a future in a busyloop on the current thread.
+* spin_yield_on: like spin_on, but yields the thread to the OS scheduler between polls.
 * sleep_on: polls a future on the current thread, sleeping between polls.
+* sleep_on_with: like sleep_on, with a choice of how to wait between polls.
+* catch_on: like sleep_on, but returns the payload of a panic instead of unwinding.
+* block_on_compat: sleep_on natively, and a single poll on wasm32-unknown-unknown, for portable test helpers.
 * spawn_on: spawns a future on a new thread, polling it there.
+* spawn_on_with: like spawn_on, but constructs the future on the new thread, so it need not be `Send`.
+* spawn_local_on: runs an event loop on a new thread, onto which futures that need not be `Send` can be spawned.
+* spawn_with_handle: spawns a future, returning a handle that resolves with its output and cancels it when dropped.
+* spawn_blocking: runs a blocking closure on a helper thread, returning a future that resolves with its result.
+* watchdog::sleep_on_timeout: like sleep_on, but gives up on the future after a timeout.
+* latency::sleep_on_with_latency: like sleep_on, but delays each poll and wake, to simulate a slow scheduler.
+* wake_faults::sleep_on_with_wake_faults: like sleep_on, but drops (coalesces), duplicates, delays and invents wakes
+  as decided by a seed; wake_faults::check_seeds runs a test under many seeds and reports the one that fails.
+* wake_storm::wake_storm: a future woken thousands of times at once from several threads, which reports how often
+  it was polled, to check that an executor coalesces wakes.
+* step::StepExecutor: polls a future, and tasks spawned onto it, only when and in the order the test says, counting
+  their wakes in between.
+* lost_wake::check_on: like sleep_on, but reports a future that can no longer be woken instead of hanging.
+* waker_reuse::check_waker_reuse: like sleep_on, but reports whether the future kept, reused and woke its wakers
+  correctly.
+* current_thread::CurrentThreadRuntime: runs a future and the tasks it spawns on the current thread.
+* deterministic::DeterministicRuntime: runs a future and the tasks it spawns on the current thread, in an order
+  decided by a seed.
+* priority::PriorityRuntime: runs some_executor tasks on the current thread, polling the woken task with the
+  highest priority first.
+* aruntime::NullRuntime: accepts some_executor tasks but never polls them, cancelling them when discarded.
+* aruntime::InlineRuntime: polls each some_executor task once while spawning it, cancelling those that are not
+  ready, to check that code completes without waiting.
+
+spin_on, sleep_on and spawn_on accept anything that implements `IntoFuture`, so builder types can be passed
+directly.
 
 # some_executor
 
 This crate implements the [some_executor](https://crates.io/crates/some_executor) trait for all executors, allowing them
 to be used in executor-agnostic code.
 
+# Wakers
+
+[wakers::waker_from_fn] builds a waker that runs a closure when woken, for tests that observe wakes, and
+[wakers::waker_for_thread] one that unparks a thread, for tests with their own `block_on` loop.
+
+# Combinators
+
+`select2!` races two futures and `join!` awaits several at once, in the current task, so a test can combine a
+couple of futures without depending on the futures crate.  Neither tries to be fair; see [future_util].
+
+# Retrying
+
+[retry::retry] runs a flaky async operation until it succeeds or runs out of attempts, waiting between attempts
+with the crate's timers, so integration-style tests can tolerate an unreliable external resource on any executor.
+[retry::run_until] checks some state until a predicate holds, reporting the last state it saw on timeout, for
+"eventually consistent" assertions.
+
+# Choosing a strategy at run time
+
+Helpers that block through [blocking::default_executor], and `#[async_test]`s that name no `executor`, use
+[sleep_on], unless the [blocking::EXECUTOR_VAR] environment variable selects `spin`, `sleep` or `adaptive` (spin
+briefly, then park), so a suite's polling strategy can be switched without code changes when diagnosing a hang.
+
+# Configuration
+
+The [config] module gathers the defaults the executors fall back on (the default `#[async_test]` timeout, the blocking
+strategy and its spin and park settings, a chaos seed to reproduce, and the log level) behind one `Config`, read
+from `TEST_EXECUTORS_*` environment variables or installed from code.
+
+# Hooks
+
+A [hooks::ExecutorHooks] object, passed to [spin_on_with_hooks], [sleep_on_with_hooks], or the `with_hooks` builder of
+[current_thread::CurrentThreadRuntime], the deterministic runtime and the runtimes in [aruntime], is called before and after
+every poll, on every wake and on completion, so instrumentation can be layered onto the executors without changing
+them.
+
+# Schedule snapshots
+
+[assert_schedule_snapshot!] compares the events recorded by [current_thread::CurrentThreadRuntime] or the
+deterministic runtime against a snapshot checked in under `snapshots/`, so a change in the order tasks are polled
+fails the test; `TEST_EXECUTORS_UPDATE_SNAPSHOTS=1` rewrites the snapshots.  The logs name tasks by their
+[event_log::TaskId], which these runtimes and the priority runtime assign in spawn order; tasks spawned through the
+runtimes in [aruntime] or with [spawn_on] have none.
+
+# Running tasks
+
+[debug::running_tasks] lists the tasks spawned through this crate's runtimes that have not finished yet, with their label,
+state, last poll time and spawn location, so a hung test can show what is still alive.  An `#[async_test]` that
+times out prints the list, and [debug::assert_all_tasks_complete_guard] returns a guard that fails the test if
+tasks it spawned are still running when the guard is dropped.  Tracking costs a little on every spawn and poll;
+[config::Config::with_task_tracking] or `TEST_EXECUTORS_TRACK_TASKS=0` turns it off.
+[aruntime::TrackedSpawnRuntime::wait_idle] blocks until the tasks spawned through a runtime have finished, for tests that check the side
+effects of fire-and-forget work.
+
+# Observers
+
+[observation::resolve_observer] blocks until a some_executor task finishes and returns its output, panicking with a useful
+message if the task was cancelled, and [observation::resolve_with_timeout] gives up after a timeout.
+[observation::ObservationExt] adds `expect_ready`, `expect_cancelled` and `into_result` to observations, so an assertion on a
+task's outcome is one line, and `assert_task_completes!` spawns a future onto a runtime and asserts on its
+output.
+
 # `async_test`
 This crate provides a macro, `async_test`, allowing tests to be used with async functions, including support
 for wasm32 targets.
 
+# `async_main`
+`#[async_main]` turns an async `main` (of an example or a `harness = false` test binary, say) into a sync one that
+drives it with [sleep_on], or with [spawn_local] on wasm32-unknown-unknown.
+
+```
+#[test_executors::async_main]
+async fn main() {
+    assert_eq!(async { 1 + 1 }.await, 2);
+}
+```
+
+# `async_bench`
+`#[async_bench]` turns an async function into a quick micro-benchmark: it runs under [spin_on] with a warmup,
+and reports the mean time per iteration and the distribution of poll latency.  See [bench].
+
+# harness
+
+With the `harness` feature, [harness] runs async tests in a `harness = false` test binary built on
+[libtest-mimic](https://crates.io/crates/libtest-mimic), with a default timeout and executor for every test and
+control over how many run in parallel.
+
+# reactor
+
+With the `reactor` feature, on unix, `reactor::Async` wraps std's TCP and Unix sockets with async accept, read and
+write, woken by a small `poll(2)` reactor thread, so loopback networking tests can run on these executors.
+
+# alloc-track
+
+With the `alloc-track` feature and [stats::TrackingAllocator] installed as the global allocator,
+[stats::time_on] reports how many allocations a future made, so a test can assert that a hot path is
+allocation-free.
+
+# Logging
+
+By default the executors log through [logwise](https://crates.io/crates/logwise).  Disable the `logwise` feature
+(`default-features = false, features = ["std"]`) to send the instrumentation nowhere.
+
+With the `tracing` feature, the same events are emitted through [tracing](https://crates.io/crates/tracing), and
+each future driven by a runtime (or [spawn_on]) runs inside a span named after its task label.
+
+[log_capture::capture_logs] collects what the executors log on the current thread while a closure runs, whichever
+backends are enabled, so a test can assert on the log or print it only when it fails.  Only this crate's own messages
+are collected, not what other code sends to logwise or tracing.  It needs the `log-capture` feature, on by default;
+with none of `logwise`, `tracing` and `log-capture`, the instrumentation compiles to nothing.
+
+# loom
+
+With the `loom` feature, [sleep_on], [spawn_on] and the runtimes in [aruntime] use
+[loom](https://crates.io/crates/loom)'s threads and synchronization primitives, so code driven by these executors
+can be model-checked inside `loom::model`.  Delays requested via `poll_after` are not slept under loom, since
+loom has no notion of time.
+
+# shuttle
+
+With the `shuttle` feature, the executors run on [shuttle](https://crates.io/crates/shuttle)'s threads and
+primitives instead, and [explore] runs an async test body under many randomized thread interleavings.  A failing
+run prints a schedule which can be fed back to [explore::replay].
+
+# no_std
+
+Disabling the default `std` feature makes the crate `no_std` (core-only).  [spin_on], [poll_once],
+[poll_once_pin], [noop_waker], [pend_forever] and [future_util] remain available; everything that needs threads,
+time or some_executor does not.
+
+# `async_proptest`
+With the `proptest` feature, `async_proptest` turns an async function into a property test whose cases each run
+under [sleep_on].  See [prop] for the underlying adapter.
+
+# WASI
+
+`wasm32-wasip1` and `wasm32-wasip2` targets are supported and use `std` rather than wasm-bindgen.  Since these
+targets generally lack threads, [sleep_on] sleeps briefly between polls instead of waiting to be woken, and
+[spawn_on], the runtimes in [aruntime] that spawn threads and the native timers in [time] are not available at
+runtime.  [async_test] expands to a regular `#[test]` so suites can run under wasmtime.
+
 */
 
 /*!
 Blocks the calling thread until a future is ready.
 */
 
+#[cfg(feature = "std")]
+mod logging;
+#[cfg(feature = "std")]
+mod call_site;
+#[cfg(feature = "std")]
+pub mod log_capture;
 mod noop_waker;
+#[cfg(feature = "std")]
 pub mod aruntime;
 pub mod pend_forever;
+pub mod future_util;
+#[cfg(feature = "std")]
 mod sys;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod js;
+#[cfg(feature = "shuttle")]
+pub mod explore;
+#[cfg(feature = "proptest")]
+pub mod prop;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod panic_hook;
+#[cfg(feature = "std")]
+pub mod watchdog;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "harness")]
+pub mod harness;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+pub mod deterministic;
+#[cfg(feature = "std")]
+pub mod local;
+#[cfg(feature = "std")]
+mod scheduler;
+#[cfg(feature = "std")]
+pub mod current_thread;
+#[cfg(feature = "std")]
+pub mod priority;
+#[cfg(feature = "std")]
+pub mod coop;
+#[cfg(feature = "std")]
+pub mod lost_wake;
+#[cfg(feature = "std")]
+pub mod waker_reuse;
+#[cfg(feature = "std")]
+pub mod latency;
+#[cfg(feature = "std")]
+pub mod wake_faults;
+#[cfg(feature = "std")]
+pub mod wake_storm;
+#[cfg(feature = "std")]
+pub mod wakers;
+#[cfg(feature = "std")]
+pub mod blocking;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod step;
+#[cfg(feature = "std")]
+pub mod hooks;
+#[cfg(feature = "std")]
+pub mod event_log;
+#[cfg(feature = "std")]
+pub mod debug;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
+mod timer;
+#[cfg(all(feature = "reactor", unix))]
+pub mod reactor;
+#[cfg(feature = "std")]
+pub mod objsafe;
+#[cfg(feature = "std")]
+pub mod observation;
+#[cfg(feature = "std")]
+pub mod remote;
+#[cfg(feature = "std")]
+pub mod time;
+#[cfg(feature = "std")]
+pub mod retry;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod macro_support;
 
-use std::future::Future;
-use std::pin::Pin;
+use core::future::{Future, IntoFuture};
+use core::pin::Pin;
+#[cfg(feature = "std")]
 use std::sync::{Arc};
-use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
-use blocking_semaphore::one::Semaphore;
-use crate::noop_waker::new_context;
+use core::task::{Context, Poll};
+#[cfg(feature = "std")]
+use core::task::Waker;
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+use crate::sys::Semaphore;
+pub use crate::noop_waker::noop_waker;
 
 pub use test_executors_proc::async_test;
+#[cfg(feature = "std")]
+pub use aruntime::current;
+pub use test_executors_proc::async_main;
+pub use test_executors_proc::async_bench;
+#[cfg(feature = "proptest")]
+pub use test_executors_proc::async_proptest;
 
 extern crate self as test_executors;
 
@@ -43,58 +306,354 @@ extern crate self as test_executors;
 Blocks the calling thread until a future is ready.
 
 This implementation uses a spinloop.
+
+# Panics
+With `std`, like [sleep_on], if called from inside a future that sleep_on or another of this crate's blocking
+executors is already driving on this thread.
+*/
+#[track_caller]
+pub fn spin_on<F: IntoFuture>(future: F) -> F::Output {
+    #[cfg(feature = "std")]
+    let _sleeping = SleepingGuard::enter(false, "spin_on", core::panic::Location::caller());
+    spin_on_unguarded(future)
+}
+
+/**
+Like [spin_on], but may be nested inside another blocking executor on purpose, as [aruntime::SpinRuntime] does.
 */
-pub fn spin_on<F: Future>(mut future: F) -> F::Output {
+#[cfg(feature = "std")]
+pub(crate) fn spin_on_nested<F: IntoFuture>(future: F, location: &'static core::panic::Location<'static>) -> F::Output {
+    let _sleeping = SleepingGuard::enter(true, "spin_on", location);
+    spin_on_unguarded(future)
+}
+
+#[track_caller]
+fn spin_on_unguarded<F: IntoFuture>(future: F) -> F::Output {
+    #[cfg(feature = "std")]
+    let _call_site = call_site::enter("spin_on", core::panic::Location::caller());
     //we inherit the parent dlog::context here.
-    let mut context = new_context();
-    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+    let future = future.into_future();
+    let waker = noop_waker();
+    let mut context = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
+    loop {
+        if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
+            return val;
+        }
+        #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+        core::hint::spin_loop();
+        #[cfg(any(feature = "loom", feature = "shuttle"))]
+        crate::sys::spin_loop();
+    }
+}
+
+/**
+Blocks the calling thread until a future is ready, like [spin_on], reporting its polls and wakes to `hooks`.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn spin_on_with_hooks<F: IntoFuture>(future: F, hooks: Arc<dyn hooks::ExecutorHooks>) -> F::Output {
+    spin_on(hooks::hooked(future.into_future(), hooks))
+}
+
+/**
+Blocks the calling thread until a future is ready.
+
+Like [spin_on], this polls in a loop, but it calls [std::thread::yield_now] between polls instead of spinning.  On
+a machine with few cores (a CI runner, say), a pure spinloop can keep the thread that would complete the future
+from running at all; yielding lets it run.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn spin_yield_on<F: IntoFuture>(future: F) -> F::Output {
+    let _sleeping = SleepingGuard::enter(false, "spin_yield_on", core::panic::Location::caller());
+    let _call_site = call_site::enter("spin_yield_on", core::panic::Location::caller());
+    let future = future.into_future();
+    let waker = noop_waker();
+    let mut context = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
     loop {
         if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
             return val;
         }
-        std::hint::spin_loop();
+        crate::sys::thread::yield_now();
     }
 }
 
-struct SimpleWakeShared {
+#[cfg(feature = "std")]
+pub(crate) struct SimpleWakeShared {
+    #[cfg(not(target_os = "wasi"))]
     semaphore: Semaphore,
+    #[cfg(target_os = "wasi")]
+    woken: std::sync::atomic::AtomicBool,
 }
 
+/**
+How long [sleep_on] sleeps between polls on WASI when the future has not been woken.
+
+WASI targets generally have no threads, so there is nobody who could wake a blocked thread.  Instead
+we sleep (via `poll_oneoff`) and poll the future again.
+*/
+#[cfg(all(feature = "std", target_os = "wasi"))]
+const WASI_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+#[cfg(feature = "std")]
+impl SimpleWakeShared {
+    pub(crate) fn new() -> Self {
+        SimpleWakeShared {
+            #[cfg(not(target_os = "wasi"))]
+            semaphore: Semaphore::new(false),
+            #[cfg(target_os = "wasi")]
+            woken: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn signal(&self) {
+        #[cfg(not(target_os = "wasi"))]
+        self.semaphore.signal_if_needed();
+        #[cfg(target_os = "wasi")]
+        self.woken.store(true, std::sync::atomic::Ordering::Release);
+    }
+
+    pub(crate) fn wait(&self) {
+        #[cfg(not(target_os = "wasi"))]
+        self.semaphore.wait();
+        #[cfg(target_os = "wasi")]
+        if !self.woken.swap(false, std::sync::atomic::Ordering::AcqRel) {
+            std::thread::sleep(WASI_POLL_INTERVAL);
+        }
+    }
+
+    /**
+    Consumes a wake if there has been one, without waiting for it.
+    */
+    pub(crate) fn try_wait(&self) -> bool {
+        #[cfg(not(target_os = "wasi"))]
+        return self.semaphore.try_wait();
+        #[cfg(target_os = "wasi")]
+        return self.woken.swap(false, std::sync::atomic::Ordering::AcqRel);
+    }
+}
+
+
+#[cfg(feature = "std")]
+impl std::task::Wake for SimpleWakeShared {
+    fn wake(self: Arc<Self>) {
+        crate::logging::trace!("waking");
+        self.signal();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        crate::logging::trace!("waking (by ref)");
+        self.signal();
+    }
+}
 
-static CONDVAR_WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
-    |ctx|{
-        let ctx = unsafe{Arc::from_raw(ctx as *const SimpleWakeShared)};
-        let ctx2 = ctx.clone();
-        std::mem::forget(ctx);
-        RawWaker::new(Arc::into_raw(ctx2) as *const (), &CONDVAR_WAKER_VTABLE)
-    },
-    |ctx| {
-        let ctx = unsafe{Arc::from_raw(ctx as *const SimpleWakeShared)};
-        logwise::trace_sync!("waking");
-        ctx.semaphore.signal_if_needed();
-    },
-    |ctx| {
-        let ctx = unsafe{Arc::from_raw(ctx as *const SimpleWakeShared)};
-        logwise::trace_sync!("waking (by ref)");
-        ctx.semaphore.signal_if_needed();
-        std::mem::forget(ctx);
-    },
-    |ctx| {
-        let ctx = unsafe{Arc::from_raw(ctx as *const SimpleWakeShared)};
-        std::mem::drop(ctx);
-    },
-);
 /**
 Blocks the calling thread until a future is ready.
 
-This implementation uses a condvar to sleep the thread.
+This implementation uses a condvar to sleep the thread, woken through a [std::task::Wake] waker.
+
+On WASI targets, which generally lack threads, the thread instead sleeps briefly between polls until
+the future is ready.
+
+# Panics
+If called from inside a future that `sleep_on` is already driving on this thread.  The outer future could not
+make progress until the inner one completed, which usually shows up as a deadlock rather than an error.  Wrap
+the call in [allow_nested_sleep_on] if blocking the outer future is really what you want.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn sleep_on<F: IntoFuture>(future: F) -> F::Output {
+    sleep_on_at(future, "sleep_on", core::panic::Location::caller())
+}
+
+/**
+[sleep_on], reporting `executor` as called from `location` in diagnostics.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+fn sleep_on_at<F: IntoFuture>(future: F, executor: &'static str, location: &'static core::panic::Location<'static>) -> F::Output {
+    let _sleeping = SleepingGuard::enter(false, executor, location);
+    let _call_site = call_site::enter(executor, location);
+    sleep_on_unguarded(future)
+}
+
+/**
+Blocks the calling thread until a future is ready, like [sleep_on], reporting its polls and wakes to `hooks`.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn sleep_on_with_hooks<F: IntoFuture>(future: F, hooks: Arc<dyn hooks::ExecutorHooks>) -> F::Output {
+    sleep_on(hooks::hooked(future.into_future(), hooks))
+}
+
+/**
+Like [sleep_on], but may be nested inside another sleep_on on purpose, as the runtimes in [aruntime] and
+[spawn_local] do.
+*/
+#[cfg(feature = "std")]
+pub(crate) fn sleep_on_nested<F: IntoFuture>(future: F, location: &'static core::panic::Location<'static>) -> F::Output {
+    let _sleeping = SleepingGuard::enter(true, "sleep_on", location);
+    sleep_on_unguarded(future)
+}
+
+/**
+Blocks the calling thread until a future is ready, like [sleep_on], catching a panic while it is polled.
+
+Returns the future's output, or the panic's payload, so a test can assert on how async code panics:
+
+```
+let payload = test_executors::catch_on(async { panic!("bad input") }).unwrap_err();
+assert_eq!(payload.downcast_ref::<&str>(), Some(&"bad input"));
+```
+
+The panic hook still runs, so the panic is printed as usual.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn catch_on<F: IntoFuture>(future: F) -> Result<F::Output, Box<dyn std::any::Any + Send>> {
+    let future = future.into_future();
+    let location = core::panic::Location::caller();
+    //the future is dropped before we return, so no broken state is observed after the panic
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| sleep_on_at(future, "catch_on", location)))
+}
+
+/**
+Blocks on a future where that is possible, for test helpers shared between native and wasm tests.
+
+Natively this is [sleep_on].  The wasm32-unknown-unknown main thread cannot block, so there the future is polled
+once: a future that is already ready, as many helpers' futures are, returns its output, and one that is not
+panics, explaining how to await it instead.
+
+```
+assert_eq!(test_executors::block_on_compat(async { 3 }), 3);
+```
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn block_on_compat<F: IntoFuture>(future: F) -> F::Output {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    match poll_once_pin(future) {
+        Poll::Ready(value) => value,
+        Poll::Pending => panic!("block_on_compat: `{}` is not ready, and the wasm32-unknown-unknown main thread cannot \
+            block until it is.  Make the helper async and `.await` the future from an #[async_test], or spawn it with \
+            spawn_local_with_handle and await the handle", core::any::type_name::<F::IntoFuture>()),
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    sleep_on(future)
+}
+
+/**
+How [sleep_on_with] waits between polls for the future to be woken.
+*/
+#[cfg(feature = "std")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ParkPolicy {
+    /// Park the thread until the future is woken, as [sleep_on] does.  Cheapest on CPU.
+    #[default]
+    Park,
+    /// Check for a wake `spins` times in a spinloop before parking, for lower latency when the future is
+    /// usually woken soon.
+    SpinThenPark { spins: u32 },
+    /// Park until the future is woken, but for no longer than the given duration before polling again anyway.
+    /// Useful for futures which poll for a condition without arranging to be woken.
+    ParkTimeout(std::time::Duration),
+}
+
+/**
+Blocks the calling thread until a future is ready, waiting between polls according to `policy`.
+
+On WASI targets, and under the `loom` and `shuttle` features, the policy is ignored and this behaves like
+[sleep_on].
+
+# Panics
+Like [sleep_on], if called from inside a future that sleep_on is already driving on this thread.
 */
-pub fn sleep_on<F: Future>(mut future: F) -> F::Output {
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn sleep_on_with<F: IntoFuture>(future: F, policy: ParkPolicy) -> F::Output {
+    #[cfg(any(target_os = "wasi", feature = "loom", feature = "shuttle"))]
+    {
+        let _ = policy;
+        sleep_on(future)
+    }
+    #[cfg(not(any(target_os = "wasi", feature = "loom", feature = "shuttle")))]
+    {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        struct Unpark {
+            thread: std::thread::Thread,
+            woken: AtomicBool,
+        }
+        impl std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref()
+            }
+            fn wake_by_ref(self: &Arc<Self>) {
+                crate::logging::trace!("waking");
+                self.woken.store(true, Ordering::Release);
+                self.thread.unpark();
+            }
+        }
+
+        let location = core::panic::Location::caller();
+        let _sleeping = SleepingGuard::enter(false, "sleep_on_with", location);
+        let _call_site = call_site::enter("sleep_on_with", location);
+        let future = future.into_future();
+        let unpark = Arc::new(Unpark { thread: std::thread::current(), woken: AtomicBool::new(false) });
+        let waker = Waker::from(unpark.clone());
+        let mut context = Context::from_waker(&waker);
+        let mut future = core::pin::pin!(future);
+        let woken = || unpark.woken.swap(false, Ordering::AcqRel);
+        loop {
+            crate::logging::trace!("polling future");
+            if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
+                crate::logging::trace!("future is ready");
+                return val;
+            }
+            crate::logging::trace!("future is not ready");
+            match policy {
+                ParkPolicy::Park => while !woken() {
+                    std::thread::park();
+                },
+                ParkPolicy::SpinThenPark { spins } => {
+                    let mut spun = 0;
+                    while !woken() {
+                        if spun < spins {
+                            spun += 1;
+                            core::hint::spin_loop();
+                        } else {
+                            std::thread::park();
+                        }
+                    }
+                }
+                ParkPolicy::ParkTimeout(max) => match std::time::Instant::now().checked_add(max) {
+                    Some(deadline) => while !woken() {
+                        let now = std::time::Instant::now();
+                        if now >= deadline {
+                            crate::logging::trace!("gave up waiting");
+                            break;
+                        }
+                        std::thread::park_timeout(deadline - now);
+                    },
+                    //too far off to be reached, so park until woken
+                    None => while !woken() {
+                        std::thread::park();
+                    },
+                },
+            }
+            crate::logging::trace!("woken");
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn sleep_on_unguarded<F: IntoFuture>(future: F) -> F::Output {
     //we inherit the parent dlog::context here.
-    let shared = Arc::new(SimpleWakeShared{semaphore: Semaphore::new(false)});
+    let future = future.into_future();
+    let shared = Arc::new(SimpleWakeShared::new());
     let local = shared.clone();
-    let raw_waker = RawWaker::new(Arc::into_raw(shared) as *const (), &CONDVAR_WAKER_VTABLE);
-    let waker = unsafe{Waker::from_raw(raw_waker)};
+    let waker = Waker::from(shared);
     let mut context = Context::from_waker(&waker);
     /*
     per docs,
@@ -103,35 +662,357 @@ pub fn sleep_on<F: Future>(mut future: F) -> F::Output {
 
     ergo, the lock must be locked when polling.
      */
-    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+    let mut future = core::pin::pin!(future);
 
     loop {
-        logwise::trace_sync!("polling future");
+        crate::logging::trace!("polling future");
         if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
-            logwise::trace_sync!("future is ready");
+            crate::logging::trace!("future is ready");
             return val;
         }
-        logwise::trace_sync!("future is not ready");
-        local.semaphore.wait();
-        logwise::trace_sync!("woken");
+        crate::logging::trace!("future is not ready");
+        local.wait();
+        crate::logging::trace!("woken");
+    }
+}
+
+/*
+loom and shuttle run their model threads on a single OS thread, so a thread-local cannot tell them apart.
+ */
+#[cfg(all(feature = "std", not(any(feature = "loom", feature = "shuttle"))))]
+std::thread_local! {
+    static SLEEPING: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+    static NESTING_ALLOWED: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+}
+
+/**
+Marks this thread as being inside [sleep_on], or another of the blocking executors, until dropped.
+*/
+#[cfg(feature = "std")]
+struct SleepingGuard {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    prior: bool,
+}
+
+#[cfg(feature = "std")]
+impl SleepingGuard {
+    /**
+    # Panics
+    If the thread is already inside sleep_on, naming `executor`, its caller `location` and the outer call site.
+    */
+    #[track_caller]
+    fn enter(nested: bool, executor: &'static str, location: &'static core::panic::Location<'static>) -> Self {
+        #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+        {
+            let prior = SLEEPING.with(|sleeping| sleeping.replace(true));
+            if prior && !nested && !NESTING_ALLOWED.with(|allowed| allowed.get()) {
+                let outer = call_site::current().map(|outer| outer.to_string()).unwrap_or_else(|| "sleep_on".to_string());
+                panic!("{executor} called at {location} from inside a future that {outer} is already driving on \
+                        this thread; the outer future cannot make progress until the inner one completes.  \
+                        Spawn the inner future instead, or wrap the call in allow_nested_sleep_on");
+            }
+            SleepingGuard { prior }
+        }
+        #[cfg(any(feature = "loom", feature = "shuttle"))]
+        {
+            let _ = (nested, executor, location);
+            SleepingGuard {}
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for SleepingGuard {
+    fn drop(&mut self) {
+        #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+        SLEEPING.with(|sleeping| sleeping.set(self.prior));
+    }
+}
+
+/**
+Runs `f`, allowing it to call [sleep_on] even though a future driven by sleep_on is running on this thread.
+
+The nested future runs to completion before `f` returns, blocking the outer future meanwhile.
+
+```
+use test_executors::{allow_nested_sleep_on, sleep_on};
+let value = sleep_on(async {
+    allow_nested_sleep_on(|| sleep_on(async { 2 }))
+});
+assert_eq!(value, 2);
+```
+*/
+#[cfg(feature = "std")]
+pub fn allow_nested_sleep_on<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    {
+        struct Restore(bool);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                NESTING_ALLOWED.with(|allowed| allowed.set(self.0));
+            }
+        }
+        let _restore = Restore(NESTING_ALLOWED.with(|allowed| allowed.replace(true)));
+        f()
     }
+    #[cfg(any(feature = "loom", feature = "shuttle"))]
+    f()
 }
 
 /**
 A function that spawns the given future and does not wait for it to complete.
 */
-pub fn spawn_on<F: Future + Send + 'static>(thread_name: &'static str, future: F) {
-    let prior_context = logwise::context::Context::current();
-    let new_context = logwise::context::Context::new_task(Some(prior_context), thread_name);
-    std::thread::Builder::new()
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn spawn_on<F: IntoFuture>(thread_name: &'static str, future: F)
+where
+    F::IntoFuture: Send + 'static,
+{
+    let future = future.into_future();
+    spawn_on_with(thread_name, move || future)
+}
+
+/**
+Like [spawn_on], but constructs the future on the new thread by calling `make_future`.
+
+Only the closure has to be `Send`, so the future may hold `!Send` state, such as an `Rc`, created there.
+
+```
+use std::rc::Rc;
+let (sender, receiver) = std::sync::mpsc::channel();
+test_executors::spawn_on_with("worker", move || {
+    let cache = Rc::new(5);
+    async move { sender.send(*cache).unwrap() }
+});
+assert_eq!(receiver.recv().unwrap(), 5);
+```
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn spawn_on_with<M, F>(thread_name: &'static str, make_future: M)
+where
+    M: FnOnce() -> F + Send + 'static,
+    F: IntoFuture,
+{
+    try_spawn_on_with(thread_name, make_future).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/**
+Like [spawn_on_with], but returns an [error::SpawnError] instead of panicking if the thread can't be spawned.
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn try_spawn_on_with<M, F>(thread_name: &'static str, make_future: M) -> Result<(), error::SpawnError>
+where
+    M: FnOnce() -> F + Send + 'static,
+    F: IntoFuture,
+{
+    #[cfg(feature = "logwise")]
+    let new_context = {
+        let prior_context = logwise::context::Context::current();
+        logwise::context::Context::new_task(Some(prior_context), thread_name)
+    };
+    let registration = debug::Registration::new(thread_name, core::panic::Location::caller());
+    crate::sys::thread::Builder::new()
         .name(thread_name.to_string())
         .spawn(move || {
+            #[cfg(feature = "logwise")]
             let pushed_id = new_context.context_id();
+            #[cfg(feature = "logwise")]
             logwise::context::Context::set_current(new_context);
 
-            sleep_on(future);
+            let future = make_future().into_future();
+            sleep_on(registration.track(crate::logging::instrument!(future, thread_name)));
+            #[cfg(feature = "logwise")]
             logwise::context::Context::pop(pushed_id);
-        }).expect("Cant spawn thread");
+        }).map(drop).map_err(|e| error::SpawnError::new(thread_name, e))
+}
+
+/**
+Spawns the given future, returning a [remote::RemoteHandle] that resolves with its output.
+
+Dropping the handle cancels the task, dropping the future the next time it would be polled, unless
+[remote::RemoteHandle::detach_on_drop] was called.  Natively the future runs on a new thread, as with [spawn_on];
+on wasm32-unknown-unknown it runs on the browser's event loop.
+*/
+#[cfg(feature = "std")]
+pub fn spawn_with_handle<F: Future + Send + 'static>(future: F) -> remote::RemoteHandle<F::Output>
+where
+    F::Output: Send,
+{
+    let (task, handle) = remote::remote(future);
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_futures::spawn_local(task);
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    spawn_on("spawn_with_handle", task);
+    handle
+}
+
+/**
+Runs a blocking closure on a helper thread, returning a future that resolves with its result.
+
+Awaiting it wakes the task when the closure finishes, so blocking calls (filesystem or DNS, say) can be mixed
+into async code without stalling a single-threaded executor.  If the closure panics, awaiting the future resumes
+the panic.  The closure runs to completion even if the future is dropped.
+
+```
+let length = test_executors::sleep_on(test_executors::spawn_blocking(|| std::fs::read("Cargo.toml").map(|b| b.len())));
+assert!(length.unwrap() > 0);
+```
+
+Like [spawn_on], this is not available at runtime on wasm32 targets, which generally lack threads.
+*/
+#[cfg(feature = "std")]
+pub fn spawn_blocking<T, F>(f: F) -> impl Future<Output = T> + Send
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (task, handle) = remote::remote(async move { std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) });
+    spawn_on("spawn_blocking", task);
+    let handle = handle.detach_on_drop();
+    async move {
+        match handle.await {
+            Ok(value) => value,
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+}
+
+/**
+Spawns the given future and does not wait for it to complete, on every platform.
+
+Natively this is [spawn_on], running the future on a new thread named `label`.  On wasm32-unknown-unknown, where
+threads are not available, the future is handed to the browser's event loop via `wasm_bindgen_futures::spawn_local`
+instead.  Either way the caller continues immediately and the future's output is discarded.
+
+Like [spawn_on], this is not available at runtime on WASI targets, which generally lack threads.
+*/
+#[cfg(feature = "std")]
+pub fn spawn_detached<F: Future + Send + 'static>(future: F, label: &'static str) {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        crate::logging::info!("spawned future: {label}", label = label);
+        wasm_bindgen_futures::spawn_local(async move {
+            future.await;
+        });
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    spawn_on(label, async move {
+        future.await;
+    });
+}
+
+/**
+Runs a future that need not be `Send` on the current thread.
+
+On wasm32-unknown-unknown this hands the future to the browser's event loop via
+`wasm_bindgen_futures::spawn_local` and returns immediately, since the main thread cannot block there.
+Elsewhere, it blocks until the future completes, as with [sleep_on].
+*/
+#[cfg(feature = "std")]
+#[track_caller]
+pub fn spawn_local<F: Future<Output = ()> + 'static>(future: F) {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_futures::spawn_local(future);
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    sleep_on_nested(future, core::panic::Location::caller());
+}
+
+/**
+Queues a future that need not be `Send` to run on the current thread, without waiting for it.
+
+On wasm32-unknown-unknown this is `wasm_bindgen_futures::spawn_local`, and the browser's event loop runs the future.
+Elsewhere the future is queued on a per-thread run queue, and runs when [run_local_tasks] is called on this
+thread, so code written for "spawn and continue" behaves the same on both.  When called from a task that is
+already being run by [run_local_tasks] (or by a [deterministic::DeterministicRuntime]), the future joins that
+run.
+*/
+#[cfg(feature = "std")]
+pub fn spawn_local_detached<F: Future<Output = ()> + 'static>(future: F) {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_futures::spawn_local(future);
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    if let Err(task) = scheduler::spawn_current(Box::pin(future)) {
+        LOCAL_QUEUE.with(|queue| queue.borrow_mut().push(task));
+    }
+}
+
+#[cfg(all(feature = "std", not(all(target_arch = "wasm32", target_os = "unknown"))))]
+std::thread_local! {
+    static LOCAL_QUEUE: std::cell::RefCell<Vec<scheduler::LocalTask>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/**
+Runs the futures queued by [spawn_local_detached] on this thread, including any they queue in turn, until all of
+them have completed.  Tasks are polled in the order they were woken.
+
+On wasm32-unknown-unknown the browser runs such futures itself, and this does nothing.
+*/
+#[cfg(feature = "std")]
+pub fn run_local_tasks() {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        let mut scheduler = scheduler::Scheduler::new();
+        for task in LOCAL_QUEUE.with(|queue| std::mem::take(&mut *queue.borrow_mut())) {
+            scheduler.spawn(task);
+        }
+        scheduler.run_all(&mut |_| Some(0));
+    }
+}
+
+/**
+Starts an event loop on a new thread named `thread_name`, returning a [local::LocalSpawner] that runs futures
+there.
+
+The futures are constructed on that thread by `Send` closures, and need not be `Send` themselves, which suits
+tests of single-threaded actors, GUI code or FFI bindings tied to one thread.
+
+```
+use std::rc::Rc;
+let spawner = test_executors::spawn_local_on("actor");
+let handle = spawner.spawn(|| {
+    let state = Rc::new(2);
+    async move { *state * 2 }
+});
+assert_eq!(handle.join(), 4);
+```
+*/
+#[cfg(feature = "std")]
+pub fn spawn_local_on(thread_name: &'static str) -> local::LocalSpawner {
+    local::LocalSpawner::start(thread_name)
+}
+
+/**
+Spawns a sibling task onto the single-threaded runtime running the current future, returning a handle to its
+output, which knows the task's [event_log::TaskId].
+
+This works inside [current_thread::CurrentThreadRuntime], [deterministic::DeterministicRuntime],
+[priority::PriorityRuntime] and [run_local_tasks].  The task need not be `Send`.
+
+# Panics
+If none of those is running on this thread.
+*/
+#[cfg(feature = "std")]
+pub fn spawn_local_task<F: Future + 'static>(future: F) -> local::LocalJoinHandle<F::Output> {
+    let (sender, handle) = local::channel();
+    match scheduler::spawn_current(Box::pin(async move { sender.send(future.await) })) {
+        Ok(id) => handle.with_task_id(id),
+        Err(_) => panic!("spawn_local_task called outside of a single-threaded runtime"),
+    }
+}
+
+/**
+Like [spawn_local], but returns a handle that completes with the future's output.
+
+This lets a wasm test await a task it spawned.  Natively, [spawn_local] blocks, so the handle is already
+finished when this returns.
+*/
+#[cfg(feature = "std")]
+pub fn spawn_local_with_handle<F: Future + 'static>(future: F) -> local::LocalJoinHandle<F::Output> {
+    let (sender, handle) = local::channel();
+    spawn_local(async move { sender.send(future.await) });
+    handle
 }
 
 /**
@@ -144,9 +1025,11 @@ let mut future = PendForever;
 let result = test_executors::poll_once(std::pin::Pin::new(&mut future));
 ```
 */
+#[track_caller]
 pub fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
-    let mut context = new_context();
-    future.poll(&mut context)
+    #[cfg(feature = "std")]
+    let _call_site = call_site::enter("poll_once", core::panic::Location::caller());
+    noop_waker::with_noop_context(|context| future.poll(context))
 }
 
 /**
@@ -163,18 +1046,20 @@ let result = test_executors::poll_once_pin(future);
 
 The main drawback of this function is that by transferring ownership of the future to the function, you lose the ability to poll the future again.
 */
-pub fn poll_once_pin<F: Future>(future: F) -> Poll<F::Output> {
-    let mut context = new_context();
-    let pinned = std::pin::pin!(future);
-    let output = pinned.poll(&mut context);
-    output
+#[track_caller]
+pub fn poll_once_pin<F: IntoFuture>(future: F) -> Poll<F::Output> {
+    #[cfg(feature = "std")]
+    let _call_site = call_site::enter("poll_once_pin", core::panic::Location::caller());
+    let future = future.into_future();
+    let pinned = core::pin::pin!(future);
+    noop_waker::with_noop_context(|context| pinned.poll(context))
 }
 
-#[cfg(test)] mod tests {
+#[cfg(all(test, feature = "std"))] mod tests {
     use std::future::Future;
     use std::task::Poll;
 
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
     #[test] fn test_sleep_reentrant() {
@@ -195,8 +1080,304 @@ pub fn poll_once_pin<F: Future>(future: F) -> Poll<F::Output> {
         super::sleep_on(f);
     }
 
+    #[test] fn test_sleep_wake_from_thread() {
+        struct F(Option<std::thread::JoinHandle<()>>);
+        impl Future for F {
+            type Output = ();
+            fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+                match self.0.take() {
+                    None => {
+                        let waker = cx.waker().clone();
+                        self.0 = Some(std::thread::spawn(move || waker.wake()));
+                        Poll::Pending
+                    }
+                    Some(handle) => {
+                        handle.join().unwrap();
+                        Poll::Ready(())
+                    }
+                }
+            }
+        }
+        super::sleep_on(F(None));
+    }
+
 
 
+    #[crate::async_test(timeout_ms = 10_000)] async fn with_timeout() {
+        let f = async {
+            "hello world"
+        };
+        assert_eq!(f.await, "hello world");
+    }
+
+    #[crate::async_test(timeout = "none")] async fn without_timeout() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    #[crate::async_test(executor = "spin")] async fn on_spin() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    #[crate::async_test(executor = "spawn", timeout_ms = 10_000)] async fn on_spawn() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    #[crate::async_test]
+    #[should_panic(expected = "boom")]
+    async fn forwards_should_panic() {
+        panic!("boom");
+    }
+
+    #[crate::async_test(executor = "spawn")]
+    #[should_panic(expected = "boom")]
+    async fn spawn_resumes_panic() {
+        panic!("boom");
+    }
+
+    #[crate::async_test] async fn returns_result() -> Result<(), std::num::ParseIntError> {
+        let parsed: u8 = async { "42" }.await.parse()?;
+        assert_eq!(parsed, 42);
+        Ok(())
+    }
+
+    #[crate::async_test]
+    #[ignore]
+    async fn forwards_ignore() {
+        panic!("ignored tests are not run");
+    }
+
+    #[crate::async_test]
+    #[cfg(any())]
+    async fn forwards_cfg() {
+        this_does_not_exist();
+    }
+
+    static REPEATED: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    #[crate::async_test(repeat = 5)] async fn repeats() {
+        REPEATED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    static ATTEMPTS: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    #[crate::async_test(detect_flaky = 10)]
+    #[should_panic(expected = "flaky")]
+    async fn detects_flaky() {
+        static RUNS: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        //every run happens, so the third fails even though the first passes
+        let run = RUNS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        assert!(run != 2, "flaky");
+    }
+
+    #[crate::async_test(detect_flaky = 4, chaos_seeds = 2)] async fn detect_flaky_cycles_seeds() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    static SERIAL_RUNNING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    async fn runs_alone() {
+        assert!(!SERIAL_RUNNING.swap(true, std::sync::atomic::Ordering::SeqCst), "serial tests overlapped");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        SERIAL_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[crate::async_test(serial = "serial_tests")] async fn serial_first() {
+        runs_alone().await;
+    }
+
+    #[crate::async_test(serial = "serial_tests", executor = "spawn")] async fn serial_second() {
+        runs_alone().await;
+    }
+
+    #[crate::async_test(retries = 3)] async fn retries_until_pass() {
+        let attempt = ATTEMPTS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        assert!(attempt >= 2, "flaky failure");
+    }
+
+    #[crate::async_test(retries = 1)]
+    #[should_panic(expected = "always fails")]
+    async fn retries_exhausted() {
+        panic!("always fails");
+    }
+
+    #[crate::async_test(matrix)] async fn every_executor() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    #[crate::async_test(global_runtime)] async fn installs_global_runtime() {
+        assert!(some_executor::global_executor::global_executor(|e| e.is_some()));
+        assert!(some_executor::current_executor::current_executor().is_some());
+    }
+
+    async fn answer() -> u32 {
+        42
+    }
+    static TORN_DOWN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+    async fn tear_down() {
+        TORN_DOWN.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+    #[crate::async_test(setup = answer, teardown = tear_down)] async fn uses_fixture(answer: u32) {
+        assert_eq!(answer, 42);
+    }
+
+    #[test] fn teardown_after_panic() {
+        let torn_down = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = torn_down.clone();
+        let result = std::panic::catch_unwind(|| {
+            super::sleep_on(crate::macro_support::teardown_after(async { panic!("boom") }, async move {
+                flag.store(true, std::sync::atomic::Ordering::Relaxed);
+            }))
+        });
+        assert!(result.is_err());
+        assert!(torn_down.load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[crate::async_test]
+    #[case(1, 2, 3)]
+    #[case::negative(-1, 1, 0)]
+    async fn adds(a: i32, b: i32, sum: i32) {
+        assert_eq!(async { a + b }.await, sum);
+    }
+
+    #[crate::async_test(wasm_env = "node")] async fn ignores_wasm_env_natively() -> Result<(), std::fmt::Error> {
+        Ok(())
+    }
+
+    #[crate::async_test(wasm(run_in = "browser"))] async fn configures_wasm() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    #[crate::async_bench(iterations = 10, warmup = 1)] async fn benches() {
+        assert_eq!(async { 1 }.await, 1);
+    }
+
+    #[crate::async_test(chaos_seeds = 4)]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    async fn chaos() {
+        let done = std::rc::Rc::new(std::cell::Cell::new(false));
+        let task_done = done.clone();
+        crate::deterministic::spawn(async move { task_done.set(true) });
+        std::future::poll_fn(|cx| {
+            if done.get() {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }).await;
+    }
+
+    #[crate::async_test] async fn joins_local_task() {
+        let value = std::rc::Rc::new(21);
+        let handle = crate::spawn_local_with_handle(async move { *value * 2 });
+        assert_eq!(handle.await, 42);
+    }
+
+    #[test] fn spawn_blocking_resumes_panics() {
+        let slow = super::spawn_blocking(|| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            5
+        });
+        assert_eq!(super::sleep_on(slow), 5);
+        let payload = super::catch_on(super::spawn_blocking(|| panic!("blocking"))).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"blocking"));
+    }
+
+    #[test] fn catch_on_recovers() {
+        let payload = super::catch_on(async { panic!("{}", 2 + 2) }).unwrap_err();
+        assert_eq!(payload.downcast_ref::<String>().map(String::as_str), Some("4"));
+        //the thread is no longer inside sleep_on
+        assert_eq!(super::catch_on(async { 1 }).unwrap(), 1);
+    }
+
+    #[test] fn detached_local_tasks() {
+        let order = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let first = order.clone();
+        let second = order.clone();
+        crate::spawn_local_detached(async move {
+            first.borrow_mut().push(1);
+            let third = first.clone();
+            crate::spawn_local_detached(async move { third.borrow_mut().push(3) });
+        });
+        crate::spawn_local_detached(async move { second.borrow_mut().push(2) });
+        assert!(order.borrow().is_empty());
+        crate::run_local_tasks();
+        assert_eq!(*order.borrow(), [1, 2, 3]);
+    }
+
+    #[test] fn spawns_detached() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        crate::spawn_detached(async move { sender.send(async { 7 }.await).unwrap() }, "spawns_detached");
+        assert_eq!(receiver.recv().unwrap(), 7);
+    }
+
+    #[test] fn spin_yield_on_other_thread() {
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let setter = done.clone();
+        let thread = std::thread::spawn(move || setter.store(true, std::sync::atomic::Ordering::Release));
+        super::spin_yield_on(std::future::poll_fn(|_| {
+            if done.load(std::sync::atomic::Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+        thread.join().unwrap();
+    }
+
+    #[test] fn sleep_on_with_policies() {
+        use super::ParkPolicy;
+        for policy in [ParkPolicy::Park, ParkPolicy::SpinThenPark { spins: 100 }, ParkPolicy::ParkTimeout(std::time::Duration::from_secs(10)), ParkPolicy::ParkTimeout(std::time::Duration::MAX)] {
+            let mut thread = None;
+            super::sleep_on_with(std::future::poll_fn(|cx| match thread.take() {
+                None => {
+                    let waker = cx.waker().clone();
+                    thread = Some(std::thread::spawn(move || waker.wake()));
+                    Poll::Pending
+                }
+                Some(thread) => {
+                    thread.join().unwrap();
+                    Poll::Ready(())
+                }
+            }), policy);
+        }
+        //never woken, so only the timeout gets it polled again
+        let mut polls = 0;
+        super::sleep_on_with(std::future::poll_fn(|_| {
+            polls += 1;
+            if polls == 3 { Poll::Ready(()) } else { Poll::Pending }
+        }), ParkPolicy::ParkTimeout(std::time::Duration::from_millis(1)));
+    }
+
+    #[test] fn accepts_into_future() {
+        struct Builder(u8);
+        impl std::future::IntoFuture for Builder {
+            type Output = u8;
+            type IntoFuture = std::future::Ready<u8>;
+            fn into_future(self) -> Self::IntoFuture {
+                std::future::ready(self.0)
+            }
+        }
+        assert_eq!(super::spin_on(Builder(1)), 1);
+        assert_eq!(super::sleep_on(Builder(2)), 2);
+        assert_eq!(super::poll_once_pin(Builder(3)), Poll::Ready(3));
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    fn nested_sleep_on_panics() {
+        let line = line!() + 1;
+        let result = std::panic::catch_unwind(|| super::sleep_on(async { super::sleep_on(async {}) }));
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        let expected = format!("sleep_on called at {file}:{line}:74 from inside a future that sleep_on called at {file}:{line}:50 is already driving", file = file!());
+        assert!(message.starts_with(&expected), "{message}");
+        //the flag is reset as the panic unwinds
+        super::sleep_on(async {});
+        let result = std::panic::catch_unwind(|| super::sleep_on(async { super::spin_on(async {}) }));
+        assert!(result.unwrap_err().downcast::<String>().unwrap().starts_with("spin_on called at "));
+        let result = std::panic::catch_unwind(|| super::spin_on(async { crate::watchdog::sleep_on_timeout(async {}, std::time::Duration::from_secs(1)) }));
+        assert!(result.unwrap_err().downcast::<String>().unwrap().starts_with("sleep_on_timeout called at "));
+        assert_eq!(super::sleep_on(async { super::allow_nested_sleep_on(|| super::sleep_on(async { 1 })) }), 1);
+    }
+
     #[crate::async_test] async fn hello_world() {
         let f = async {
             "hello world"
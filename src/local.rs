@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Support for futures that run on the current thread, and need not be `Send`.
+
+[LocalSpawner], returned by [crate::spawn_local_on], runs such futures on a dedicated thread instead: each is
+constructed there by a `Send` closure, so it may hold `Rc`s and other thread-bound state, while its output is
+returned to the spawning thread through a [SpawnerJoinHandle].
+*/
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use crate::event_log::TaskId;
+
+enum Slot<T> {
+    Pending(Option<Waker>),
+    Ready(T),
+    Taken,
+}
+
+/**
+Awaits the output of a task spawned by [crate::spawn_local_task] or [crate::spawn_local_with_handle].
+*/
+pub struct LocalJoinHandle<T> {
+    slot: Rc<RefCell<Slot<T>>>,
+    task_id: Option<TaskId>,
+}
+
+/**
+The task's half of a [LocalJoinHandle].
+*/
+pub(crate) struct LocalSender<T>(Rc<RefCell<Slot<T>>>);
+
+/**
+Creates a oneshot channel from a local task to its [LocalJoinHandle].
+*/
+pub(crate) fn channel<T>() -> (LocalSender<T>, LocalJoinHandle<T>) {
+    let slot = Rc::new(RefCell::new(Slot::Pending(None)));
+    (LocalSender(slot.clone()), LocalJoinHandle { slot, task_id: None })
+}
+
+impl<T> LocalSender<T> {
+    pub(crate) fn send(self, value: T) {
+        let prior = self.0.replace(Slot::Ready(value));
+        if let Slot::Pending(Some(waker)) = prior {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> LocalJoinHandle<T> {
+    /**
+    Whether the task has completed.
+    */
+    pub fn is_finished(&self) -> bool {
+        !matches!(*self.slot.borrow(), Slot::Pending(_))
+    }
+
+    /**
+    The id of the task in the single-threaded runtime running it, or `None` if it was not spawned onto one (by
+    [crate::spawn_local_with_handle]).
+    */
+    pub fn task_id(&self) -> Option<TaskId> {
+        self.task_id
+    }
+
+    pub(crate) fn with_task_id(mut self, id: TaskId) -> Self {
+        self.task_id = Some(id);
+        self
+    }
+}
+
+impl<T> Future for LocalJoinHandle<T> {
+    type Output = T;
+
+    /**
+    # Panics
+    If polled again after returning the task's output.
+    */
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.borrow_mut();
+        match std::mem::replace(&mut *slot, Slot::Taken) {
+            Slot::Ready(value) => Poll::Ready(value),
+            Slot::Pending(_) => {
+                *slot = Slot::Pending(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            Slot::Taken => panic!("LocalJoinHandle polled after completion"),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for LocalJoinHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalJoinHandle").field("task_id", &self.task_id).field("finished", &self.is_finished()).finish()
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Inbox {
+    jobs: VecDeque<Job>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/**
+Spawns futures onto an event loop running on a dedicated thread.  See [crate::spawn_local_on].
+
+Dropping the spawner stops the event loop once the futures sent to it have been started, dropping any that are
+still running, and waits for the thread to exit.  If the thread panicked, so does the drop.
+*/
+pub struct LocalSpawner {
+    inbox: Arc<Mutex<Inbox>>,
+    thread: Option<crate::sys::thread::JoinHandle<()>>,
+}
+
+impl LocalSpawner {
+    pub(crate) fn start(thread_name: &'static str) -> Self {
+        let inbox = Arc::new(Mutex::new(Inbox { jobs: VecDeque::new(), waker: None, closed: false }));
+        let event_loop = inbox.clone();
+        let thread = crate::sys::thread::Builder::new()
+            .name(thread_name.to_string())
+            .spawn(move || {
+                crate::logging::trace!("local spawner started");
+                crate::current_thread::CurrentThreadRuntime::new().block_on(std::future::poll_fn(|cx| loop {
+                    let mut inbox = event_loop.lock().unwrap();
+                    match inbox.jobs.pop_front() {
+                        Some(job) => {
+                            drop(inbox);
+                            job();
+                        }
+                        None if inbox.closed => return Poll::Ready(()),
+                        None => {
+                            inbox.waker = Some(cx.waker().clone());
+                            return Poll::Pending;
+                        }
+                    }
+                }));
+                crate::logging::trace!("local spawner stopped");
+            }).expect("Cant spawn thread");
+        LocalSpawner { inbox, thread: Some(thread) }
+    }
+
+    /**
+    Calls `make_future` on the spawner's thread, and runs the future it returns there as a task of the event loop.
+
+    The future may spawn siblings with [crate::spawn_local_task].
+    */
+    pub fn spawn<M, F>(&self, make_future: M) -> SpawnerJoinHandle<F::Output>
+    where
+        M: FnOnce() -> F + Send + 'static,
+        F: Future + 'static,
+        F::Output: Send + 'static,
+    {
+        let slot = Arc::new(Mutex::new(Slot::Pending(None)));
+        let sender = SpawnerSender(Some(slot.clone()));
+        self.send(Box::new(move || {
+            let future = make_future();
+            crate::spawn_local_detached(async move { sender.send(future.await) });
+        }));
+        SpawnerJoinHandle(slot)
+    }
+
+    fn send(&self, job: Job) {
+        let mut inbox = self.inbox.lock().unwrap();
+        inbox.jobs.push_back(job);
+        let waker = inbox.waker.take();
+        drop(inbox);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for LocalSpawner {
+    fn drop(&mut self) {
+        let mut inbox = self.inbox.lock().unwrap_or_else(|e| e.into_inner());
+        inbox.closed = true;
+        let waker = inbox.waker.take();
+        drop(inbox);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        if let Some(thread) = self.thread.take() {
+            if let Err(panic) = thread.join() {
+                if !std::thread::panicking() {
+                    std::panic::resume_unwind(panic);
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for LocalSpawner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalSpawner").finish_non_exhaustive()
+    }
+}
+
+/**
+The task's half of a [SpawnerJoinHandle], which abandons the handle if the task is dropped before completing.
+*/
+struct SpawnerSender<T>(Option<Arc<Mutex<Slot<T>>>>);
+
+impl<T> SpawnerSender<T> {
+    fn send(mut self, value: T) {
+        let slot = self.0.take().expect("sent once");
+        let prior = std::mem::replace(&mut *slot.lock().unwrap(), Slot::Ready(value));
+        if let Slot::Pending(Some(waker)) = prior {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for SpawnerSender<T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0.take() {
+            let prior = std::mem::replace(&mut *slot.lock().unwrap_or_else(|e| e.into_inner()), Slot::Taken);
+            if let Slot::Pending(Some(waker)) = prior {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/**
+Awaits the output of a task spawned by [LocalSpawner::spawn], from any thread.
+*/
+pub struct SpawnerJoinHandle<T>(Arc<Mutex<Slot<T>>>);
+
+impl<T> SpawnerJoinHandle<T> {
+    /**
+    Whether the task has completed.
+    */
+    pub fn is_finished(&self) -> bool {
+        !matches!(*self.0.lock().unwrap(), Slot::Pending(_))
+    }
+
+    /**
+    Blocks the calling thread until the task completes, returning its output.
+
+    # Panics
+    If the spawner's thread dropped the task before it completed, for example because it panicked.
+    */
+    pub fn join(self) -> T {
+        crate::sleep_on(self)
+    }
+}
+
+impl<T> Future for SpawnerJoinHandle<T> {
+    type Output = T;
+
+    /**
+    # Panics
+    If polled again after returning the task's output, or if the spawner's thread dropped the task before it
+    completed.
+    */
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap();
+        match std::mem::replace(&mut *slot, Slot::Taken) {
+            Slot::Ready(value) => Poll::Ready(value),
+            Slot::Pending(_) => {
+                *slot = Slot::Pending(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            Slot::Taken => panic!("SpawnerJoinHandle polled after completion, or its task was dropped by the spawner thread"),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for SpawnerJoinHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpawnerJoinHandle").field("finished", &self.is_finished()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    #[test]
+    fn runs_non_send_futures_on_its_thread() {
+        let spawner = crate::spawn_local_on("actor");
+        let first = spawner.spawn(|| {
+            let cache = Rc::new(std::thread::current().name().map(str::to_string));
+            async move {
+                let child = crate::spawn_local_task(async { 2 }).await;
+                (*cache).clone().map(|name| (name, child))
+            }
+        });
+        assert_eq!(first.join(), Some(("actor".to_string(), 2)));
+        let second = spawner.spawn(|| async { 3 });
+        assert_eq!(second.join(), 3);
+    }
+}
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Capturing the executors' log output for one run.
+
+Turning on trace output globally to debug one async test buries it under every other test's.  [capture_logs]
+instead collects what the executors log while a closure runs on the current thread, and returns it with the
+closure's result, so a test can assert on it, or print it only when something went wrong:
+
+```
+# #[cfg(feature = "log-capture")] {
+use test_executors::log_capture::capture_logs;
+let (value, logs) = capture_logs(|| test_executors::sleep_on(async { 4 }));
+assert_eq!(value, 4);
+assert!(logs.iter().any(|record| record.message() == "future is ready"));
+# }
+```
+
+Capturing needs the `log-capture` feature, which is on by default; without it, only [Level] is left, for
+[crate::config::Config::with_log_level].  Records are collected whether or not the `logwise` or `tracing` features are enabled, and are still sent to those
+backends as usual.  Only the instrumentation of this crate is captured: what the code under test, or any other
+crate, logs through logwise or tracing does not appear, so this is no substitute for those backends' own
+subscribers.  And only what is logged on the calling thread is captured; runtimes that poll on the calling
+thread, like [crate::sleep_on] and [crate::current_thread::CurrentThreadRuntime], log there, while
+[crate::spawn_on] logs on its own thread.
+*/
+
+#[cfg(feature = "log-capture")]
+use std::cell::RefCell;
+use std::fmt::Display;
+
+/**
+The level of a [LogRecord].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Info,
+    Warning,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Level::Trace => "TRACE",
+            Level::Info => "INFO",
+            Level::Warning => "WARN",
+        })
+    }
+}
+
+/**
+A message logged by the executors, as collected by [capture_logs].
+*/
+#[cfg(feature = "log-capture")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LogRecord {
+    level: Level,
+    message: String,
+}
+
+#[cfg(feature = "log-capture")]
+impl LogRecord {
+    /**
+    How severe the message is.
+    */
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /**
+    The message, formatted, without its level.
+    */
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+#[cfg(feature = "log-capture")]
+impl Display for LogRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.level, self.message)
+    }
+}
+
+#[cfg(feature = "log-capture")]
+thread_local! {
+    //one buffer per nested capture_logs, innermost last
+    static CAPTURES: RefCell<Vec<Vec<LogRecord>>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+Adds a record to the captures on this thread, formatting it only if there are any.
+*/
+#[cfg(feature = "log-capture")]
+pub(crate) fn record(level: Level, message: impl FnOnce() -> String) {
+    //try_with: logging from a thread-local destructor is not an error
+    let _ = CAPTURES.try_with(|captures| {
+        let mut captures = captures.borrow_mut();
+        if captures.is_empty() {
+            return;
+        }
+        let record = LogRecord { level, message: message() };
+        for capture in captures.iter_mut() {
+            capture.push(record.clone());
+        }
+    });
+}
+
+/**
+Pops the capture pushed by [capture_logs], even if `f` panics.
+*/
+#[cfg(feature = "log-capture")]
+struct CaptureGuard;
+
+#[cfg(feature = "log-capture")]
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURES.with(|captures| captures.borrow_mut().pop());
+    }
+}
+
+/**
+Runs `f`, returning its result and the records the executors logged on this thread meanwhile.
+
+Captures nest: an outer capture also receives the records of an inner one.
+*/
+#[cfg(feature = "log-capture")]
+pub fn capture_logs<T>(f: impl FnOnce() -> T) -> (T, Vec<LogRecord>) {
+    CAPTURES.with(|captures| captures.borrow_mut().push(Vec::new()));
+    let guard = CaptureGuard;
+    let value = f();
+    let records = CAPTURES.with(|captures| std::mem::take(captures.borrow_mut().last_mut().expect("capture pushed")));
+    drop(guard);
+    (value, records)
+}
+
+#[cfg(all(test, feature = "log-capture"))]
+mod tests {
+    use super::{capture_logs, Level};
+
+    #[test]
+    fn captures_nested_runs() {
+        let (inner, outer) = capture_logs(|| {
+            let ((), inner) = capture_logs(|| crate::sleep_on(crate::future_util::yield_now()));
+            inner
+        });
+        assert_eq!(inner.iter().filter(|record| record.message() == "polling future").count(), 2);
+        assert!(inner.iter().all(|record| record.level() == Level::Trace));
+        assert_eq!(outer, inner);
+        assert!(capture_logs(|| ()).1.is_empty());
+    }
+}
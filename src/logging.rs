@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Internal logging shims.
+
+All instrumentation in the crate goes through these macros, which forward to logwise, tracing and
+[crate::log_capture] depending on which features are enabled.  Messages below the [crate::config] log level are
+dropped before reaching any of them.  With none of the three features, the macros compile to nothing.
+*/
+
+macro_rules! trace {
+    ($fmt:literal $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[cfg(any(feature = "logwise", feature = "tracing", feature = "log-capture"))]
+        if $crate::config::log_enabled($crate::log_capture::Level::Trace) {
+            #[cfg(feature = "log-capture")]
+            $crate::log_capture::record($crate::log_capture::Level::Trace, || format!($fmt $(, $name = $value)*));
+            #[cfg(feature = "logwise")]
+            logwise::trace_sync!($fmt $(, $name = $value)*);
+            #[cfg(feature = "tracing")]
+            tracing::trace!($fmt $(, $name = $value)*);
+        }
+        //with nowhere to send them, only type-check the arguments without evaluating them
+        #[cfg(not(any(feature = "logwise", feature = "tracing", feature = "log-capture")))]
+        if false {
+            $(let _ = &$value;)*
+        }
+    }};
+}
+
+macro_rules! info {
+    ($fmt:literal $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[cfg(any(feature = "logwise", feature = "tracing", feature = "log-capture"))]
+        if $crate::config::log_enabled($crate::log_capture::Level::Info) {
+            #[cfg(feature = "log-capture")]
+            $crate::log_capture::record($crate::log_capture::Level::Info, || format!($fmt $(, $name = $value)*));
+            #[cfg(feature = "logwise")]
+            logwise::info_sync!($fmt $(, $name = $value)*);
+            #[cfg(feature = "tracing")]
+            tracing::info!($fmt $(, $name = $value)*);
+        }
+        #[cfg(not(any(feature = "logwise", feature = "tracing", feature = "log-capture")))]
+        if false {
+            $(let _ = &$value;)*
+        }
+    }};
+}
+
+macro_rules! warning {
+    ($fmt:literal $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[cfg(any(feature = "logwise", feature = "tracing", feature = "log-capture"))]
+        if $crate::config::log_enabled($crate::log_capture::Level::Warning) {
+            #[cfg(feature = "log-capture")]
+            $crate::log_capture::record($crate::log_capture::Level::Warning, || format!($fmt $(, $name = $value)*));
+            #[cfg(feature = "logwise")]
+            logwise::warn_sync!($fmt $(, $name = $value)*);
+            #[cfg(feature = "tracing")]
+            tracing::warn!($fmt $(, $name = $value)*);
+        }
+        #[cfg(not(any(feature = "logwise", feature = "tracing", feature = "log-capture")))]
+        if false {
+            $(let _ = &$value;)*
+        }
+    }};
+}
+
+/**
+Wraps a future in a tracing span named after `$label`, so that everything logged while it is polled
+is attributed to it.  Without the `tracing` feature, this is the identity.
+*/
+#[cfg(feature = "tracing")]
+macro_rules! instrument {
+    ($future:expr, $label:expr) => {{
+        let span = tracing::info_span!("task", label = $label);
+        tracing::Instrument::instrument($future, span)
+    }};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! instrument {
+    ($future:expr, $label:expr) => {
+        $future
+    };
+}
+
+pub(crate) use trace;
+pub(crate) use info;
+pub(crate) use warning;
+pub(crate) use instrument;
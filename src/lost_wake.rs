@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+An executor that detects lost wakeups.
+
+A future that returns `Pending` must arrange to be woken, usually by storing a clone of the waker somewhere that
+will call it later.  If it forgets, [crate::sleep_on] waits forever, and the test hangs without saying why.
+[check_on] drives a future like sleep_on, but watches its waker: if the future returns `Pending` without waking
+itself or keeping the waker, or if every copy it kept is dropped without waking it, nothing can ever wake it, and
+check_on returns a [LostWake] naming the future's type and the poll where this happened.
+*/
+
+use std::fmt::Display;
+use std::future::{Future, IntoFuture};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+/**
+How often [check_on] looks at the waker while waiting to be woken.
+*/
+const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+The error returned when a future could no longer be woken.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LostWake {
+    future: &'static str,
+    poll: u64,
+    called_at: &'static std::panic::Location<'static>,
+}
+
+impl LostWake {
+    pub(crate) fn new(future: &'static str, poll: u64, called_at: &'static std::panic::Location<'static>) -> Self {
+        LostWake { future, poll, called_at }
+    }
+
+    /**
+    The type name of the future that was driven.
+    */
+    pub fn future(&self) -> &'static str {
+        self.future
+    }
+
+    /**
+    The index, counting from 0, of the poll after which the future could no longer be woken.
+    */
+    pub fn poll(&self) -> u64 {
+        self.poll
+    }
+
+    /**
+    Where [check_on], or [crate::waker_reuse::check_waker_reuse], was called.
+    */
+    pub fn location(&self) -> &'static std::panic::Location<'static> {
+        self.called_at
+    }
+}
+
+impl Display for LostWake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lost wakeup: `{}` returned Pending from poll {} but dropped every copy of its waker without waking it; waited for at {}",
+               self.future, self.poll, self.called_at)
+    }
+}
+
+impl std::error::Error for LostWake {}
+
+struct Signal {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Wake for Signal {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        crate::logging::trace!("waking");
+        *self.woken.lock().unwrap() = true;
+        self.condvar.notify_one();
+    }
+}
+
+/**
+Blocks the calling thread until a future is ready, or until it can no longer be woken.
+
+```
+use test_executors::pend_forever::PendForever;
+let lost = test_executors::lost_wake::check_on(PendForever).unwrap_err();
+assert_eq!(lost.poll(), 0);
+```
+
+# Panics
+Like [crate::sleep_on], if called from inside a future that a blocking executor is already driving on this thread.
+*/
+#[track_caller]
+pub fn check_on<F: IntoFuture>(future: F) -> Result<F::Output, LostWake> {
+    let location = std::panic::Location::caller();
+    let _sleeping = crate::SleepingGuard::enter(false, "check_on", location);
+    let future = future.into_future();
+    let signal = Arc::new(Signal { woken: Mutex::new(false), condvar: Condvar::new() });
+    let waker = Waker::from(signal.clone());
+    //held by `signal` and `waker`; anything more is a copy held by the future
+    const OURS: usize = 2;
+    let mut context = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
+    let mut poll = 0;
+    loop {
+        crate::logging::trace!("polling future");
+        if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
+            crate::logging::trace!("future is ready");
+            return Ok(val);
+        }
+        crate::logging::trace!("future is not ready");
+        let mut woken = signal.woken.lock().unwrap();
+        while !*woken {
+            //a copy is dropped only after a wake through it has set `woken`, so with the lock held this is exact
+            if Arc::strong_count(&signal) == OURS {
+                return Err(LostWake::new(core::any::type_name::<F::IntoFuture>(), poll, location));
+            }
+            woken = signal.condvar.wait_timeout(woken, CHECK_INTERVAL).unwrap().0;
+        }
+        *woken = false;
+        drop(woken);
+        crate::logging::trace!("woken");
+        poll += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Poll;
+
+    #[test]
+    fn woken_from_thread() {
+        let mut handle = None;
+        let result = super::check_on(std::future::poll_fn(|cx| match handle.take() {
+            None => {
+                let waker = cx.waker().clone();
+                handle = Some(std::thread::spawn(move || waker.wake()));
+                Poll::Pending
+            }
+            Some(handle) => {
+                handle.join().unwrap();
+                Poll::Ready(1)
+            }
+        }));
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn waker_dropped_later() {
+        let mut polls = 0;
+        let line = line!() + 1;
+        let lost = super::check_on(std::future::poll_fn(|cx| {
+            polls += 1;
+            if polls == 1 {
+                cx.waker().wake_by_ref();
+            } else {
+                let waker = cx.waker().clone();
+                std::thread::spawn(move || drop(waker));
+            }
+            Poll::<()>::Pending
+        })).unwrap_err();
+        assert_eq!(lost.poll(), 1);
+        assert!(lost.to_string().contains("PollFn"));
+        assert_eq!((lost.location().file(), lost.location().line()), (file!(), line));
+    }
+}
@@ -0,0 +1,713 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Runtime support for the code generated by [crate::async_test].  Not public API.
+*/
+
+use std::any::Any;
+use std::convert::Infallible;
+use std::fmt::Debug;
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::Receiver;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use some_executor::{DynExecutor, SomeExecutor};
+use some_executor::task::{Configuration, Task};
+use crate::ParkPolicy;
+use crate::aruntime::{SleepRuntime, SpawnRuntime, SpinRuntime};
+use crate::blocking::Strategy;
+
+/**
+Whether one run of a test body passed, for the return types libtest accepts.
+*/
+pub trait TestOutcome {
+    /**
+    A description of the failure, if the run failed without panicking.
+    */
+    fn failure(&self) -> Option<String>;
+
+    /**
+    The outcome of a test that passed, for tests skipped at runtime.
+    */
+    fn passed() -> Self;
+}
+
+impl TestOutcome for () {
+    fn failure(&self) -> Option<String> {
+        None
+    }
+
+    fn passed() -> Self {}
+}
+
+impl<E: Debug> TestOutcome for Result<(), E> {
+    fn failure(&self) -> Option<String> {
+        self.as_ref().err().map(|e| format!("{e:?}"))
+    }
+
+    fn passed() -> Self {
+        Ok(())
+    }
+}
+
+/**
+A JavaScript environment selected by `#[async_test(wasm_env = "...")]`.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WasmEnv {
+    Node,
+    Browser,
+}
+
+/**
+Whether a test for `env` should be skipped, because the wasm tests are running in the other environment.  Logs
+the skip.  Natively, no test is skipped.
+*/
+pub fn skip_wasm_env(name: &str, env: WasmEnv) -> bool {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        //Node is the environment with `process.versions.node`
+        let node = js_sys::Reflect::get(&js_sys::global(), &"process".into()).ok()
+            .filter(|process| process.is_object())
+            .and_then(|process| js_sys::Reflect::get(&process, &"versions".into()).ok())
+            .filter(|versions| versions.is_object())
+            .and_then(|versions| js_sys::Reflect::get(&versions, &"node".into()).ok())
+            .is_some_and(|version| !version.is_undefined());
+        let skip = node != (env == WasmEnv::Node);
+        if skip {
+            let env = format!("{env:?}");
+            crate::logging::info!("skipping async test {name}, which only runs in {env}", name = name, env = env);
+        }
+        skip
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        let _ = (name, env);
+        false
+    }
+}
+
+/**
+Installs console_error_panic_hook, with the `console-panic-hook` feature on wasm32-unknown-unknown, so that a
+panicking browser test logs its message rather than just "unreachable executed".  Elsewhere this does nothing.
+*/
+pub fn install_panic_hook() {
+    #[cfg(all(feature = "console-panic-hook", target_arch = "wasm32", target_os = "unknown"))]
+    console_error_panic_hook::set_once();
+}
+
+/**
+Reports the output of an `#[async_main]` that cannot return it, because the future was spawned.
+*/
+pub fn main_returned<O: TestOutcome>(output: O) {
+    if let Some(description) = output.failure() {
+        panic!("Error: {description}");
+    }
+}
+
+/**
+How a failed run ended.
+*/
+enum Failure<O> {
+    Panicked(Box<dyn Any + Send>),
+    Returned(O),
+}
+
+/**
+A runtime selected by `#[async_test(global_runtime = "...")]`.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Runtime {
+    Spin,
+    Sleep,
+    Spawn,
+}
+
+impl Runtime {
+    fn boxed(self) -> Box<DynExecutor> {
+        match self {
+            Runtime::Spin => Box::new(SpinRuntime::new()),
+            Runtime::Sleep => Box::new(SleepRuntime::new()),
+            Runtime::Spawn => Box::new(SpawnRuntime::new()),
+        }
+    }
+}
+
+pub use crate::config::{DEFAULT_TIMEOUT, DEFAULT_TIMEOUT_VAR};
+
+/**
+The timeout of an `#[async_test]`.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Timeout {
+    /// [DEFAULT_TIMEOUT], or as configured by [crate::config].
+    Default,
+    /// `timeout_ms = N`.
+    After(Duration),
+    /// `timeout = "none"`.
+    Never,
+}
+
+impl Timeout {
+    fn duration(self) -> Option<Duration> {
+        match self {
+            Timeout::Default => crate::config::current().default_timeout(),
+            Timeout::After(duration) => Some(duration),
+            Timeout::Never => None,
+        }
+    }
+}
+
+/**
+Describes an `#[async_test]` as written.
+*/
+#[derive(Debug, Clone)]
+pub struct Test {
+    pub name: &'static str,
+    /// The name of the logwise context the body runs in: the test's name and location.
+    pub context: &'static str,
+    /// Where the test is defined, reported when it times out.
+    pub location: &'static std::panic::Location<'static>,
+    pub timeout: Timeout,
+    /// Run the body this many times; every run must pass.
+    pub repeat: u32,
+    /// Retry each failed run up to this many times before failing the test.
+    pub retries: u32,
+    /// Make this runtime available to some_executor's `current_executor` while the test runs.
+    pub global_runtime: Option<Runtime>,
+    /// The number of seeds [Self::chaos] runs the body under.
+    pub chaos_seeds: u64,
+    /// If nonzero, run the body this many times regardless of failures, and report the pass rate.
+    pub detect_flaky: u32,
+    /// Hold this group's process-wide lock while the test runs.
+    pub serial: Option<&'static str>,
+}
+
+impl Test {
+    /**
+    Runs the test with [crate::spin_on].
+    */
+    pub fn spin<T: Fn() -> F, F: Future>(&self, test: T) -> F::Output
+    where
+        F::Output: TestOutcome,
+    {
+        self.iterate(|_| match self.timeout.duration() {
+            Some(timeout) => crate::watchdog::spin_on_timeout_at(self.in_context(test()), timeout, Some(self.location))
+                .unwrap_or_else(|e| self.timed_out(e)),
+            None => crate::spin_on(self.in_context(test())),
+        })
+    }
+
+    /**
+    Runs the test with [crate::sleep_on].
+    */
+    pub fn sleep<T: Fn() -> F, F: Future>(&self, test: T) -> F::Output
+    where
+        F::Output: TestOutcome,
+    {
+        self.iterate(|_| match self.timeout.duration() {
+            Some(timeout) => crate::watchdog::sleep_on_timeout_at(self.in_context(test()), timeout, Some(self.location), ParkPolicy::Park)
+                .unwrap_or_else(|e| self.timed_out(e)),
+            None => crate::sleep_on(self.in_context(test())),
+        })
+    }
+
+    /**
+    Runs the test with the strategy [crate::blocking::default_executor] picks, as an `#[async_test]` that names no
+    `executor` does: [Self::spin] for `spin`, and otherwise as [Self::sleep] does, waiting between polls as the
+    strategy says whether or not the test has a timeout.
+    */
+    pub fn configured<T: Fn() -> F, F: Future>(&self, test: T) -> F::Output
+    where
+        F::Output: TestOutcome,
+    {
+        self.configured_by(crate::config::current(), test)
+    }
+
+    /**
+    [Self::configured], with the strategy `config` picks.
+    */
+    fn configured_by<T: Fn() -> F, F: Future>(&self, config: crate::config::Config, test: T) -> F::Output
+    where
+        F::Output: TestOutcome,
+    {
+        let policy = match config.executor() {
+            Strategy::Spin => return self.spin(test),
+            Strategy::Sleep => config.park_policy(),
+            Strategy::Adaptive => ParkPolicy::SpinThenPark { spins: config.spins() },
+        };
+        self.iterate(|_| match self.timeout.duration() {
+            Some(timeout) => crate::watchdog::sleep_on_timeout_at(self.in_context(test()), timeout, Some(self.location), policy)
+                .unwrap_or_else(|e| self.timed_out(e)),
+            None => crate::sleep_on_with(self.in_context(test()), policy),
+        })
+    }
+
+    /**
+    Runs the test under [crate::deterministic::DeterministicRuntime] once for each seed in `0..chaos_seeds`,
+    reporting the seed of a failing run.
+
+    With `detect_flaky`, each run instead uses one seed, cycling through `0..chaos_seeds`.  A chaos seed set by
+    [crate::config] replaces the seeds.
+    */
+    pub fn chaos<T: Fn() -> F, F: Future>(&self, test: T) -> F::Output
+    where
+        F::Output: TestOutcome,
+    {
+        let seeds: Vec<u64> = match crate::config::current().chaos_seed() {
+            Some(seed) => vec![seed],
+            None => (0..self.chaos_seeds.max(1)).collect(),
+        };
+        if self.detect_flaky > 0 {
+            return self.iterate(|run| self.chaos_once(seeds[run as usize % seeds.len()], &test));
+        }
+        self.iterate(|_| {
+            let mut output = None;
+            for &seed in &seeds {
+                let o = self.chaos_once(seed, &test);
+                if o.failure().is_some() {
+                    return o;
+                }
+                output = Some(o);
+            }
+            output.expect("at least one seed")
+        })
+    }
+
+    fn chaos_once<T: Fn() -> F, F: Future>(&self, seed: u64, test: &T) -> F::Output
+    where
+        F::Output: TestOutcome,
+    {
+        let mut runtime = crate::deterministic::DeterministicRuntime::new(seed);
+        let o = match catch_unwind(AssertUnwindSafe(|| runtime.block_on(self.in_context(test())))) {
+            Ok(o) => o,
+            Err(payload) => {
+                eprintln!("async test `{}` failed with chaos seed {seed}", self.name);
+                resume_unwind(payload)
+            }
+        };
+        if let Some(description) = o.failure() {
+            eprintln!("async test `{}` failed with chaos seed {seed}: {description}", self.name);
+        }
+        o
+    }
+
+    /**
+    Runs each iteration of the test on a new thread named after the test, waiting for it from the test thread.
+
+    Panics on the spawned thread are resumed on the test thread, so `should_panic` and the test report behave as
+    they would for [Self::sleep].  On timeout the spawned thread is abandoned.
+    */
+    pub fn spawn<T: Fn() -> F, F>(&self, test: T) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: TestOutcome + Send + 'static,
+    {
+        self.iterate(|_| self.spawn_once(self.in_context(test())))
+    }
+
+    fn spawn_once<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name(self.name.to_string())
+            .spawn(move || {
+                let result = catch_unwind(AssertUnwindSafe(|| crate::sleep_on(future)));
+                //the receiver may have timed out already
+                let _ = sender.send(result);
+            }).expect("Cant spawn thread");
+        self.receive(receiver)
+    }
+
+    /**
+    Runs each iteration of the test as a task on [crate::aruntime::SpawnRuntime], waiting for it from the test
+    thread.
+    */
+    pub fn spawn_runtime<T: Fn() -> F, F>(&self, test: T) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: TestOutcome + Send + 'static,
+    {
+        self.iterate(|_| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let future = CatchUnwind(Box::pin(self.in_context(test())));
+            let task = Task::<_, Infallible>::without_notifications(self.name.to_string(), async move {
+                let _ = sender.send(future.await);
+            }, Configuration::default());
+            //dropping the observer would cancel the task
+            let _observer = SpawnRuntime::new().spawn(task);
+            self.receive(receiver)
+        })
+    }
+
+    /**
+    Wraps one run of the body so that it is polled inside the test's logwise context, on whichever thread polls it.
+    */
+    fn in_context<F: Future>(&self, future: F) -> InContext<F> {
+        InContext {
+            future: Box::pin(future),
+            #[cfg(feature = "logwise")]
+            context: logwise::context::Context::new_task(Some(logwise::context::Context::current()), self.context),
+        }
+    }
+
+    /**
+    Waits for the result of an iteration running elsewhere, resuming its panic if it panicked.
+    */
+    fn receive<O>(&self, receiver: Receiver<std::thread::Result<O>>) -> O {
+        let result = match self.timeout.duration() {
+            Some(timeout) => receiver.recv_timeout(timeout)
+                .unwrap_or_else(|_| self.timed_out(format_args!("did not complete within {timeout:?}"))),
+            None => receiver.recv().expect("test thread disappeared"),
+        };
+        match result {
+            Ok(value) => value,
+            Err(payload) => resume_unwind(payload),
+        }
+    }
+
+    /**
+    Applies `repeat` and `retries`, or `detect_flaky`, to `run_once`, reporting which iteration failed.
+
+    `run_once` is passed the index of the run, counting from 0, for `detect_flaky`.
+    */
+    fn iterate<O: TestOutcome>(&self, run_once: impl Fn(u32) -> O) -> O {
+        let _serial = self.serial.map(SerialGuard::lock);
+        let _runtime = self.global_runtime.map(install_runtime);
+        if self.detect_flaky > 0 {
+            return self.detect_flaky(run_once);
+        }
+        let run_once = || run_once(0);
+        let repeat = self.repeat.max(1);
+        let attempts = self.retries + 1;
+        let mut output = None;
+        for iteration in 1..=repeat {
+            let mut attempt = 1;
+            let passed = loop {
+                let failure = match catch_unwind(AssertUnwindSafe(&run_once)) {
+                    Ok(o) => match o.failure() {
+                        None => break o,
+                        Some(description) => {
+                            self.report(iteration, repeat, attempt, attempts, &description);
+                            Failure::Returned(o)
+                        }
+                    },
+                    Err(payload) => {
+                        self.report(iteration, repeat, attempt, attempts, panic_message(&*payload));
+                        Failure::Panicked(payload)
+                    }
+                };
+                if attempt == attempts {
+                    match failure {
+                        Failure::Panicked(payload) => resume_unwind(payload),
+                        Failure::Returned(o) => return o,
+                    }
+                }
+                attempt += 1;
+            };
+            output = Some(passed);
+        }
+        output.expect("at least one iteration")
+    }
+
+    /**
+    Runs `run_once` `detect_flaky` times, whether or not the runs fail, and reports the pass rate.  Fails with the
+    first failure, if any.
+    */
+    fn detect_flaky<O: TestOutcome>(&self, run_once: impl Fn(u32) -> O) -> O {
+        let runs = self.detect_flaky;
+        let mut passed = 0;
+        let mut first_failure = None;
+        let mut output = None;
+        for run in 0..runs {
+            match catch_unwind(AssertUnwindSafe(|| run_once(run))) {
+                Ok(o) => match o.failure() {
+                    None => {
+                        passed += 1;
+                        output = Some(o);
+                    }
+                    Some(description) => {
+                        eprintln!("async test `{}` failed on run {} of {runs}: {description}", self.name, run + 1);
+                        first_failure.get_or_insert(Failure::Returned(o));
+                    }
+                },
+                Err(payload) => {
+                    eprintln!("async test `{}` failed on run {} of {runs}: {}", self.name, run + 1, panic_message(&*payload));
+                    first_failure.get_or_insert(Failure::Panicked(payload));
+                }
+            }
+        }
+        let rate = f64::from(passed) * 100.0 / f64::from(runs);
+        eprintln!("async test `{}` passed {passed} of {runs} runs ({rate:.1}%)", self.name);
+        match first_failure {
+            Some(Failure::Panicked(payload)) => resume_unwind(payload),
+            Some(Failure::Returned(o)) => o,
+            None => output.expect("at least one run"),
+        }
+    }
+
+    fn timed_out(&self, detail: impl std::fmt::Display) -> ! {
+        let mut running = String::new();
+        for task in crate::debug::running_tasks() {
+            running += &format!("\n  {task}");
+        }
+        if !running.is_empty() {
+            running.insert_str(0, "\nTasks still running:");
+        }
+        match self.timeout {
+            Timeout::Default => panic!("async test `{}` timed out: {detail}.  This is the default timeout for async tests; \
+                set `timeout_ms` or `timeout = \"none\"` on the test, or {DEFAULT_TIMEOUT_VAR} for every test, to change it{running}", self.name),
+            _ => panic!("async test `{}` timed out: {detail}{running}", self.name),
+        }
+    }
+
+    fn report(&self, iteration: u32, repeat: u32, attempt: u32, attempts: u32, description: &str) {
+        let mut message = format!("async test `{}` failed", self.name);
+        if repeat > 1 {
+            message += &format!(" on iteration {iteration} of {repeat}");
+        }
+        if attempts > 1 {
+            message += &format!(" (attempt {attempt} of {attempts})");
+        }
+        eprintln!("{message}: {description}");
+    }
+}
+
+/**
+A lock shared by the `#[async_test(serial = "...")]` tests of one group.
+*/
+#[derive(Default)]
+struct SerialGroup {
+    held: std::sync::Mutex<bool>,
+    released: std::sync::Condvar,
+}
+
+static SERIAL_GROUPS: std::sync::Mutex<Vec<(&'static str, std::sync::Arc<SerialGroup>)>> = std::sync::Mutex::new(Vec::new());
+
+/**
+Holds a serial group's lock until dropped.
+*/
+struct SerialGuard(std::sync::Arc<SerialGroup>);
+
+impl SerialGuard {
+    fn lock(name: &'static str) -> Self {
+        let group = {
+            let mut groups = SERIAL_GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+            match groups.iter().find(|(n, _)| *n == name) {
+                Some((_, group)) => group.clone(),
+                None => {
+                    let group = std::sync::Arc::new(SerialGroup::default());
+                    groups.push((name, group.clone()));
+                    group
+                }
+            }
+        };
+        //a failing test panics while holding the lock, which is released all the same
+        let mut held = group.held.lock().unwrap_or_else(|e| e.into_inner());
+        while *held {
+            held = group.released.wait(held).unwrap_or_else(|e| e.into_inner());
+        }
+        *held = true;
+        drop(held);
+        SerialGuard(group)
+    }
+}
+
+impl Drop for SerialGuard {
+    fn drop(&mut self) {
+        *self.0.held.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        self.0.released.notify_one();
+    }
+}
+
+/**
+Restores the test thread's previous thread executor when dropped.
+*/
+struct ThreadExecutorGuard(Option<Box<DynExecutor>>);
+
+impl Drop for ThreadExecutorGuard {
+    fn drop(&mut self) {
+        //some_executor cannot unset a thread executor, but libtest runs each test on its own thread anyway
+        if let Some(prior) = self.0.take() {
+            some_executor::thread_executor::set_thread_executor(prior);
+        }
+    }
+}
+
+/**
+Installs `runtime` as the global runtime (if none is set yet) and as the test thread's executor.
+*/
+fn install_runtime(runtime: Runtime) -> ThreadExecutorGuard {
+    if !crate::aruntime::set_global_runtime_if_unset(runtime.boxed()) {
+        crate::logging::info!("global runtime already set; async test uses the existing one");
+    }
+    let prior = some_executor::thread_executor::thread_executor(|e| e.map(|e| e.clone_box()));
+    some_executor::thread_executor::set_thread_executor(runtime.boxed());
+    ThreadExecutorGuard(prior)
+}
+
+/**
+Runs `teardown` once `body` completes, even if `body` panics.  For `#[async_test(teardown = ...)]`.
+*/
+pub async fn teardown_after<F: Future, T: Future<Output = ()>>(body: F, teardown: T) -> F::Output {
+    let result = CatchUnwind(Box::pin(body)).await;
+    teardown.await;
+    match result {
+        Ok(value) => value,
+        Err(payload) => resume_unwind(payload),
+    }
+}
+
+/**
+Spawns `future` onto `runtime` and asserts that it completes with `expected`.  For
+[assert_task_completes!](crate::assert_task_completes).
+*/
+#[track_caller]
+pub fn assert_task_completes<R, F>(runtime: &mut R, label: &str, future: F, expected: F::Output, timeout: Option<Duration>)
+where
+    R: SomeExecutor,
+    F: Future + Send + 'static,
+    F::Output: Debug + PartialEq + Send + Unpin,
+{
+    use crate::observation::{finished, FinishedObservation};
+    let task = Task::<_, Infallible>::without_notifications(label.to_string(), future, Configuration::default());
+    let observer = runtime.spawn(task);
+    let observation = match timeout {
+        Some(timeout) => match crate::watchdog::sleep_on_timeout(finished(&observer), timeout) {
+            Ok(observation) => observation,
+            Err(e) => panic!("{}", e.with_task(label)),
+        },
+        None => crate::sleep_on(finished(&observer)),
+    };
+    match observation {
+        FinishedObservation::Ready(output) => if output != expected {
+            panic!("assertion `task output == expected` failed for task `{label}`\n task output: {output:?}\n    expected: {expected:?}");
+        },
+        FinishedObservation::Done => panic!("output of task `{label}` was already observed"),
+        FinishedObservation::Cancelled => panic!("task `{label}` was cancelled before it completed"),
+    }
+}
+
+/**
+Asserts that `log` matches the snapshot `name` of the crate in `manifest_dir`.  For
+[assert_schedule_snapshot!](crate::assert_schedule_snapshot).
+*/
+#[track_caller]
+pub fn assert_schedule_snapshot(log: &crate::event_log::EventLog, manifest_dir: &str, name: &str) {
+    let path = std::path::Path::new(manifest_dir).join("snapshots").join(format!("{name}.schedule"));
+    crate::event_log::assert_snapshot(log, name, &path, crate::config::current().update_snapshots());
+}
+
+/**
+Catches panics while polling the inner future, so they can be sent back to the test thread.
+*/
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/**
+Polls the inner future inside its test's logwise context.  See [Test::in_context].
+*/
+struct InContext<F> {
+    future: Pin<Box<F>>,
+    #[cfg(feature = "logwise")]
+    context: logwise::context::Context,
+}
+
+/**
+Restores the context that was current before a poll, even if the poll panics.
+*/
+#[cfg(feature = "logwise")]
+struct RestoreContext(Option<logwise::context::Context>);
+
+#[cfg(feature = "logwise")]
+impl Drop for RestoreContext {
+    fn drop(&mut self) {
+        if let Some(prior) = self.0.take() {
+            logwise::context::Context::set_current(prior);
+        }
+    }
+}
+
+impl<F: Future> Future for InContext<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        #[cfg(feature = "logwise")]
+        let _restore = {
+            let prior = logwise::context::Context::current();
+            logwise::context::Context::set_current(self.context.clone());
+            RestoreContext(Some(prior))
+        };
+        self.future.as_mut().poll(cx)
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "non-string panic payload"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::Poll;
+    use std::time::Duration;
+    use super::{Test, Timeout};
+    use crate::ParkPolicy;
+    use crate::blocking::Strategy;
+    use crate::config::Config;
+
+    #[test]
+    fn configured_follows_park_policy_under_default_timeout() {
+        let test = Test {
+            name: "configured_follows_park_policy",
+            context: "configured_follows_park_policy",
+            location: std::panic::Location::caller(),
+            timeout: Timeout::Default,
+            repeat: 1,
+            retries: 0,
+            global_runtime: None,
+            chaos_seeds: 0,
+            detect_flaky: 0,
+            serial: None,
+        };
+        //set without waking the test, so only polling again after the park timeout notices it
+        let ready = Arc::new(AtomicBool::new(false));
+        let setter = {
+            let ready = ready.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                ready.store(true, Ordering::Release);
+            })
+        };
+        let config = Config::new().with_executor(Strategy::Sleep).with_park_policy(ParkPolicy::ParkTimeout(Duration::from_millis(1)));
+        let start = std::time::Instant::now();
+        test.configured_by(config, || {
+            let ready = ready.clone();
+            std::future::poll_fn(move |_| if ready.load(Ordering::Acquire) { Poll::Ready(()) } else { Poll::Pending })
+        });
+        assert!(start.elapsed() < Duration::from_secs(10), "waited for the watchdog instead of the park timeout");
+        setter.join().unwrap();
+    }
+}
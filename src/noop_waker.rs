@@ -3,32 +3,76 @@
 A waker which does not do anything.  Primarily useful for testing.
 */
 
-use std::sync::OnceLock;
-use std::task::{Context, RawWaker, RawWakerVTable, Waker};
+use core::task::Waker;
+#[cfg(all(feature = "std", not(feature = "waker-noop")))]
+use std::sync::{Arc, OnceLock};
 
-static NOOP_WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
-    |_| RawWaker::new(std::ptr::null(), &NOOP_WAKER_VTABLE),
+#[cfg(all(feature = "std", not(feature = "waker-noop")))]
+struct NoopWake;
+
+#[cfg(all(feature = "std", not(feature = "waker-noop")))]
+impl std::task::Wake for NoopWake {
+    fn wake(self: Arc<Self>) {}
+    fn wake_by_ref(self: &Arc<Self>) {}
+}
+
+#[cfg(all(feature = "std", not(feature = "waker-noop")))]
+static NOOP_WAKER: OnceLock<Waker> = OnceLock::new();
+
+/**
+Without std there is no [std::task::Wake], so we fall back to a vtable.  The data pointer is null and never
+dereferenced, so this is sound (and Miri-clean).
+*/
+#[cfg(all(not(feature = "std"), not(feature = "waker-noop")))]
+static NOOP_WAKER_VTABLE: core::task::RawWakerVTable = core::task::RawWakerVTable::new(
+    |_| core::task::RawWaker::new(core::ptr::null(), &NOOP_WAKER_VTABLE),
     |_| (),
     |_| (),
     |_| (),
 );
 
-
-
 //error: `Waker::from_raw` is not yet stable as a const fn
 
-static NOOP_WAKER: OnceLock<Waker> = OnceLock::new();
+/**
+Calls `f` with a context whose waker has no effect.
 
-fn noop_waker() -> &'static Waker {
-    NOOP_WAKER.get_or_init(|| {
-        let raw = RawWaker::new(std::ptr::null(), &NOOP_WAKER_VTABLE);
-        unsafe { Waker::from_raw(raw) }
-    })
+With the `waker-noop` feature this borrows std's `Waker::noop`, a constant, so the context costs nothing to
+build; otherwise it uses [noop_waker].
+*/
+//the `waker-noop` feature requires a newer Rust than the crate otherwise does
+#[cfg_attr(feature = "waker-noop", allow(clippy::incompatible_msrv))]
+pub(crate) fn with_noop_context<R>(f: impl FnOnce(&mut core::task::Context<'_>) -> R) -> R {
+    #[cfg(feature = "waker-noop")]
+    {
+        f(&mut core::task::Context::from_waker(Waker::noop()))
+    }
+    #[cfg(not(feature = "waker-noop"))]
+    {
+        let waker = noop_waker();
+        f(&mut core::task::Context::from_waker(&waker))
+    }
 }
+
 /**
-Creates a new context that has no effect.
+Creates a waker that has no effect.
+
+With `std`, the waker is created once and cloned; without it there is nowhere to cache it, so we build
+a fresh one (which is just as cheap).
 */
-pub fn new_context() -> Context<'static> {
-    Context::from_waker(noop_waker())
+//the `waker-noop` feature requires a newer Rust than the crate otherwise does
+#[cfg_attr(feature = "waker-noop", allow(clippy::incompatible_msrv))]
+pub fn noop_waker() -> Waker {
+    #[cfg(feature = "waker-noop")]
+    {
+        Waker::noop().clone()
+    }
+    #[cfg(all(feature = "std", not(feature = "waker-noop")))]
+    {
+        NOOP_WAKER.get_or_init(|| Waker::from(Arc::new(NoopWake))).clone()
+    }
+    #[cfg(all(not(feature = "std"), not(feature = "waker-noop")))]
+    {
+        let raw = core::task::RawWaker::new(core::ptr::null(), &NOOP_WAKER_VTABLE);
+        unsafe { Waker::from_raw(raw) }
+    }
 }
-
@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Typed access to the results of objsafe spawns.
+
+A task spawned through `SomeExecutor::spawn_objsafe` produces a `Box<dyn Any + Send>`, which every test then
+has to downcast.  [typed_observation] does the downcast, panicking with the expected type if it fails, and
+[ObjsafeObserverExt] adds typed versions of `observe` to objsafe observers.
+
+```
+use some_executor::SomeExecutor;
+use some_executor::observer::Observation;
+use some_executor::task::Task;
+use test_executors::objsafe::ObjsafeObserverExt;
+
+let task = Task::<_, std::convert::Infallible>::without_notifications("answer".to_string(), async { 42u32 }, Default::default());
+let observer = test_executors::aruntime::SleepRuntime::new().spawn_objsafe(task.into_objsafe());
+assert_eq!(observer.observe_value::<u32>(), 42);
+```
+*/
+
+use std::any::Any;
+use some_executor::observer::{Observation, Observer};
+
+/**
+Downcasts the value of an objsafe observation to `T`.
+
+# Panics
+If the task's output is not a `T`.
+*/
+pub fn typed_observation<T: 'static>(observation: Observation<Box<dyn Any + Send>>) -> Observation<T> {
+    match observation {
+        Observation::Pending => Observation::Pending,
+        Observation::Ready(value) => match value.downcast::<T>() {
+            Ok(value) => Observation::Ready(*value),
+            Err(_) => panic!("objsafe task output is not a `{}`", std::any::type_name::<T>()),
+        },
+        Observation::Done => Observation::Done,
+        Observation::Cancelled => Observation::Cancelled,
+    }
+}
+
+/**
+Typed observation of observers whose value is a `Box<dyn Any + Send>`, such as those returned by
+`spawn_objsafe`.
+*/
+pub trait ObjsafeObserverExt {
+    /**
+    Like `observe`, with the value downcast to `T`.
+
+    # Panics
+    If the task's output is not a `T`.
+    */
+    fn observe_typed<T: 'static>(&self) -> Observation<T>;
+
+    /**
+    The task's output, downcast to `T`.
+
+    # Panics
+    If the task has not finished, was cancelled, or its output was already observed, or if the output is not a
+    `T`.
+    */
+    fn observe_value<T: 'static>(&self) -> T {
+        match self.observe_typed::<T>() {
+            Observation::Ready(value) => value,
+            Observation::Pending => panic!("task has not finished"),
+            Observation::Done => panic!("task output was already observed"),
+            Observation::Cancelled => panic!("task was cancelled"),
+        }
+    }
+}
+
+impl<O: Observer<Value = Box<dyn Any + Send>> + ?Sized> ObjsafeObserverExt for O {
+    fn observe_typed<T: 'static>(&self) -> Observation<T> {
+        typed_observation(self.observe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use some_executor::observer::Observation;
+    use super::typed_observation;
+
+    #[test]
+    fn downcasts() {
+        let value: Box<dyn Any + Send> = Box::new(3u8);
+        assert_eq!(typed_observation::<u8>(Observation::Ready(value)), Observation::Ready(3));
+        assert_eq!(typed_observation::<u8>(Observation::Cancelled), Observation::Cancelled);
+    }
+
+    #[test]
+    #[should_panic(expected = "objsafe task output is not a `u16`")]
+    fn names_expected_type() {
+        let value: Box<dyn Any + Send> = Box::new(3u8);
+        let _ = typed_observation::<u16>(Observation::Ready(value));
+    }
+}
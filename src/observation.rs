@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Waiting for a some_executor task's outcome from synchronous test code.
+
+Observers in some_executor 0.3 can only be asked for the task's result, not awaited, so tests end up polling
+`observe` in a loop and matching on the outcome.  [resolve_observer] blocks with [crate::sleep_on] until the task
+finishes and returns its output, panicking with the task's id if it was cancelled:
+
+```
+use some_executor::SomeExecutor;
+use some_executor::task::Task;
+
+let task = Task::<_, std::convert::Infallible>::without_notifications("answer".to_string(), async { 42 }, Default::default());
+let observer = test_executors::aruntime::SpawnRuntime::new().spawn(task);
+assert_eq!(test_executors::observation::resolve_observer(observer), 42);
+```
+
+[resolve_with_timeout] gives up after a timeout, and [finished] is the async wait underneath both, which yields a
+[FinishedObservation] for tests that expect a cancellation.  [ObservationExt] turns either kind of observation
+into a one-line assertion:
+
+```
+use some_executor::observer::Observation;
+use test_executors::observation::{FinishedObservation, ObservationExt};
+
+assert_eq!(FinishedObservation::Ready(3).expect_ready(), 3);
+Observation::<u32>::Cancelled.expect_cancelled();
+assert!(Observation::<u32>::Pending.into_result().is_err());
+```
+*/
+
+use std::fmt::Debug;
+use std::time::Duration;
+use some_executor::observer::{Observation, Observer};
+use crate::error::{NotReady, TimeoutError};
+
+/**
+How long [finished] sleeps between checks of the observer.
+*/
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/**
+An [Observation] of a task that is no longer pending.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FinishedObservation<T> {
+    /// The task completed with this output.
+    Ready(T),
+    /// The task completed, but its output was already taken by an earlier observation.
+    Done,
+    /// The task was cancelled before it completed.
+    Cancelled,
+}
+
+impl<T> From<FinishedObservation<T>> for Observation<T> {
+    fn from(observation: FinishedObservation<T>) -> Self {
+        match observation {
+            FinishedObservation::Ready(value) => Observation::Ready(value),
+            FinishedObservation::Done => Observation::Done,
+            FinishedObservation::Cancelled => Observation::Cancelled,
+        }
+    }
+}
+
+/**
+Assertions on the outcome of a task, implemented for [Observation] and [FinishedObservation].
+*/
+pub trait ObservationExt<T>: Sized {
+    /**
+    The task's output, or what was observed instead.
+    */
+    fn into_result(self) -> Result<T, NotReady>;
+
+    /**
+    The task's output.
+
+    # Panics
+    If the task has not finished, was cancelled, or its output was already observed.
+    */
+    #[track_caller]
+    fn expect_ready(self) -> T {
+        match self.into_result() {
+            Ok(value) => value,
+            Err(e) => panic!("expected the task's output, but {e}"),
+        }
+    }
+
+    /**
+    Asserts that the task was cancelled.
+
+    # Panics
+    If the task completed, or has not finished.
+    */
+    #[track_caller]
+    fn expect_cancelled(self) where T: Debug {
+        match self.into_result() {
+            Ok(value) => panic!("expected the task to be cancelled, but it completed with {value:?}"),
+            Err(e) if e.is_cancelled() => (),
+            Err(e) => panic!("expected the task to be cancelled, but {e}"),
+        }
+    }
+}
+
+impl<T> ObservationExt<T> for Observation<T> {
+    fn into_result(self) -> Result<T, NotReady> {
+        match self {
+            Observation::Ready(value) => Ok(value),
+            Observation::Pending => Err(NotReady::new(Observation::Pending)),
+            Observation::Done => Err(NotReady::new(Observation::Done)),
+            Observation::Cancelled => Err(NotReady::new(Observation::Cancelled)),
+        }
+    }
+}
+
+impl<T> ObservationExt<T> for FinishedObservation<T> {
+    fn into_result(self) -> Result<T, NotReady> {
+        Observation::from(self).into_result()
+    }
+}
+
+/**
+Waits until the observer's task is no longer pending.
+
+Observers are not woken when their task finishes, so this checks the observer every millisecond, sleeping with
+[crate::time::sleep] in between.
+*/
+pub async fn finished<O: Observer + ?Sized>(observer: &O) -> FinishedObservation<O::Value> {
+    loop {
+        match observer.observe() {
+            Observation::Pending => crate::time::sleep(POLL_INTERVAL).await,
+            Observation::Ready(value) => return FinishedObservation::Ready(value),
+            Observation::Done => return FinishedObservation::Done,
+            Observation::Cancelled => return FinishedObservation::Cancelled,
+        }
+    }
+}
+
+#[track_caller]
+fn unwrap_ready<O: Observer>(observer: &O, observation: FinishedObservation<O::Value>) -> O::Value {
+    match observation.into_result() {
+        Ok(value) => value,
+        Err(e) => panic!("{e} (task {:?})", observer.task_id()),
+    }
+}
+
+/**
+Blocks the calling thread until the observer's task completes, and returns its output.
+
+# Panics
+If the task was cancelled, or its output was already observed.
+*/
+#[track_caller]
+pub fn resolve_observer<O: Observer>(observer: O) -> O::Value {
+    let observation = crate::sleep_on(finished(&observer));
+    unwrap_ready(&observer, observation)
+}
+
+/**
+Like [resolve_observer], but gives up if the task has not finished after `timeout`.
+
+The observer is dropped on timeout, which cancels the task if it was not detached.
+
+# Panics
+If the task was cancelled, or its output was already observed.
+*/
+#[track_caller]
+pub fn resolve_with_timeout<O: Observer>(observer: O, timeout: Duration) -> Result<O::Value, TimeoutError> {
+    let observation = crate::watchdog::sleep_on_timeout(finished(&observer), timeout)
+        .map_err(|e| e.with_task(format!("{:?}", observer.task_id())))?;
+    Ok(unwrap_ready(&observer, observation))
+}
+
+/**
+Spawns a future onto a runtime, blocks until it completes, and asserts that its output equals an expected value.
+
+Takes the runtime, the future and the expected output, and optionally a `Duration` after which to give up; without
+one, the [crate::config] default timeout applies.  The task is labelled with the future's source, which the
+failure message names along with both values:
+
+```
+use std::time::Duration;
+use test_executors::aruntime::SpawnRuntime;
+test_executors::assert_task_completes!(SpawnRuntime::new(), async { 1 + 1 }, 2);
+test_executors::assert_task_completes!(SpawnRuntime::new(), async { "done" }, "done", Duration::from_secs(5));
+```
+
+```text
+assertion `task output == expected` failed for task `async { 1 + 1 }`
+ task output: 2
+    expected: 3
+```
+
+Like [resolve_observer], this blocks the calling thread, so call it from a synchronous test.
+*/
+#[macro_export]
+macro_rules! assert_task_completes {
+    ($runtime:expr, $future:expr, $expected:expr $(,)?) => {
+        $crate::macro_support::assert_task_completes(&mut $runtime, stringify!($future), $future, $expected, $crate::config::current().default_timeout())
+    };
+    ($runtime:expr, $future:expr, $expected:expr, $timeout:expr $(,)?) => {
+        $crate::macro_support::assert_task_completes(&mut $runtime, stringify!($future), $future, $expected, ::core::option::Option::Some($timeout))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use some_executor::SomeExecutor;
+    use some_executor::task::{Configuration, Task};
+    use some_executor::observer::Observation;
+    use super::{resolve_observer, resolve_with_timeout, FinishedObservation, ObservationExt};
+
+    #[test]
+    fn resolves_spawned_task() {
+        let task = Task::<_, std::convert::Infallible>::without_notifications("observation::resolves".to_string(), async {
+            crate::time::sleep(Duration::from_millis(5)).await;
+            7
+        }, Configuration::default());
+        assert_eq!(resolve_observer(crate::aruntime::SpawnRuntime::new().spawn(task)), 7);
+
+        let task = Task::<_, std::convert::Infallible>::without_notifications("observation::times_out".to_string(), crate::pend_forever::PendForever, Configuration::default());
+        let err = resolve_with_timeout(crate::aruntime::SpawnRuntime::new().spawn(task), Duration::from_millis(10)).unwrap_err();
+        assert!(err.to_string().contains("TaskID"), "{err}");
+    }
+
+    #[test]
+    fn asserts_on_outcome() {
+        assert_eq!(Observation::Ready(1).expect_ready(), 1);
+        FinishedObservation::<u8>::Cancelled.expect_cancelled();
+        let err = FinishedObservation::<u8>::Done.into_result().unwrap_err();
+        assert_eq!((err.observation(), err.is_cancelled()), (&Observation::Done, false));
+        let panic = std::panic::catch_unwind(|| Observation::Ready(2).expect_cancelled()).unwrap_err();
+        assert_eq!(panic.downcast_ref::<String>().map(String::as_str), Some("expected the task to be cancelled, but it completed with 2"));
+    }
+
+    #[test]
+    fn asserts_task_output() {
+        use crate::aruntime::SpawnRuntime;
+        crate::assert_task_completes!(SpawnRuntime::new(), async { 1 + 1 }, 2);
+        let panic = std::panic::catch_unwind(|| crate::assert_task_completes!(SpawnRuntime::new(), async { 1 + 1 }, 3)).unwrap_err();
+        assert_eq!(panic.downcast_ref::<String>().map(String::as_str), Some("assertion `task output == expected` failed for task `async { 1 + 1 }`\n task output: 2\n    expected: 3"));
+        let panic = std::panic::catch_unwind(|| crate::assert_task_completes!(crate::aruntime::NullRuntime::refusing(), async {}, ())).unwrap_err();
+        assert_eq!(panic.downcast_ref::<String>().map(String::as_str), Some("task `async {}` was cancelled before it completed"));
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A panic hook that says which task panicked.
+
+When many tasks run at once, the default panic message names only the thread, which is often a runtime's
+anonymous worker ([crate::aruntime::SpawnRuntime] at least names its threads after their tasks).  After
+[install], each panic message is preceded by a line naming the task being polled when it happened (its
+some_executor label), with the `logwise` feature the logwise context it was running in, and the call of the
+executor driving it, such as [crate::sleep_on]:
+
+```text
+panic in task `fetch user 3` (logwise context ContextID(12)), driven by sleep_on called at tests/users.rs:12:5:
+thread '<unnamed>' panicked at src/users.rs:40:9:
+no such user
+```
+
+The hook is opt-in, since it replaces any hook the test binary set before it; the previous hook still prints
+the message that follows.
+*/
+
+use std::sync::Once;
+
+static INSTALL: Once = Once::new();
+
+/**
+Installs the hook, wrapping the current one.  Calling this again does nothing.
+*/
+pub fn install() {
+    INSTALL.call_once(|| {
+        let prior = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            match (current_task(), crate::call_site::current()) {
+                (Some(task), Some(call_site)) => eprintln!("panic in {task}, driven by {call_site}:"),
+                (Some(task), None) => eprintln!("panic in {task}:"),
+                (None, Some(call_site)) => eprintln!("panic in a future driven by {call_site}:"),
+                (None, None) => (),
+            }
+            prior(info)
+        }));
+    });
+}
+
+/**
+Describes the task running on this thread, if it can be identified.
+*/
+fn current_task() -> Option<String> {
+    //not the thread's name, which libtest sets to the test's, so every panic in a test would seem to be in a task
+    let label = some_executor::task::TASK_LABEL.with(|label| label.cloned())?;
+    #[cfg(feature = "logwise")]
+    return Some(format!("task `{label}` (logwise context {:?})", logwise::context::Context::current().context_id()));
+    #[cfg(not(feature = "logwise"))]
+    Some(format!("task `{label}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use some_executor::SomeExecutor;
+    use some_executor::task::{Configuration, Task};
+    use crate::objsafe::ObjsafeObserverExt;
+
+    #[test]
+    fn describes_task_being_polled() {
+        let task = Task::<_, Infallible>::without_notifications("describes".to_string(), async { super::current_task() }, Configuration::default());
+        let observer = crate::aruntime::SleepRuntime::new().spawn_objsafe(task.into_objsafe());
+        let description = observer.observe_value::<Option<String>>().unwrap();
+        assert!(description.starts_with("task `describes`"), "{description}");
+        //libtest names this thread after the test, but no task is running on it
+        assert_eq!(super::current_task(), None);
+    }
+
+    #[test]
+    fn records_executor_call_site() {
+        let line = line!() + 1;
+        let call_site = crate::sleep_on(async { crate::call_site::current() }).expect("inside sleep_on");
+        assert_eq!((call_site.executor, call_site.location.line()), ("sleep_on", line));
+        assert_eq!(crate::call_site::current(), None);
+    }
+}
@@ -5,9 +5,10 @@ A simple future type that is always pending.
 This is primarily useful for testing or "todo"-style workflows.
 */
 
-use std::future::Future;
-use std::pin::Pin;
-use std::task::{Context, Poll};
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
 
 #[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash)]
 pub struct PendForever;
@@ -20,6 +21,36 @@ impl Future for PendForever {
     }
 }
 
+/**
+A future of any output type that is always pending.  See [pending].
+*/
+pub struct Pending<T>(PhantomData<fn() -> T>);
+
+/**
+Creates a future that never completes, but claims to produce a `T`.
+
+Unlike [PendForever], this can stand in for futures of any output type, such as a `select` arm or the
+implementation of a trait that returns a typed future.
+
+# Example
+```
+use test_executors::pend_forever::pending;
+let result = test_executors::poll_once_pin(pending::<u32>());
+assert!(result.is_pending());
+```
+*/
+pub const fn pending<T>() -> Pending<T> {
+    Pending(PhantomData)
+}
+
+impl<T> Future for Pending<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Poll::Pending
+    }
+}
+
 
 //boilerplate
 impl Default for PendForever {
@@ -28,3 +59,23 @@ impl Default for PendForever {
     }
 }
 
+impl<T> Default for Pending<T> {
+    fn default() -> Self {
+        pending()
+    }
+}
+
+impl<T> Clone for Pending<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Pending<T> {}
+
+impl<T> core::fmt::Debug for Pending<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Pending<{}>", core::any::type_name::<T>())
+    }
+}
+
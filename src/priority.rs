@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A single-threaded runtime that polls tasks by priority.
+
+[PriorityRuntime] runs some_executor [Task]s on the current thread.  Whenever several of them have been woken, it
+polls the one whose `Configuration` gives it the highest [Priority](some_executor::Priority) next, so a test can
+check what happens when a high-priority task is woken while low-priority work is queued: it runs first.  Tasks of
+equal priority are polled in the order they were woken.
+
+```
+use std::cell::RefCell;
+use std::rc::Rc;
+use some_executor::Priority;
+use some_executor::task::{ConfigurationBuilder, Task};
+use test_executors::priority::PriorityRuntime;
+
+let order = Rc::new(RefCell::new(Vec::new()));
+let mut runtime = PriorityRuntime::new();
+for (name, priority) in [("report", Priority::Background), ("click", Priority::UserInteractive)] {
+    let order = order.clone();
+    let configuration = ConfigurationBuilder::new().priority(priority).build();
+    runtime.spawn(Task::without_notifications(name.to_string(), async move { order.borrow_mut().push(name) }, configuration));
+}
+runtime.run();
+assert_eq!(*order.borrow(), ["click", "report"]);
+```
+
+Priorities rank as they are declared, from `UserInteractive` down to `Unknown`, the priority of a default
+`Configuration`.  The future passed to [PriorityRuntime::block_on] runs at `Unknown`, and tasks spawned with
+[crate::spawn_local_task] inherit the priority of the task that spawned them.  Priorities only decide which woken
+task goes next; a running task is never interrupted.
+*/
+
+use std::future::{Future, IntoFuture};
+use some_executor::observer::ObserverNotified;
+use some_executor::task::Task;
+use crate::coop::OnStarvation;
+use crate::local::LocalJoinHandle;
+use crate::scheduler::Scheduler;
+
+/**
+A single-threaded runtime that polls the woken task with the highest priority first.
+*/
+pub struct PriorityRuntime {
+    scheduler: Scheduler,
+}
+
+impl PriorityRuntime {
+    /**
+    A runtime with no tasks, which polls woken tasks in priority order.
+    */
+    pub fn new() -> Self {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_prioritized(true);
+        PriorityRuntime { scheduler }
+    }
+
+    /**
+    Detects a task that stays woken while the runtime polls other tasks more than `polls` times, and reports it
+    as [crate::error::Starved], as `action` says.
+    */
+    pub fn detect_starvation(mut self, polls: u64, action: OnStarvation) -> Self {
+        self.scheduler.set_starvation(Some((polls, action)));
+        self
+    }
+
+    /**
+    Spawns `task` at the priority of its configuration, returning a handle to its output.
+
+    The task is polled once the runtime runs, and not before its `poll_after` time.  The task's notifier, if any, is
+    not called; await the handle instead.
+    */
+    #[track_caller]
+    pub fn spawn<F, N>(&mut self, task: Task<F, N>) -> LocalJoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        N: ObserverNotified<F::Output>,
+    {
+        let priority = task.priority();
+        let poll_after = task.poll_after();
+        let label = task.label().to_string();
+        crate::logging::info!("spawned future: {label} at {priority:?}", label = &label, priority = priority);
+        let registration = crate::debug::Registration::new(&label, std::panic::Location::caller());
+        let future = task.into_future();
+        let (sender, handle) = crate::local::channel();
+        let id = self.scheduler.spawn_with_priority(Box::pin(registration.track(async move {
+            let now = crate::sys::time::Instant::now();
+            if poll_after > now {
+                crate::time::sleep(poll_after - now).await;
+            }
+            sender.send(future.await)
+        })), priority);
+        self.scheduler.name_task(id, &label);
+        handle.with_task_id(id)
+    }
+
+    /**
+    Runs the spawned tasks, and the tasks they spawn, until all of them have completed.
+    */
+    pub fn run(&mut self) {
+        self.scheduler.run_all(&mut |_| Some(0))
+    }
+
+    /**
+    Runs `future`, at `Priority::Unknown`, and the spawned tasks, until `future` completes.
+
+    Tasks that are still running when `future` completes are dropped.
+    */
+    pub fn block_on<F: IntoFuture>(&mut self, future: F) -> F::Output {
+        self.scheduler.block_on(future.into_future(), &mut |_| Some(0))
+    }
+}
+
+impl Default for PriorityRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for PriorityRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PriorityRuntime").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use some_executor::Priority;
+    use some_executor::task::{ConfigurationBuilder, Task};
+    use super::PriorityRuntime;
+
+    fn task<F: std::future::Future>(name: &str, priority: Priority, future: F) -> Task<F, std::convert::Infallible> {
+        Task::without_notifications(name.to_string(), future, ConfigurationBuilder::new().priority(priority).build())
+    }
+
+    #[test]
+    fn woken_high_priority_task_goes_first() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let mut runtime = PriorityRuntime::new();
+        let (sender, receiver) = crate::local::channel::<()>();
+        let log = order.clone();
+        runtime.spawn(task("urgent", Priority::UserInteractive, async move {
+            receiver.await;
+            log.borrow_mut().push("urgent");
+        }));
+        for name in ["first", "second"] {
+            let log = order.clone();
+            runtime.spawn(task(name, Priority::Background, async move {
+                log.borrow_mut().push(name);
+            }));
+        }
+        let log = order.clone();
+        runtime.spawn(task("waker", Priority::Utility, async move {
+            log.borrow_mut().push("waker");
+            sender.send(());
+        }));
+        runtime.run();
+        //the urgent task is woken by `waker` while both background tasks are still queued
+        assert_eq!(*order.borrow(), ["waker", "urgent", "first", "second"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "starved was woken but not polled while the runtime polled other tasks 6 times")]
+    fn detects_starved_task() {
+        let mut runtime = PriorityRuntime::new().detect_starvation(5, crate::coop::OnStarvation::Panic);
+        runtime.spawn(task("busy", Priority::UserInteractive, async {
+            for _ in 0..10 {
+                crate::future_util::yield_now().await;
+            }
+        }));
+        runtime.spawn(task("starved", Priority::Background, async {}));
+        runtime.run();
+    }
+}
@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Property testing of async code with [proptest](https://crates.io/crates/proptest).
+
+[run] drives each generated case through [crate::sleep_on] while leaving case generation, failure persistence and
+shrinking to proptest.  The [crate::async_proptest] macro expands to a call to [run].
+
+# Example
+```
+use test_executors::prop::{run, Config};
+run(Config::with_cases(16), (0..100u32,), |(n,)| async move {
+    assert!(n < 100);
+});
+```
+*/
+
+use std::future::Future;
+use proptest::strategy::Strategy;
+use proptest::test_runner::TestRunner;
+
+pub use proptest::arbitrary::any;
+pub use proptest::test_runner::Config;
+
+/**
+Runs `test` against values generated by `strategy`, one [crate::sleep_on] per case.
+
+# Panics
+
+Panics with the minimal failing input when a case fails.
+*/
+pub fn run<S, T, F>(config: Config, strategy: S, test: T)
+where
+    S: Strategy,
+    T: Fn(S::Value) -> F,
+    F: Future<Output = ()>,
+{
+    let mut runner = TestRunner::new(config);
+    let result = runner.run(&strategy, |value| {
+        crate::sleep_on(test(value));
+        Ok(())
+    });
+    if let Err(e) = result {
+        panic!("{e}");
+    }
+}
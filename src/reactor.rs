@@ -0,0 +1,375 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+A minimal reactor, for loopback networking tests.
+
+[Async] wraps a std socket (a `TcpListener`, `TcpStream`, `UnixListener` or `UnixStream`), puts it in nonblocking
+mode, and offers async versions of its operations.  When an operation would block, the task registers interest in
+the socket with a reactor thread, which waits on every registered socket with `poll(2)` and wakes the task once its
+socket is ready.  So a test can run a server and a client on any of this crate's executors:
+
+```
+use std::net::{TcpListener, TcpStream};
+use test_executors::reactor::Async;
+
+let listener = Async::new(TcpListener::bind("127.0.0.1:0").unwrap()).unwrap();
+let address = listener.get_ref().local_addr().unwrap();
+test_executors::spawn_on("server", async move {
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut buffer = [0; 5];
+    stream.read_exact(&mut buffer).await.unwrap();
+    stream.write_all(&buffer).await.unwrap();
+});
+test_executors::sleep_on(async {
+    let stream = Async::<TcpStream>::connect(address).unwrap();
+    stream.write_all(b"hello").await.unwrap();
+    let mut echo = [0; 5];
+    stream.read_exact(&mut echo).await.unwrap();
+    assert_eq!(&echo, b"hello");
+});
+```
+
+This is meant for tests, not throughput: the reactor is one thread, and rebuilds its list of sockets on every
+wake.  Requires the `reactor` feature, and a unix target.
+*/
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::task::{Poll, Waker};
+
+#[allow(unsafe_code)]
+mod sys {
+    use std::os::raw::{c_int, c_short};
+
+    pub(super) const POLLIN: c_short = 0x1;
+    pub(super) const POLLOUT: c_short = 0x4;
+
+    #[repr(C)]
+    pub(super) struct PollFd {
+        pub(super) fd: c_int,
+        pub(super) events: c_short,
+        pub(super) revents: c_short,
+    }
+
+    #[cfg(target_os = "linux")]
+    type NFds = std::os::raw::c_ulong;
+    #[cfg(not(target_os = "linux"))]
+    type NFds = std::os::raw::c_uint;
+
+    extern "C" {
+        fn poll(fds: *mut PollFd, nfds: NFds, timeout: c_int) -> c_int;
+    }
+
+    /**
+    Waits, without a timeout, until one of `fds` has an event, filling in their `revents`.
+    */
+    pub(super) fn poll_forever(fds: &mut [PollFd]) -> std::io::Result<()> {
+        loop {
+            //SAFETY: the pointer and length describe a live, exclusively borrowed slice of pollfd structs
+            let result = unsafe { poll(fds.as_mut_ptr(), fds.len() as NFds, -1) };
+            if result >= 0 {
+                return Ok(());
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Interest {
+    waker: Option<Waker>,
+    ready: bool,
+}
+
+struct Registration {
+    fd: RawFd,
+    events: std::os::raw::c_short,
+    interest: Weak<Mutex<Interest>>,
+}
+
+struct Reactor {
+    registrations: Mutex<Vec<Registration>>,
+    //writing to this interrupts the reactor's poll, so that it picks up new registrations
+    notify: UnixStream,
+}
+
+fn reactor() -> &'static Reactor {
+    static REACTOR: OnceLock<Reactor> = OnceLock::new();
+    REACTOR.get_or_init(|| {
+        let (notify, notified) = UnixStream::pair().expect("Can't create the reactor's socket pair");
+        notify.set_nonblocking(true).expect("Can't make the reactor's socket nonblocking");
+        notified.set_nonblocking(true).expect("Can't make the reactor's socket nonblocking");
+        //the thread waits for this initialization to finish before it can get the reactor
+        std::thread::Builder::new()
+            .name("test_executors reactor".to_string())
+            .spawn(move || reactor().run(notified))
+            .expect("Cant spawn thread");
+        Reactor { registrations: Mutex::new(Vec::new()), notify }
+    })
+}
+
+impl Reactor {
+    fn register(&self, fd: RawFd, events: std::os::raw::c_short, interest: Weak<Mutex<Interest>>) {
+        self.registrations.lock().unwrap().push(Registration { fd, events, interest });
+        //a full socket already has a notification pending
+        let _ = (&self.notify).write(&[0]);
+    }
+
+    fn run(&self, mut notified: UnixStream) -> ! {
+        let mut fds = Vec::new();
+        let mut interests = Vec::new();
+        loop {
+            fds.clear();
+            interests.clear();
+            fds.push(sys::PollFd { fd: notified.as_raw_fd(), events: sys::POLLIN, revents: 0 });
+            {
+                let mut registrations = self.registrations.lock().unwrap();
+                registrations.retain(|registration| registration.interest.upgrade().is_some_and(|interest| !interest.lock().unwrap().ready));
+                for registration in registrations.iter() {
+                    fds.push(sys::PollFd { fd: registration.fd, events: registration.events, revents: 0 });
+                    interests.push(registration.interest.clone());
+                }
+            }
+            sys::poll_forever(&mut fds).expect("poll(2) failed");
+            if fds[0].revents != 0 {
+                while matches!(notified.read(&mut [0; 64]), Ok(n) if n > 0) {}
+            }
+            for (fd, interest) in fds[1..].iter().zip(&interests) {
+                //errors and hangups are reported whatever was asked for, and the retried operation will see them
+                if fd.revents == 0 {
+                    continue;
+                }
+                if let Some(interest) = interest.upgrade() {
+                    let waker = {
+                        let mut interest = interest.lock().unwrap();
+                        interest.ready = true;
+                        interest.waker.take()
+                    };
+                    if let Some(waker) = waker {
+                        crate::logging::trace!("socket {fd} is ready", fd = fd.fd);
+                        waker.wake();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+Waits until `fd` has one of `events`, or an error or hangup.
+*/
+async fn ready(fd: RawFd, events: std::os::raw::c_short) {
+    let interest = Arc::new(Mutex::new(Interest::default()));
+    let mut registered = false;
+    std::future::poll_fn(|cx| {
+        let mut state = interest.lock().unwrap();
+        if state.ready {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        drop(state);
+        if !registered {
+            reactor().register(fd, events, Arc::downgrade(&interest));
+            registered = true;
+        }
+        Poll::Pending
+    }).await
+}
+
+/**
+A socket that can be put in nonblocking mode, for [Async].
+*/
+pub trait Source: AsRawFd {
+    /**
+    Moves the socket into or out of nonblocking mode.
+    */
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+macro_rules! source {
+    ($($t:ty),*) => {$(
+        impl Source for $t {
+            fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+                <$t>::set_nonblocking(self, nonblocking)
+            }
+        }
+    )*};
+}
+source!(TcpListener, TcpStream, UnixListener, UnixStream);
+
+/**
+A nonblocking socket whose operations are futures, woken by the reactor.
+*/
+#[derive(Debug)]
+pub struct Async<T> {
+    io: T,
+}
+
+impl<T: Source> Async<T> {
+    /**
+    Puts `io` in nonblocking mode, and wraps it.
+    */
+    pub fn new(io: T) -> io::Result<Self> {
+        io.set_nonblocking(true)?;
+        Ok(Async { io })
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /**
+    Unwraps the socket, which is left in nonblocking mode.
+    */
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
+    /**
+    Waits until the socket is readable.
+    */
+    pub async fn readable(&self) {
+        ready(self.io.as_raw_fd(), sys::POLLIN).await
+    }
+
+    /**
+    Waits until the socket is writable.
+    */
+    pub async fn writable(&self) {
+        ready(self.io.as_raw_fd(), sys::POLLOUT).await
+    }
+
+    /**
+    Performs a nonblocking read operation, waiting for the socket to be readable each time it would block.
+    */
+    pub async fn read_with<R>(&self, mut op: impl FnMut(&T) -> io::Result<R>) -> io::Result<R> {
+        loop {
+            match op(&self.io) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.readable().await,
+                result => return result,
+            }
+        }
+    }
+
+    /**
+    Performs a nonblocking write operation, waiting for the socket to be writable each time it would block.
+    */
+    pub async fn write_with<R>(&self, mut op: impl FnMut(&T) -> io::Result<R>) -> io::Result<R> {
+        loop {
+            match op(&self.io) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.writable().await,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<T: Source> Async<T>
+where
+    for<'a> &'a T: Read + Write,
+{
+    /**
+    Reads some bytes into `buf`, returning how many; 0 means the peer closed the connection.
+    */
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_with(|mut io| io.read(buf)).await
+    }
+
+    /**
+    Reads exactly enough bytes to fill `buf`.
+    */
+    pub async fn read_exact(&self, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf).await? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /**
+    Writes some bytes from `buf`, returning how many.
+    */
+    pub async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.write_with(|mut io| io.write(buf)).await
+    }
+
+    /**
+    Writes all of `buf`.
+    */
+    pub async fn write_all(&self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Async<TcpListener> {
+    /**
+    Accepts a connection.
+    */
+    pub async fn accept(&self) -> io::Result<(Async<TcpStream>, SocketAddr)> {
+        let (stream, address) = self.read_with(|listener| listener.accept()).await?;
+        Ok((Async::new(stream)?, address))
+    }
+}
+
+impl Async<TcpStream> {
+    /**
+    Connects to `address`.
+
+    Connecting blocks the calling thread, which is brief for the loopback connections this is meant for.
+    */
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Async::new(TcpStream::connect(address)?)
+    }
+}
+
+impl Async<UnixListener> {
+    /**
+    Accepts a connection.
+    */
+    pub async fn accept(&self) -> io::Result<(Async<UnixStream>, std::os::unix::net::SocketAddr)> {
+        let (stream, address) = self.read_with(|listener| listener.accept()).await?;
+        Ok((Async::new(stream)?, address))
+    }
+}
+
+impl Async<UnixStream> {
+    /**
+    A connected pair of sockets.
+    */
+    pub fn pair() -> io::Result<(Self, Self)> {
+        let (a, b) = UnixStream::pair()?;
+        Ok((Async::new(a)?, Async::new(b)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Async;
+
+    #[test]
+    fn wakes_reader_on_write() {
+        let (reader, writer) = Async::<std::os::unix::net::UnixStream>::pair().unwrap();
+        crate::spawn_on("writer", async move {
+            crate::time::sleep(std::time::Duration::from_millis(10)).await;
+            writer.write_all(b"ping").await.unwrap();
+        });
+        let mut buffer = [0; 4];
+        crate::sleep_on(reader.read_exact(&mut buffer)).unwrap();
+        assert_eq!(&buffer, b"ping");
+        //the writer has hung up
+        assert_eq!(crate::sleep_on(reader.read(&mut buffer)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Spawning a future and awaiting its output later.
+
+[crate::spawn_with_handle] spawns a future like [crate::spawn_on], and returns a [RemoteHandle] that resolves with
+its output.  Dropping the handle cancels the task, so a test that stops caring about a result does not leave the
+task running; call [RemoteHandle::detach_on_drop] to let it run to completion instead.
+
+```
+let handle = test_executors::spawn_with_handle(async { 2 + 2 });
+assert_eq!(test_executors::sleep_on(handle), 4);
+```
+*/
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+enum Slot<T> {
+    Pending(Option<Waker>),
+    Ready(T),
+    Taken,
+}
+
+struct Shared<T> {
+    output: Mutex<Slot<T>>,
+    cancelled: AtomicBool,
+    //the task's, so that cancelling can wake it to drop its future
+    task: Mutex<Option<Waker>>,
+}
+
+/**
+Awaits the output of a task spawned by [crate::spawn_with_handle], cancelling the task when dropped.
+*/
+pub struct RemoteHandle<T> {
+    shared: Arc<Shared<T>>,
+    detach_on_drop: bool,
+}
+
+/**
+The spawned future, which stops early if its handle is dropped.
+*/
+pub(crate) struct Remote<F: Future> {
+    future: Pin<Box<F>>,
+    shared: Arc<Shared<F::Output>>,
+}
+
+/**
+Wraps `future` so that its output is sent to the returned handle.
+*/
+pub(crate) fn remote<F: Future>(future: F) -> (Remote<F>, RemoteHandle<F::Output>) {
+    let shared = Arc::new(Shared {
+        output: Mutex::new(Slot::Pending(None)),
+        cancelled: AtomicBool::new(false),
+        task: Mutex::new(None),
+    });
+    (Remote { future: Box::pin(future), shared: shared.clone() }, RemoteHandle { shared, detach_on_drop: false })
+}
+
+impl<F: Future> Future for Remote<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        *self.shared.task.lock().unwrap() = Some(cx.waker().clone());
+        if self.shared.cancelled.load(Ordering::Acquire) {
+            crate::logging::trace!("remote task cancelled");
+            return Poll::Ready(());
+        }
+        match self.future.as_mut().poll(cx) {
+            Poll::Ready(value) => {
+                let prior = std::mem::replace(&mut *self.shared.output.lock().unwrap(), Slot::Ready(value));
+                if let Slot::Pending(Some(waker)) = prior {
+                    waker.wake();
+                }
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> RemoteHandle<T> {
+    /**
+    Lets the task run to completion when the handle is dropped, rather than cancelling it.
+    */
+    pub fn detach_on_drop(mut self) -> Self {
+        self.detach_on_drop = true;
+        self
+    }
+
+    /**
+    Whether the task has completed.
+    */
+    pub fn is_finished(&self) -> bool {
+        !matches!(*self.shared.output.lock().unwrap(), Slot::Pending(_))
+    }
+}
+
+impl<T> Future for RemoteHandle<T> {
+    type Output = T;
+
+    /**
+    # Panics
+    If polled again after returning the task's output.
+    */
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut output = self.shared.output.lock().unwrap();
+        match std::mem::replace(&mut *output, Slot::Taken) {
+            Slot::Ready(value) => Poll::Ready(value),
+            Slot::Pending(_) => {
+                *output = Slot::Pending(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            Slot::Taken => panic!("RemoteHandle polled after completion"),
+        }
+    }
+}
+
+impl<T> Drop for RemoteHandle<T> {
+    fn drop(&mut self) {
+        if self.detach_on_drop {
+            return;
+        }
+        self.shared.cancelled.store(true, Ordering::Release);
+        let task = self.shared.task.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(task) = task {
+            task.wake();
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for RemoteHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteHandle")
+            .field("finished", &self.is_finished())
+            .field("detach_on_drop", &self.detach_on_drop)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    struct Dropped(mpsc::Sender<&'static str>);
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            let _ = self.0.send("dropped");
+        }
+    }
+
+    #[test]
+    fn drop_cancels_unless_detached() {
+        let (sender, receiver) = mpsc::channel();
+        let guard = Dropped(sender.clone());
+        drop(crate::spawn_with_handle(async move {
+            let _guard = guard;
+            crate::pend_forever::PendForever.await
+        }));
+        assert_eq!(receiver.recv().unwrap(), "dropped");
+
+        let (go, wait) = mpsc::channel::<()>();
+        drop(crate::spawn_with_handle(async move {
+            wait.recv().unwrap();
+            sender.send("finished").unwrap();
+        }).detach_on_drop());
+        go.send(()).unwrap();
+        assert_eq!(receiver.recv().unwrap(), "finished");
+    }
+}
@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Retrying a flaky async operation, or waiting for a condition to become true, on any executor.
+
+Integration-style tests that reach a real server or file system sometimes fail for reasons the test does not
+control.  [retry] runs an operation until it succeeds or runs out of attempts, waiting between attempts as a
+[Backoff] says, with [crate::time::sleep], so it works on every executor, natively and in the browser:
+
+```
+use std::time::Duration;
+use test_executors::retry::{retry, Backoff};
+
+let mut calls = 0;
+let result = test_executors::sleep_on(retry(3, Backoff::fixed(Duration::from_millis(1)), || {
+    calls += 1;
+    let calls = calls;
+    async move { if calls < 3 { Err("not yet") } else { Ok(calls) } }
+}));
+assert_eq!(result, Ok(3));
+```
+
+[run_until] instead checks some state until a predicate holds, for "eventually consistent" assertions, and
+reports the last state it saw if the predicate never holds:
+
+```
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use test_executors::retry::run_until;
+
+let counter = AtomicU32::new(0);
+let err = test_executors::sleep_on(run_until(
+    || async { counter.fetch_add(1, Ordering::Relaxed) },
+    |count| *count > 1_000_000,
+    Duration::from_millis(20),
+)).unwrap_err();
+assert!(err.last().is_some());
+```
+
+Both wait on the real clock: backoff delays and the timeout of [run_until] are waited out with
+[crate::time::sleep], and this crate has no mock clock to skip them, so keep them short in tests.
+*/
+
+use std::future::Future;
+use std::time::Duration;
+use crate::error::Unsatisfied;
+use crate::sys::time::Instant;
+
+/**
+How long [run_until] sleeps between checks of the state.
+*/
+const CHECK_INTERVAL: Duration = Duration::from_millis(1);
+
+/**
+How long [retry] waits after each failed attempt.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Backoff {
+    /// Wait the same time after every attempt.
+    Fixed(Duration),
+    /// Wait `initial` after the first attempt, doubling after each one after that, up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    /**
+    Retries at once.
+    */
+    pub const fn none() -> Self {
+        Backoff::Fixed(Duration::ZERO)
+    }
+
+    /**
+    Waits `delay` after every attempt.
+    */
+    pub const fn fixed(delay: Duration) -> Self {
+        Backoff::Fixed(delay)
+    }
+
+    /**
+    Waits `initial` after the first attempt, doubling after each one after that, up to `max`.
+    */
+    pub const fn exponential(initial: Duration, max: Duration) -> Self {
+        Backoff::Exponential { initial, max }
+    }
+
+    /**
+    How long to wait after attempt `attempt`, counting from 1.
+    */
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+                initial.saturating_mul(factor).min(max)
+            }
+        }
+    }
+}
+
+/**
+Runs the future made by `operation` up to `attempts` times, until it returns `Ok`, waiting as `backoff` says
+between attempts.
+
+Returns the first `Ok`, or the `Err` of the last attempt.
+
+# Panics
+If `attempts` is 0.
+*/
+pub async fn retry<T, E, F, O>(attempts: u32, backoff: Backoff, mut operation: O) -> Result<T, E>
+where
+    O: FnMut() -> F,
+    F: Future<Output = Result<T, E>>,
+{
+    assert!(attempts > 0, "retry needs at least one attempt");
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt == attempts => {
+                crate::logging::warning!("attempt {attempt} of {attempts} failed; giving up", attempt=attempt, attempts=attempts);
+                return Err(e);
+            }
+            Err(_) => {
+                let delay = backoff.delay(attempt);
+                crate::logging::info!("attempt {attempt} of {attempts} failed; retrying in {delay:?}", attempt=attempt, attempts=attempts, delay=delay);
+                if delay > Duration::ZERO {
+                    crate::time::sleep(delay).await;
+                }
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/**
+Awaits the future made by `check`, again and again, until its output satisfies `predicate`, and returns that
+output.
+
+Gives up once `timeout` has passed, even in the middle of a check, returning the last output that was checked.
+*/
+pub async fn run_until<T, F, C, P>(mut check: C, mut predicate: P, timeout: Duration) -> Result<T, Unsatisfied<T>>
+where
+    C: FnMut() -> F,
+    F: Future<Output = T>,
+    P: FnMut(&T) -> bool,
+{
+    let start = Instant::now();
+    let mut last = None;
+    let mut checks = 0;
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        let Ok(state) = crate::time::timeout(remaining, check()).await else {
+            return Err(Unsatisfied::new(timeout, checks, last));
+        };
+        checks += 1;
+        if predicate(&state) {
+            return Ok(state);
+        }
+        if start.elapsed() >= timeout {
+            return Err(Unsatisfied::new(timeout, checks, Some(state)));
+        }
+        last = Some(state);
+        crate::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{retry, run_until, Backoff};
+
+    #[test]
+    fn retries_until_attempts_run_out() {
+        let backoff = Backoff::exponential(Duration::from_millis(1), Duration::from_millis(4));
+        let delays: Vec<_> = (1..=5).map(|attempt| backoff.delay(attempt).as_millis()).collect();
+        assert_eq!(delays, [1, 2, 4, 4, 4]);
+        assert_eq!(Backoff::exponential(Duration::from_secs(1), Duration::from_secs(60)).delay(100), Duration::from_secs(60));
+
+        let mut calls = 0;
+        let result: Result<(), u32> = crate::sleep_on(retry(3, backoff, || {
+            calls += 1;
+            std::future::ready(Err(calls))
+        }));
+        assert_eq!(result, Err(3));
+    }
+
+    #[test]
+    fn runs_until_predicate_holds() {
+        let mut count = 0;
+        let state = crate::sleep_on(run_until(|| { count += 1; std::future::ready(count) }, |count| *count == 3, Duration::from_secs(10)));
+        assert_eq!(state, Ok(3));
+        let err = crate::sleep_on(run_until(|| std::future::ready("stale"), |state| *state == "fresh", Duration::from_millis(10))).unwrap_err();
+        assert_eq!(err.last(), Some(&"stale"));
+        assert!(err.to_string().starts_with("condition not satisfied within 10ms after "), "{err}");
+        assert!(err.to_string().ends_with(" checks; last state: \"stale\""), "{err}");
+    }
+}
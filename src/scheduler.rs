@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+The single-threaded task scheduler behind [crate::deterministic::DeterministicRuntime] and
+[crate::run_local_tasks].  The policy deciding which woken task to poll next is supplied by the caller, unless the
+scheduler is prioritized, in which case it polls the woken task with the highest priority.
+*/
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use crate::SimpleWakeShared;
+use crate::coop::OnStarvation;
+use crate::error::{Deadlock, Starved};
+use crate::event_log::{Entry, Event, PollResult, TaskId};
+use crate::hooks::ExecutorHooks;
+use crate::sys::time::Instant;
+use some_executor::Priority;
+
+pub(crate) type LocalTask = Pin<Box<dyn Future<Output = ()>>>;
+
+/**
+The id of the future passed to [Scheduler::block_on]; spawned tasks are numbered from 1.
+*/
+pub(crate) const MAIN: usize = 0;
+
+/**
+Tasks spawned since the scheduler last adopted any, with the ids they were given.
+*/
+struct SpawnQueue {
+    next_id: Cell<usize>,
+    //a task without a priority inherits that of the task that spawned it
+    tasks: RefCell<Vec<(usize, LocalTask, Option<Priority>)>>,
+}
+
+impl SpawnQueue {
+    fn push(&self, task: LocalTask, priority: Option<Priority>) -> TaskId {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.tasks.borrow_mut().push((id, task, priority));
+        TaskId::new(id)
+    }
+}
+
+type Spawned = Rc<SpawnQueue>;
+
+thread_local! {
+    static SPAWNER: RefCell<Option<Spawned>> = const { RefCell::new(None) };
+}
+
+/**
+Spawns `task` onto the scheduler running on this thread, returning its id, or gives it back if there is none.
+*/
+pub(crate) fn spawn_current(task: LocalTask) -> Result<TaskId, LocalTask> {
+    SPAWNER.with(|spawner| match &*spawner.borrow() {
+        Some(spawned) => Ok(spawned.push(task, None)),
+        None => Err(task),
+    })
+}
+
+/**
+Makes `spawned` the target of [spawn_current] until dropped, restoring the previous target (for nested
+schedulers).
+*/
+struct SpawnerGuard(Option<Spawned>);
+
+impl SpawnerGuard {
+    fn install(spawned: Spawned) -> Self {
+        SpawnerGuard(SPAWNER.with(|spawner| spawner.replace(Some(spawned))))
+    }
+}
+
+impl Drop for SpawnerGuard {
+    fn drop(&mut self) {
+        let prior = self.0.take();
+        SPAWNER.with(|spawner| *spawner.borrow_mut() = prior);
+    }
+}
+
+/**
+The label of task `id` in an [crate::event_log::EventLog].
+*/
+pub(crate) fn label(id: usize) -> String {
+    TaskId::new(id).to_string()
+}
+
+/**
+Woken task ids in the order they were woken, shared with their wakers.
+*/
+struct Shared {
+    ready: Mutex<VecDeque<usize>>,
+    wake: SimpleWakeShared,
+    //None unless recording
+    events: Mutex<Option<Recording>>,
+    hooks: Mutex<Option<Arc<dyn ExecutorHooks>>>,
+}
+
+struct Recording {
+    start: Instant,
+    entries: Vec<Entry>,
+}
+
+impl Shared {
+    fn make_ready(&self, id: usize) {
+        let mut ready = self.ready.lock().unwrap();
+        if !ready.contains(&id) {
+            ready.push_back(id);
+        }
+    }
+
+    /**
+    Calls `f` with the hooks and the label of `task`, if there are hooks.
+    */
+    fn hook(&self, task: usize, f: impl FnOnce(&dyn ExecutorHooks, &str)) {
+        let hooks = self.hooks.lock().unwrap().clone();
+        if let Some(hooks) = hooks {
+            f(&*hooks, &label(task));
+        }
+    }
+
+    fn record(&self, task: usize, event: impl FnOnce() -> Event) {
+        self.record_since(task, None, event)
+    }
+
+    /**
+    Records an event that took from `started` until now, or that happened now.
+    */
+    fn record_since(&self, task: usize, started: Option<Instant>, event: impl FnOnce() -> Event) {
+        if let Some(recording) = self.events.lock().unwrap().as_mut() {
+            let now = Instant::now();
+            let started = started.unwrap_or(now).max(recording.start);
+            recording.entries.push(Entry {
+                event: event(),
+                task,
+                at: started - recording.start,
+                duration: now.saturating_duration_since(started),
+            });
+        }
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.shared.record(self.id, || Event::woken(label(self.id)));
+        self.shared.hook(self.id, |hooks, task| hooks.on_wake(task));
+        self.shared.make_ready(self.id);
+        self.shared.wake.signal();
+    }
+}
+
+/**
+Picks which of the woken tasks (in the order they were woken) to poll next, returning its index, or `None` to wait
+for another task to be woken first.
+*/
+pub(crate) type Policy<'a> = &'a mut dyn FnMut(&VecDeque<usize>) -> Option<usize>;
+
+/**
+Polls a set of local tasks on the current thread, blocking while none of them is woken.
+*/
+pub(crate) struct Scheduler {
+    shared: Arc<Shared>,
+    //each waker is held here and by whatever may wake the task
+    tasks: Vec<Option<(LocalTask, Arc<TaskWaker>)>>,
+    spawned: Spawned,
+    //by task id, including MAIN
+    priorities: Vec<Priority>,
+    prioritized: bool,
+    budget: Option<u32>,
+    starvation: Option<(u64, OnStarvation)>,
+    //the poll count when each woken task was first seen woken, and whether it has been reported since
+    woken_at: HashMap<usize, (u64, bool)>,
+    //the names given with name_task, reported by check_starvation instead of the task's id
+    names: HashMap<usize, String>,
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> Self {
+        Scheduler {
+            shared: Arc::new(Shared {
+                ready: Mutex::new(VecDeque::new()),
+                wake: SimpleWakeShared::new(),
+                events: Mutex::new(None),
+                hooks: Mutex::new(None),
+            }),
+            tasks: Vec::new(),
+            spawned: Rc::new(SpawnQueue { next_id: Cell::new(MAIN + 1), tasks: RefCell::new(Vec::new()) }),
+            priorities: vec![Priority::Unknown],
+            prioritized: false,
+            budget: None,
+            starvation: None,
+            woken_at: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /**
+    Gives every poll of a task `budget` units to spend via [crate::coop::consume_budget].
+    */
+    pub(crate) fn set_budget(&mut self, budget: Option<u32>) {
+        self.budget = budget;
+    }
+
+    /**
+    Polls the woken task with the highest priority next, ignoring the policy, with ties going to the task woken
+    first.  Priorities rank as they are declared, from `UserInteractive` down to `Unknown`.
+    */
+    pub(crate) fn set_prioritized(&mut self, prioritized: bool) {
+        self.prioritized = prioritized;
+    }
+
+    /**
+    Reports a task that stays woken while more than `polls` polls of other tasks are made.
+    */
+    pub(crate) fn set_starvation(&mut self, starvation: Option<(u64, OnStarvation)>) {
+        self.starvation = starvation;
+    }
+
+    /**
+    Reports what the scheduler does to `hooks`.
+    */
+    pub(crate) fn set_hooks(&mut self, hooks: Option<Arc<dyn ExecutorHooks>>) {
+        *self.shared.hooks.lock().unwrap() = hooks;
+    }
+
+    /**
+    Starts or stops recording [Event]s.
+    */
+    pub(crate) fn set_recording(&mut self, recording: bool) {
+        let mut events = self.shared.events.lock().unwrap();
+        match (recording, events.is_some()) {
+            (true, false) => *events = Some(Recording { start: Instant::now(), entries: Vec::new() }),
+            (false, true) => *events = None,
+            _ => {}
+        }
+    }
+
+    /**
+    Removes and returns the events recorded so far.
+    */
+    pub(crate) fn take_events(&mut self) -> Vec<Entry> {
+        self.shared.events.lock().unwrap().as_mut().map(|recording| std::mem::take(&mut recording.entries)).unwrap_or_default()
+    }
+
+    /**
+    Adds a task, ready to be polled, returning its id.
+    */
+    pub(crate) fn spawn(&mut self, task: LocalTask) -> TaskId {
+        self.spawned.push(task, None)
+    }
+
+    /**
+    Adds a task with `priority`, ready to be polled, returning its id.
+    */
+    pub(crate) fn spawn_with_priority(&mut self, task: LocalTask, priority: Priority) -> TaskId {
+        self.spawned.push(task, Some(priority))
+    }
+
+    /**
+    Names task `id` as `name` when reporting it as starved.
+    */
+    pub(crate) fn name_task(&mut self, id: TaskId, name: &str) {
+        self.names.insert(id.get() as usize, name.to_string());
+    }
+
+    /**
+    The number of tasks that have not completed.
+    */
+    pub(crate) fn live(&self) -> usize {
+        self.tasks.iter().filter(|t| t.is_some()).count() + self.spawned.tasks.borrow().len()
+    }
+
+    /**
+    Runs `future`, and the tasks, until `future` completes.  Tasks that have not completed are dropped.
+    */
+    pub(crate) fn block_on<F: Future>(&mut self, future: F, policy: Policy) -> F::Output {
+        self.try_block_on(future, policy, false).expect("deadlocks are only detected when asked")
+    }
+
+    /**
+    Like [Scheduler::block_on], but if `detect_deadlock`, gives up once no task is woken and no copy of any waker
+    is held outside the scheduler, so that none ever can be.
+    */
+    pub(crate) fn try_block_on<F: Future>(&mut self, future: F, policy: Policy, detect_deadlock: bool) -> Result<F::Output, Deadlock> {
+        let mut future = core::pin::pin!(future);
+        let mut output = None;
+        let result = self.run(policy, Some(&mut |cx| match future.as_mut().poll(cx) {
+            Poll::Ready(o) => {
+                output = Some(o);
+                true
+            }
+            Poll::Pending => false,
+        }), detect_deadlock);
+        self.tasks.clear();
+        self.priorities.truncate(1);
+        self.woken_at.clear();
+        self.names.clear();
+        self.spawned.tasks.borrow_mut().clear();
+        //so the next future's tasks are numbered from 1 again
+        self.spawned.next_id.set(MAIN + 1);
+        self.shared.ready.lock().unwrap().clear();
+        result.map(|()| output.expect("main future completed"))
+    }
+
+    /**
+    Runs the tasks until all of them have completed.
+    */
+    pub(crate) fn run_all(&mut self, policy: Policy) {
+        self.run(policy, None, false).expect("deadlocks are only detected when asked")
+    }
+
+    fn run(&mut self, policy: Policy, mut main: Option<&mut dyn FnMut(&mut Context<'_>) -> bool>, detect_deadlock: bool) -> Result<(), Deadlock> {
+        let _spawner = SpawnerGuard::install(self.spawned.clone());
+        let main_waker = self.waker(MAIN);
+        let start = Instant::now();
+        let mut polls = 0;
+        if main.is_some() {
+            self.shared.record(MAIN, || Event::spawned(label(MAIN)));
+            self.shared.make_ready(MAIN);
+        }
+        let mut last_polled = MAIN;
+        loop {
+            self.adopt_spawned(last_polled);
+            if main.is_none() && self.live() == 0 {
+                return Ok(());
+            }
+            let Some(id) = self.next_ready(policy) else {
+                crate::logging::trace!("no task to poll is ready");
+                if detect_deadlock {
+                    if let Some(waiting) = self.deadlocked(main.is_some().then_some(&main_waker)) {
+                        return Err(Deadlock::new(waiting, polls, start.elapsed()));
+                    }
+                }
+                self.shared.wake.wait();
+                continue;
+            };
+            polls += 1;
+            last_polled = id;
+            self.check_starvation(id, polls);
+            crate::logging::trace!("polling task {id}", id = id);
+            let _budget = self.budget.map(|limit| crate::coop::BudgetGuard::enter(id, limit));
+            self.shared.hook(id, |hooks, task| hooks.before_poll(task));
+            let started = Instant::now();
+            if id == MAIN {
+                if let Some(main) = main.as_mut() {
+                    let ready = main(&mut Context::from_waker(&Waker::from(main_waker.clone())));
+                    self.record_poll(id, started, ready);
+                    if ready {
+                        return Ok(());
+                    }
+                }
+            } else if let Some((mut task, waker)) = self.tasks[id - 1].take() {
+                let ready = task.as_mut().poll(&mut Context::from_waker(&Waker::from(waker.clone()))).is_ready();
+                self.record_poll(id, started, ready);
+                if !ready {
+                    self.tasks[id - 1] = Some((task, waker));
+                }
+            }
+        }
+    }
+
+    /**
+    Moves tasks spawned since the last call, by `parent` unless given a priority, into the task list, ready to be
+    polled.
+    */
+    fn adopt_spawned(&mut self, parent: usize) {
+        let spawned = std::mem::take(&mut *self.spawned.tasks.borrow_mut());
+        for (id, task, priority) in spawned {
+            //ids are given out in the order tasks are queued, so each is the next index
+            debug_assert_eq!(id, self.tasks.len() + 1);
+            let waker = self.waker(id);
+            self.tasks.push(Some((task, waker)));
+            self.priorities.push(priority.unwrap_or(self.priorities[parent]));
+            self.shared.record(id, || Event::spawned(label(id)));
+            self.shared.make_ready(id);
+        }
+    }
+
+    fn record_poll(&self, id: usize, started: Instant, ready: bool) {
+        self.shared.record_since(id, Some(started), || Event::polled(label(id), if ready { PollResult::Ready } else { PollResult::Pending }));
+        self.shared.hook(id, |hooks, task| hooks.after_poll(task, ready));
+        if ready {
+            self.shared.record(id, || Event::completed(label(id)));
+            self.shared.hook(id, |hooks, task| hooks.on_complete(task));
+        }
+    }
+
+    /**
+    Reports woken tasks that have waited too long, now that poll number `polls`, of task `polled`, is about to
+    happen.
+    */
+    fn check_starvation(&mut self, polled: usize, polls: u64) {
+        let Some((limit, action)) = self.starvation else {
+            return;
+        };
+        self.woken_at.remove(&polled);
+        let ready: Vec<usize> = self.shared.ready.lock().unwrap().iter().copied().collect();
+        self.woken_at.retain(|id, _| ready.contains(id));
+        for id in ready {
+            let (since, reported) = self.woken_at.entry(id).or_insert((polls, false));
+            let waited = polls - *since;
+            if waited > limit && !*reported {
+                let starved = Starved::new(self.names.get(&id).cloned().unwrap_or_else(|| label(id)), waited);
+                match action {
+                    OnStarvation::Warn => {
+                        crate::logging::warning!("{starved}", starved = starved.to_string());
+                        *reported = true;
+                    }
+                    OnStarvation::Panic => panic!("{starved}"),
+                }
+            }
+        }
+    }
+
+    /**
+    Removes and returns the woken task chosen by `policy`.
+    */
+    fn next_ready(&mut self, policy: Policy) -> Option<usize> {
+        let mut ready = self.shared.ready.lock().unwrap();
+        if ready.is_empty() {
+            return None;
+        }
+        let index = if self.prioritized {
+            //min_by_key keeps the first of equal keys, which was woken first
+            ready.iter().enumerate().min_by_key(|&(_, &id)| self.priorities[id]).map(|(index, _)| index)?
+        } else {
+            policy(&ready)?
+        };
+        ready.remove(index)
+    }
+
+    fn waker(&self, id: usize) -> Arc<TaskWaker> {
+        Arc::new(TaskWaker { id, shared: self.shared.clone() })
+    }
+
+    /**
+    The labels of the waiting tasks, if none of them is woken and no waker for any of them is held outside the
+    scheduler.  `main` is the waker of the main future, if it is still running.
+    */
+    fn deadlocked(&self, main: Option<&Arc<TaskWaker>>) -> Option<Vec<String>> {
+        let waiting: Vec<&Arc<TaskWaker>> = main.into_iter()
+            .chain(self.tasks.iter().flatten().map(|(_, waker)| waker))
+            .collect();
+        if waiting.iter().any(|waker| Arc::strong_count(waker) > 1) {
+            return None;
+        }
+        //a waker is woken before it is dropped, so once none is held, any wake is already in the queue
+        if !self.shared.ready.lock().unwrap().is_empty() {
+            return None;
+        }
+        Some(waiting.iter().map(|waker| label(waker.id)).collect())
+    }
+}
@@ -0,0 +1,466 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Measuring one run of a future.
+
+[time_on] drives a future like [crate::sleep_on], and returns its output together with [RunStats]: how long it
+took, and how many times it was polled.  [timed] measures the same way from inside another executor, so it also
+works on wasm32-unknown-unknown, where time is measured with `performance.now()`, and
+[crate::assert_completes_within] builds an assertion on it.  To time a stretch of a test rather than one future,
+start a [TestStopwatch] and check it with [crate::assert_elapsed_at_least] or [crate::assert_elapsed_at_most].
+
+With the `alloc-track` feature, and [TrackingAllocator] installed as the global allocator, the stats also count
+the allocations made while the future was being polled on this thread, so a test can assert that a hot path is
+allocation-free:
+
+```ignore
+#[global_allocator]
+static ALLOCATOR: test_executors::stats::TrackingAllocator = test_executors::stats::TrackingAllocator::system();
+
+#[test]
+fn hot_path_does_not_allocate() {
+    let (_, stats) = test_executors::stats::time_on(hot_path());
+    assert_eq!(stats.allocations(), Some(0));
+}
+```
+
+Large futures are a performance problem of their own: every `async fn` stores everything it holds across an
+`.await` inline, so a deep call chain can produce a state machine of many kilobytes that is moved around on
+every spawn.  [report_future_size] logs the size of a future type, warning above [FUTURE_SIZE_WARNING], and
+[spin_on_reporting] does the same for the future it drives.
+*/
+
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use crate::sys::time::Instant;
+
+/**
+Measurements of a run by [time_on].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RunStats {
+    elapsed: Duration,
+    polls: u64,
+    allocations: Option<u64>,
+    bytes_allocated: Option<u64>,
+}
+
+impl RunStats {
+    /**
+    How long the future took to complete, including time spent waiting to be woken.
+    */
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /**
+    How many times the future was polled.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /**
+    How many allocations (including reallocations) the future made while being polled.
+
+    `None` unless the `alloc-track` feature is enabled and [TrackingAllocator] is the global allocator.
+    */
+    pub fn allocations(&self) -> Option<u64> {
+        self.allocations
+    }
+
+    /**
+    How many bytes the future allocated while being polled, counting the new size of each reallocation.
+
+    `None` unless the `alloc-track` feature is enabled and [TrackingAllocator] is the global allocator.
+    */
+    pub fn bytes_allocated(&self) -> Option<u64> {
+        self.bytes_allocated
+    }
+}
+
+/**
+Blocks the calling thread until a future is ready, like [crate::sleep_on], measuring the run.
+*/
+pub fn time_on<F: IntoFuture>(future: F) -> (F::Output, RunStats) {
+    crate::sleep_on(timed(future))
+}
+
+/**
+Wraps `future` so that it completes with its output and the [RunStats] of the run, measured from its first poll.
+
+Unlike [time_on], this does not block, so it can be awaited on any executor, including in a wasm test.
+*/
+pub fn timed<F: IntoFuture>(future: F) -> Timed<F::IntoFuture> {
+    Timed { future: Box::pin(future.into_future()), start: None, polls: 0, allocated: Allocated::default() }
+}
+
+/**
+The future returned by [timed].
+*/
+pub struct Timed<F> {
+    future: Pin<Box<F>>,
+    start: Option<Instant>,
+    polls: u64,
+    allocated: Allocated,
+}
+
+impl<F> std::fmt::Debug for Timed<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Timed").field("polls", &self.polls).finish_non_exhaustive()
+    }
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = (F::Output, RunStats);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let start = *this.start.get_or_insert_with(Instant::now);
+        crate::logging::trace!("polling future");
+        this.polls += 1;
+        let before = Allocated::now();
+        let poll = this.future.as_mut().poll(cx);
+        this.allocated.add_since(before);
+        match poll {
+            Poll::Ready(val) => {
+                crate::logging::trace!("future is ready");
+                let tracked = Allocated::tracking();
+                Poll::Ready((val, RunStats {
+                    elapsed: start.elapsed(),
+                    polls: this.polls,
+                    allocations: tracked.then_some(this.allocated.allocations),
+                    bytes_allocated: tracked.then_some(this.allocated.bytes),
+                }))
+            }
+            Poll::Pending => {
+                crate::logging::trace!("future is not ready");
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/**
+Awaits a future, and panics if it took longer than a `Duration` to complete.  Evaluates to the future's output.
+
+The assertion is checked once the future completes, so it does not cut short a future that hangs; for that, see
+[crate::watchdog] or `#[async_test(timeout_ms = N)]`.  Time is measured as by [stats::timed](crate::stats::timed),
+so this works in wasm tests too.
+
+```
+# test_executors::sleep_on(async {
+let value = test_executors::assert_completes_within!(async { 2 }, std::time::Duration::from_secs(5));
+assert_eq!(value, 2);
+# });
+```
+*/
+#[macro_export]
+macro_rules! assert_completes_within {
+    ($future:expr, $duration:expr $(,)?) => {{
+        let duration: ::std::time::Duration = $duration;
+        let (output, stats) = $crate::stats::timed($future).await;
+        if stats.elapsed() > duration {
+            panic!("`{}` took {:?} to complete, more than {:?}", stringify!($future), stats.elapsed(), duration);
+        }
+        output
+    }};
+}
+
+/**
+Measures the time elapsed since it was started, on the same clock as the rest of this crate (`performance.now()`
+on wasm32-unknown-unknown).
+
+Only the real clock is supported: this crate has no mock clock, so the elapsed-time assertions measure time that
+really passed, and need margins wide enough for a loaded machine.
+
+```
+use std::time::Duration;
+let stopwatch = test_executors::stats::TestStopwatch::start();
+test_executors::sleep_on(test_executors::time::sleep(Duration::from_millis(5)));
+test_executors::assert_elapsed_at_least!(stopwatch, Duration::from_millis(5));
+test_executors::assert_elapsed_at_most!(stopwatch, Duration::from_secs(5), "sleep overslept");
+```
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TestStopwatch {
+    start: Instant,
+}
+
+impl TestStopwatch {
+    /**
+    Starts the stopwatch now.
+    */
+    pub fn start() -> Self {
+        TestStopwatch { start: Instant::now() }
+    }
+
+    /**
+    How long ago the stopwatch was started.
+    */
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /**
+    Starts the stopwatch again, returning the time elapsed until now.
+    */
+    pub fn restart(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.start);
+        self.start = now;
+        elapsed
+    }
+}
+
+/**
+Panics if less than a `Duration` has elapsed on a [stats::TestStopwatch](crate::stats::TestStopwatch).
+
+Like `assert!`, takes an optional message, with format arguments, to print on failure.
+*/
+#[macro_export]
+macro_rules! assert_elapsed_at_least {
+    ($stopwatch:expr, $duration:expr $(,)?) => {
+        $crate::assert_elapsed_at_least!($stopwatch, $duration, "assertion failed")
+    };
+    ($stopwatch:expr, $duration:expr, $($arg:tt)+) => {{
+        let duration: ::std::time::Duration = $duration;
+        let elapsed = $crate::stats::TestStopwatch::elapsed(&$stopwatch);
+        if elapsed < duration {
+            panic!("{}: only {:?} elapsed, less than {:?}", format_args!($($arg)+), elapsed, duration);
+        }
+    }};
+}
+
+/**
+Panics if more than a `Duration` has elapsed on a [stats::TestStopwatch](crate::stats::TestStopwatch).
+
+Like `assert!`, takes an optional message, with format arguments, to print on failure.
+*/
+#[macro_export]
+macro_rules! assert_elapsed_at_most {
+    ($stopwatch:expr, $duration:expr $(,)?) => {
+        $crate::assert_elapsed_at_most!($stopwatch, $duration, "assertion failed")
+    };
+    ($stopwatch:expr, $duration:expr, $($arg:tt)+) => {{
+        let duration: ::std::time::Duration = $duration;
+        let elapsed = $crate::stats::TestStopwatch::elapsed(&$stopwatch);
+        if elapsed > duration {
+            panic!("{}: {:?} elapsed, more than {:?}", format_args!($($arg)+), elapsed, duration);
+        }
+    }};
+}
+
+/**
+The size, in bytes, above which [report_future_size] warns.
+*/
+pub const FUTURE_SIZE_WARNING: usize = 16 * 1024;
+
+/**
+Logs the size of the future type `F`, warning if it is larger than [FUTURE_SIZE_WARNING], and returns it.
+
+Consider boxing the large parts of a future that warns.
+*/
+pub fn report_future_size<F: Future>() -> usize {
+    let size = std::mem::size_of::<F>();
+    let future = std::any::type_name::<F>();
+    if size > FUTURE_SIZE_WARNING {
+        crate::logging::warning!("future {future} is {size} bytes, which is more than {threshold}; consider boxing it",
+            future = future, size = size, threshold = FUTURE_SIZE_WARNING);
+    } else {
+        crate::logging::info!("future {future} is {size} bytes", future = future, size = size);
+    }
+    size
+}
+
+/**
+Like [crate::spin_on], but first reports the size of the future with [report_future_size].
+*/
+pub fn spin_on_reporting<F: IntoFuture>(future: F) -> F::Output {
+    report_future_size::<F::IntoFuture>();
+    crate::spin_on(future)
+}
+
+/**
+Allocation counts on this thread.
+*/
+#[derive(Debug, Default, Copy, Clone)]
+struct Allocated {
+    allocations: u64,
+    bytes: u64,
+}
+
+impl Allocated {
+    fn now() -> Self {
+        #[cfg(feature = "alloc-track")]
+        return tracking::current();
+        #[cfg(not(feature = "alloc-track"))]
+        Allocated::default()
+    }
+
+    fn tracking() -> bool {
+        #[cfg(feature = "alloc-track")]
+        return tracking::INSTALLED.load(std::sync::atomic::Ordering::Relaxed);
+        #[cfg(not(feature = "alloc-track"))]
+        false
+    }
+
+    fn add_since(&mut self, before: Allocated) {
+        let now = Allocated::now();
+        self.allocations += now.allocations - before.allocations;
+        self.bytes += now.bytes - before.bytes;
+    }
+}
+
+#[cfg(feature = "alloc-track")]
+pub use tracking::TrackingAllocator;
+
+#[cfg(feature = "alloc-track")]
+#[allow(unsafe_code)]
+mod tracking {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use super::Allocated;
+
+    pub(super) static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+    //const-initialized and without destructors, so these never allocate themselves
+    thread_local! {
+        static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+        static BYTES: Cell<u64> = const { Cell::new(0) };
+    }
+
+    pub(super) fn current() -> Allocated {
+        Allocated {
+            allocations: ALLOCATIONS.with(Cell::get),
+            bytes: BYTES.with(Cell::get),
+        }
+    }
+
+    fn count(bytes: usize) {
+        INSTALLED.store(true, Ordering::Relaxed);
+        //the thread-locals are unavailable while the thread is being torn down
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        let _ = BYTES.try_with(|total| total.set(total.get() + bytes as u64));
+    }
+
+    /**
+    A global allocator that counts the allocations made on each thread, for [super::RunStats], and otherwise
+    defers to another allocator.
+
+    Requires the `alloc-track` feature.
+    */
+    #[derive(Debug, Default)]
+    pub struct TrackingAllocator<A = System>(A);
+
+    impl TrackingAllocator<System> {
+        /**
+        Tracks allocations made through the system allocator.
+        */
+        pub const fn system() -> Self {
+            TrackingAllocator(System)
+        }
+    }
+
+    impl<A> TrackingAllocator<A> {
+        /**
+        Tracks allocations made through `inner`.
+        */
+        pub const fn new(inner: A) -> Self {
+            TrackingAllocator(inner)
+        }
+    }
+
+    unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            self.0.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            self.0.alloc_zeroed(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count(new_size);
+            self.0.realloc(ptr, layout, new_size)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::time_on;
+
+    #[cfg(feature = "alloc-track")]
+    #[global_allocator]
+    static ALLOCATOR: super::TrackingAllocator = super::TrackingAllocator::system();
+
+    #[test]
+    fn counts_polls() {
+        let mut yielded = false;
+        let (value, stats) = time_on(std::future::poll_fn(|cx| {
+            if yielded {
+                std::task::Poll::Ready(3)
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                std::task::Poll::Pending
+            }
+        }));
+        assert_eq!(value, 3);
+        assert_eq!(stats.polls(), 2);
+        #[cfg(not(feature = "alloc-track"))]
+        assert_eq!(stats.allocations(), None);
+    }
+
+    #[test]
+    fn reports_future_size() {
+        async fn holds(buffer: [u8; 32 * 1024]) -> u8 {
+            async {}.await;
+            buffer[0]
+        }
+        fn size_of<F: std::future::Future>(_: &F) -> usize {
+            super::report_future_size::<F>()
+        }
+        let big = holds([1; 32 * 1024]);
+        assert!(size_of(&big) > super::FUTURE_SIZE_WARNING);
+        assert_eq!(super::spin_on_reporting(big), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "took")]
+    fn asserts_completion_time() {
+        crate::sleep_on(async {
+            crate::assert_completes_within!(async { std::thread::sleep(std::time::Duration::from_millis(20)) }, std::time::Duration::from_millis(1));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "too slow: ")]
+    fn asserts_elapsed_time() {
+        let mut stopwatch = super::TestStopwatch::start();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        crate::assert_elapsed_at_least!(stopwatch, std::time::Duration::from_millis(10));
+        assert!(stopwatch.restart() >= std::time::Duration::from_millis(10));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        crate::assert_elapsed_at_most!(stopwatch, std::time::Duration::from_millis(1), "too {}", "slow");
+    }
+
+    #[cfg(feature = "alloc-track")]
+    #[test]
+    fn counts_allocations() {
+        let (_, stats) = time_on(async { std::hint::black_box(vec![0u8; 100]) });
+        assert_eq!(stats.allocations(), Some(1));
+        assert_eq!(stats.bytes_allocated(), Some(100));
+        let (_, stats) = time_on(async { std::hint::black_box(1 + 1) });
+        assert_eq!(stats.allocations(), Some(0));
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Driving a future by hand, one poll at a time.
+
+[StepExecutor] owns a future and polls it only when told to, with the same waker every time, keeping count of the
+wakes in between.  So a test can poll, poke whatever the future is waiting on, check that this woke the future,
+and poll again:
+
+```
+use std::task::Poll;
+use test_executors::step::StepExecutor;
+
+let mut step = StepExecutor::new(test_executors::future_util::yield_now());
+assert!(step.step().is_pending());
+//yield_now wakes itself before returning Pending
+assert!(step.is_woken());
+assert_eq!(step.wake_count(), 1);
+assert_eq!(step.step(), Poll::Ready(()));
+```
+
+Unlike [crate::poll_once], which polls with a waker that does nothing, nothing is lost between steps.
+
+# Several tasks
+
+[StepExecutor::spawn] adds more tasks, numbered from 1 (the future given to [StepExecutor::new] is task 0), each
+with a waker of its own.  [StepExecutor::step_task] polls one of them, and [StepExecutor::woken_tasks] lists those
+that have been woken since their last poll, so a test can play out an exact interleaving, such as finishing the
+second of two tasks first:
+
+```
+use std::cell::RefCell;
+use std::rc::Rc;
+use test_executors::step::StepExecutor;
+
+let log = Rc::new(RefCell::new(Vec::new()));
+let mut step = StepExecutor::new(std::future::pending::<()>());
+let ids: Vec<usize> = ["a", "b"].into_iter().map(|name| {
+    let log = log.clone();
+    step.spawn(async move {
+        test_executors::future_util::yield_now().await;
+        log.borrow_mut().push(name);
+    })
+}).collect();
+assert_eq!(ids, [1, 2]);
+assert!(step.step_task(1).is_pending());
+assert!(step.step_task(2).is_pending());
+assert_eq!(step.woken_tasks(), [1, 2]);
+assert!(step.step_task(2).is_ready());
+assert!(step.step_task(1).is_ready());
+assert_eq!(*log.borrow(), ["b", "a"]);
+```
+*/
+
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use crate::scheduler::MAIN;
+
+/**
+What a task's waker has seen.
+*/
+#[derive(Default)]
+struct Wakes {
+    count: AtomicU64,
+    //since the last step
+    woken: AtomicBool,
+}
+
+impl Wake for Wakes {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        crate::logging::trace!("step task woken");
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.woken.store(true, Ordering::Release);
+    }
+}
+
+struct Spawned {
+    //None once the task has completed
+    future: Option<Pin<Box<dyn Future<Output = ()>>>>,
+    wakes: Arc<Wakes>,
+}
+
+/**
+Polls a future, and any tasks spawned onto it, only when [StepExecutor::step] or [StepExecutor::step_task] is
+called.
+*/
+pub struct StepExecutor<F: Future> {
+    //None once the future has completed
+    future: Option<Pin<Box<F>>>,
+    wakes: Arc<Wakes>,
+    steps: u64,
+    //task id - 1
+    tasks: Vec<Spawned>,
+}
+
+impl<F: Future> StepExecutor<F> {
+    /**
+    Takes `future`, without polling it.
+    */
+    pub fn new<I: IntoFuture<IntoFuture = F>>(future: I) -> Self {
+        StepExecutor { future: Some(Box::pin(future.into_future())), wakes: Arc::new(Wakes::default()), steps: 0, tasks: Vec::new() }
+    }
+
+    /**
+    Adds a task, without polling it, returning its id.
+    */
+    pub fn spawn<G: Future<Output = ()> + 'static>(&mut self, future: G) -> usize {
+        self.tasks.push(Spawned { future: Some(Box::pin(future)), wakes: Arc::new(Wakes::default()) });
+        self.tasks.len()
+    }
+
+    /**
+    Polls task `id` once.
+
+    # Panics
+    If `id` is 0 (the future from [StepExecutor::new], whose output only [StepExecutor::step] can return), is not a
+    spawned task, or has already completed.
+    */
+    pub fn step_task(&mut self, id: usize) -> Poll<()> {
+        let task = self.spawned(id);
+        let future = task.future.as_mut().unwrap_or_else(|| panic!("StepExecutor::step_task called after task {id} completed"));
+        task.wakes.woken.store(false, Ordering::Release);
+        let waker = Waker::from(task.wakes.clone());
+        crate::logging::trace!("stepping task {id}", id = id);
+        let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+        if poll.is_ready() {
+            task.future = None;
+        }
+        poll
+    }
+
+    fn spawned(&mut self, id: usize) -> &mut Spawned {
+        assert_ne!(id, MAIN, "task 0 is stepped with StepExecutor::step");
+        let count = self.tasks.len();
+        self.tasks.get_mut(id - 1).unwrap_or_else(|| panic!("no task {id}; only {count} were spawned"))
+    }
+
+    fn wakes_of(&self, id: usize) -> &Wakes {
+        if id == MAIN {
+            &self.wakes
+        } else {
+            &self.tasks.get(id - 1).unwrap_or_else(|| panic!("no task {id}; only {} were spawned", self.tasks.len())).wakes
+        }
+    }
+
+    /**
+    The ids of the unfinished tasks, including 0, that have been woken since they were last polled, in order.
+    */
+    pub fn woken_tasks(&self) -> Vec<usize> {
+        (0..=self.tasks.len()).filter(|&id| !self.is_task_finished(id) && self.is_task_woken(id)).collect()
+    }
+
+    /**
+    Whether task `id` has been woken since it was last polled.
+    */
+    pub fn is_task_woken(&self, id: usize) -> bool {
+        self.wakes_of(id).woken.load(Ordering::Acquire)
+    }
+
+    /**
+    How many times task `id` has been woken, in total.
+    */
+    pub fn task_wake_count(&self, id: usize) -> u64 {
+        self.wakes_of(id).count.load(Ordering::Relaxed)
+    }
+
+    /**
+    Whether task `id` has completed.
+    */
+    pub fn is_task_finished(&self, id: usize) -> bool {
+        if id == MAIN {
+            self.is_finished()
+        } else {
+            self.tasks.get(id - 1).is_some_and(|task| task.future.is_none())
+        }
+    }
+
+    /**
+    Polls the future once.
+
+    # Panics
+    If the future has already completed.
+    */
+    pub fn step(&mut self) -> Poll<F::Output> {
+        let future = self.future.as_mut().expect("StepExecutor::step called after the future completed");
+        self.steps += 1;
+        self.wakes.woken.store(false, Ordering::Release);
+        let waker = Waker::from(self.wakes.clone());
+        let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+        if poll.is_ready() {
+            self.future = None;
+        }
+        poll
+    }
+
+    /**
+    How many times the future's waker has been woken, in total.
+    */
+    pub fn wake_count(&self) -> u64 {
+        self.wakes.count.load(Ordering::Relaxed)
+    }
+
+    /**
+    Whether the future's waker has been woken since the last step began.
+    */
+    pub fn is_woken(&self) -> bool {
+        self.wakes.woken.load(Ordering::Acquire)
+    }
+
+    /**
+    How many times the future has been polled.
+    */
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /**
+    Whether the future has completed.
+    */
+    pub fn is_finished(&self) -> bool {
+        self.future.is_none()
+    }
+}
+
+impl<F: Future> std::fmt::Debug for StepExecutor<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StepExecutor")
+            .field("steps", &self.steps)
+            .field("wake_count", &self.wake_count())
+            .field("is_woken", &self.is_woken())
+            .field("is_finished", &self.is_finished())
+            .field("tasks", &self.tasks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Poll;
+    use super::StepExecutor;
+
+    #[test]
+    fn counts_wakes_between_steps() {
+        let (sender, receiver) = crate::local::channel::<u8>();
+        let mut step = StepExecutor::new(receiver);
+        assert!(step.step().is_pending());
+        assert!(!step.is_woken());
+        sender.send(4);
+        assert!(step.is_woken());
+        assert_eq!(step.wake_count(), 1);
+        assert_eq!(step.step(), Poll::Ready(4));
+        assert!(!step.is_woken());
+        assert!(step.is_finished());
+        assert_eq!(step.steps(), 2);
+    }
+
+    #[test]
+    fn steps_chosen_task() {
+        let mut step = StepExecutor::new(async {});
+        let (sender, receiver) = crate::local::channel::<()>();
+        let waiter = step.spawn(receiver);
+        let notifier = step.spawn(async move { sender.send(()) });
+        assert!(step.step_task(waiter).is_pending());
+        assert!(step.woken_tasks().is_empty());
+        assert!(step.step_task(notifier).is_ready());
+        assert!(step.is_task_finished(notifier));
+        assert_eq!(step.woken_tasks(), [waiter]);
+        assert_eq!(step.task_wake_count(waiter), 1);
+        assert!(step.step_task(waiter).is_ready());
+        assert_eq!(step.step(), Poll::Ready(()));
+    }
+}
@@ -1,4 +1,92 @@
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 pub use std::time;
-#[cfg(target_arch = "wasm32")]
-pub use web_time as time;
\ No newline at end of file
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use web_time as time;
+
+/*
+Threads and the semaphore used by sleep_on and the runtimes.  With the `loom` or `shuttle` features these are
+the model-checked / scheduler-controlled equivalents.
+ */
+#[cfg(all(feature = "loom", feature = "shuttle"))]
+compile_error!("the `loom` and `shuttle` features are mutually exclusive");
+
+#[cfg(not(any(feature = "loom", feature = "shuttle")))]
+pub use std::thread;
+#[cfg(feature = "loom")]
+pub use loom::thread;
+#[cfg(feature = "shuttle")]
+pub use shuttle::thread;
+
+#[cfg(all(not(any(feature = "loom", feature = "shuttle")), not(target_os = "wasi")))]
+use std::sync as model_sync;
+#[cfg(feature = "loom")]
+use loom::sync as model_sync;
+#[cfg(feature = "shuttle")]
+use shuttle::sync as model_sync;
+
+/**
+A binary semaphore built from a Mutex and Condvar (the model's, under loom or shuttle): signals made while nobody
+is waiting are remembered, but only one at a time.
+*/
+#[cfg(not(target_os = "wasi"))]
+#[derive(Debug)]
+pub struct Semaphore {
+    signaled: model_sync::Mutex<bool>,
+    condvar: model_sync::Condvar,
+}
+
+#[cfg(not(target_os = "wasi"))]
+impl Semaphore {
+    pub fn new(initially_signaled: bool) -> Semaphore {
+        Semaphore {
+            signaled: model_sync::Mutex::new(initially_signaled),
+            condvar: model_sync::Condvar::new(),
+        }
+    }
+
+    pub fn signal_if_needed(&self) {
+        let mut signaled = self.signaled.lock().unwrap();
+        if !*signaled {
+            *signaled = true;
+            self.condvar.notify_one();
+        }
+    }
+
+    pub fn wait(&self) {
+        let mut signaled = self.signaled.lock().unwrap();
+        while !*signaled {
+            signaled = self.condvar.wait(signaled).unwrap();
+        }
+        *signaled = false;
+    }
+
+    /**
+    Consumes the signal if there is one, without waiting for it.
+    */
+    pub fn try_wait(&self) -> bool {
+        std::mem::replace(&mut *self.signaled.lock().unwrap(), false)
+    }
+}
+
+/**
+Sleeps the current thread.  Neither loom nor shuttle model time, so under them this only yields.
+*/
+pub fn sleep(duration: time::Duration) {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    thread::sleep(duration);
+    #[cfg(any(feature = "loom", feature = "shuttle"))]
+    {
+        let _ = duration;
+        thread::yield_now();
+    }
+}
+
+/**
+Hints that we are busy-waiting.  Under loom or shuttle this yields, so that other threads can make progress.
+*/
+pub fn spin_loop() {
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    std::hint::spin_loop();
+    #[cfg(any(feature = "loom", feature = "shuttle"))]
+    thread::yield_now();
+}
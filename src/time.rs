@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Timers that work on every executor, natively and in the browser.
+
+[sleep] completes once a duration has passed, [timeout] gives up on a future that takes longer than a
+duration, and [interval] ticks periodically, so test code that waits for time to pass can be written once.  On
+wasm32-unknown-unknown the wakes are scheduled with JavaScript's `setTimeout`; natively, a single timer thread
+shared by every timer sleeps until the earliest deadline and wakes its task.  Like [crate::spawn_on], the native
+timers are not available at runtime on WASI targets, which generally lack threads.
+
+```
+use std::time::Duration;
+test_executors::sleep_on(async {
+    test_executors::time::sleep(Duration::from_millis(5)).await;
+    let result = test_executors::time::timeout(Duration::from_millis(5), test_executors::pend_forever::PendForever).await;
+    assert!(result.is_err());
+});
+```
+*/
+
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use crate::sys::time::Instant;
+use crate::error::TimeoutError;
+
+/**
+Creates a future that completes once `duration` has passed, counting from this call.
+
+A duration too long to represent as an [Instant], such as [Duration::MAX], never passes.
+*/
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep { deadline: Instant::now().checked_add(duration), duration, timer: None }
+}
+
+/**
+The future returned by [sleep].
+*/
+#[must_use = "futures do nothing unless polled"]
+pub struct Sleep {
+    //None if too far off to represent
+    deadline: Option<Instant>,
+    duration: Duration,
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    timer: Option<wasm_bindgen_futures::JsFuture>,
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    timer: Option<crate::timer::Timer>,
+}
+
+impl Sleep {
+    /**
+    When the sleep completes, or `None` if it was too far off to represent and never completes.
+    */
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /**
+    Completes at `deadline` instead.
+    */
+    pub fn reset(&mut self, deadline: Instant) {
+        self.reset_to(Some(deadline));
+    }
+
+    fn reset_to(&mut self, deadline: Option<Instant>) {
+        self.duration = deadline.map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(Instant::now()));
+        self.deadline = deadline;
+        self.timer = None;
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let Some(deadline) = this.deadline else {
+            return Poll::Pending;
+        };
+        if Instant::now() >= deadline {
+            return Poll::Ready(());
+        }
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        {
+            let millis = deadline.saturating_duration_since(Instant::now()).as_millis();
+            let timer = this.timer.get_or_insert_with(|| {
+                wasm_bindgen_futures::JsFuture::from(crate::js::timeout_promise(i32::try_from(millis).unwrap_or(i32::MAX)))
+            });
+            //the timer may round down, or fire before the deadline if it was longer than setTimeout allows
+            if Pin::new(timer).poll(cx).is_ready() {
+                this.timer = None;
+                cx.waker().wake_by_ref();
+            }
+        }
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        match &this.timer {
+            Some(timer) => timer.set_waker(cx.waker()),
+            None => this.timer = Some(crate::timer::Timer::new(deadline, cx.waker().clone())),
+        }
+        Poll::Pending
+    }
+}
+
+impl std::fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sleep").field("duration", &self.duration).finish_non_exhaustive()
+    }
+}
+
+/**
+Awaits `future`, or gives up once `duration` has passed.
+
+Unlike [crate::watchdog], this does not block, so it can be awaited on any executor, including in a wasm test.
+*/
+pub async fn timeout<F: IntoFuture>(duration: Duration, future: F) -> Result<F::Output, TimeoutError> {
+    let mut future = std::pin::pin!(future.into_future());
+    let mut timer = sleep(duration);
+    let start = Instant::now();
+    let mut polls = 0;
+    std::future::poll_fn(|cx| {
+        polls += 1;
+        if let Poll::Ready(val) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(val));
+        }
+        if Pin::new(&mut timer).poll(cx).is_ready() {
+            return Poll::Ready(Err(TimeoutError::new(duration, start.elapsed(), polls)));
+        }
+        Poll::Pending
+    }).await
+}
+
+/**
+Creates an [Interval] that ticks every `period`, starting one `period` from now.
+
+# Panics
+If `period` is zero.
+*/
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::ZERO, "interval period must be nonzero");
+    Interval { period, sleep: sleep(period) }
+}
+
+/**
+Ticks every `period`, as returned by [interval].
+
+Ticks are scheduled at multiples of the period from the start, so they do not drift.  A tick that is missed,
+because the interval was not polled in time, is skipped: the next tick is the next one still ahead.
+*/
+#[derive(Debug)]
+pub struct Interval {
+    period: Duration,
+    sleep: Sleep,
+}
+
+impl Interval {
+    /**
+    Waits for the next tick, returning when it was scheduled.
+    */
+    pub async fn tick(&mut self) -> Instant {
+        std::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /**
+    Polls for the next tick, returning when it was scheduled.
+    */
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let scheduled = self.sleep.deadline().expect("a sleep without a deadline never completes");
+        let now = Instant::now();
+        //None once the ticks run past what an Instant can represent, after which the interval stops
+        let mut next = scheduled.checked_add(self.period);
+        while let Some(at) = next.filter(|at| *at <= now) {
+            next = at.checked_add(self.period);
+        }
+        self.sleep.reset_to(next);
+        Poll::Ready(scheduled)
+    }
+
+    /**
+    The time between ticks.
+    */
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::sys::time::Instant;
+
+    #[test]
+    fn sleeps() {
+        let start = Instant::now();
+        crate::sleep_on(super::sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn times_out() {
+        let result = crate::sleep_on(super::timeout(Duration::from_millis(10), crate::pend_forever::PendForever));
+        let err = result.unwrap_err();
+        assert_eq!(err.polls(), 2);
+        assert_eq!(crate::sleep_on(super::timeout(Duration::from_secs(10), async { 3 })), Ok(3));
+    }
+
+    #[test]
+    fn unrepresentable_duration_never_passes() {
+        let mut sleep = super::sleep(Duration::MAX);
+        assert_eq!(sleep.deadline(), None);
+        assert!(crate::poll_once(std::pin::Pin::new(&mut sleep)).is_pending());
+        assert_eq!(crate::sleep_on(super::timeout(Duration::MAX, async { 3 })), Ok(3));
+    }
+
+    #[test]
+    fn ticks() {
+        let start = Instant::now();
+        let mut interval = super::interval(Duration::from_millis(5));
+        crate::sleep_on(async {
+            let first = interval.tick().await;
+            std::thread::sleep(Duration::from_millis(12));
+            //late, so the tick after it is missed
+            let second = interval.tick().await;
+            let third = interval.tick().await;
+            //exactly 5ms and 15ms unless the machine is loaded, but always on the schedule
+            let on_schedule = |tick: Instant| (tick - first).as_micros() % 5_000 == 0;
+            assert!(second > first && on_schedule(second), "{:?}", second - first);
+            assert!(third - first >= Duration::from_millis(15) && on_schedule(third), "{:?}", third - first);
+        });
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
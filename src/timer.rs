@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+The timer driver behind [crate::time] and [crate::watchdog] on native targets.
+
+A single thread, started by the first timer, keeps the pending deadlines in a binary heap and sleeps until the
+earliest of them, then wakes that timer's task.  This is far cheaper than a thread per timer when a test has many
+timers in flight.  A timer dropped before its deadline is skipped when the deadline comes.
+
+The driver uses std's threads even under `loom` or `shuttle`, which do not model time anyway.
+*/
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, Weak};
+use std::task::Waker;
+use crate::sys::time::Instant;
+
+type Slot = Mutex<Option<Waker>>;
+
+enum Target {
+    //a Timer, which may be dropped first
+    Timer(Weak<Slot>),
+    Waker(Waker),
+}
+
+struct Entry {
+    deadline: Instant,
+    //breaks ties in registration order
+    seq: u64,
+    target: Target,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        (self.deadline, self.seq) == (other.deadline, other.seq)
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.deadline, self.seq).cmp(&(other.deadline, other.seq))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    deadlines: BinaryHeap<Reverse<Entry>>,
+    next_seq: u64,
+}
+
+struct Driver {
+    state: Mutex<State>,
+    //notified when a deadline earlier than all the others is registered
+    condvar: Condvar,
+}
+
+fn driver() -> &'static Driver {
+    static DRIVER: OnceLock<Driver> = OnceLock::new();
+    DRIVER.get_or_init(|| {
+        //the thread waits for this initialization to finish before it can get the driver
+        std::thread::Builder::new()
+            .name("test_executors timer".to_string())
+            .spawn(|| driver().run())
+            .expect("Cant spawn thread");
+        Driver { state: Mutex::new(State::default()), condvar: Condvar::new() }
+    })
+}
+
+impl Driver {
+    fn register(&self, deadline: Instant, target: Target) {
+        let mut state = self.state.lock().unwrap();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.deadlines.push(Reverse(Entry { deadline, seq, target }));
+        if state.deadlines.peek().is_some_and(|Reverse(first)| first.seq == seq) {
+            self.condvar.notify_one();
+        }
+    }
+
+    fn run(&self) -> ! {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            match state.deadlines.peek() {
+                None => state = self.condvar.wait(state).unwrap(),
+                Some(Reverse(first)) if first.deadline > now => {
+                    let delay = first.deadline - now;
+                    state = self.condvar.wait_timeout(state, delay).unwrap().0;
+                }
+                Some(_) => {
+                    let Reverse(entry) = state.deadlines.pop().expect("peeked");
+                    drop(state);
+                    let waker = match entry.target {
+                        Target::Timer(slot) => slot.upgrade().and_then(|slot| slot.lock().unwrap().take()),
+                        Target::Waker(waker) => Some(waker),
+                    };
+                    if let Some(waker) = waker {
+                        crate::logging::trace!("timer fired");
+                        waker.wake();
+                    }
+                    state = self.state.lock().unwrap();
+                }
+            }
+        }
+    }
+}
+
+/**
+Wakes a task once `deadline` has passed, unless dropped first.
+
+The waker is called at most once, and only once `Instant::now() >= deadline`, so a task that finds the deadline
+still ahead after being woken only needs to have been woken spuriously.
+*/
+pub(crate) struct Timer {
+    waker: Arc<Slot>,
+}
+
+impl Timer {
+    pub(crate) fn new(deadline: Instant, waker: Waker) -> Self {
+        let slot = Arc::new(Mutex::new(Some(waker)));
+        driver().register(deadline, Target::Timer(Arc::downgrade(&slot)));
+        Timer { waker: slot }
+    }
+
+    /**
+    Wakes `waker` at the deadline instead of the waker given before.
+    */
+    pub(crate) fn set_waker(&self, waker: &Waker) {
+        let mut slot = self.waker.lock().unwrap();
+        //after the timer has fired it stays empty, since the deadline has passed
+        if let Some(current) = slot.as_mut() {
+            if !current.will_wake(waker) {
+                *current = waker.clone();
+            }
+        }
+    }
+}
+
+/**
+Wakes `waker` once `deadline` has passed.
+*/
+pub(crate) fn wake_at(deadline: Instant, waker: Waker) {
+    driver().register(deadline, Target::Waker(waker));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Wake, Waker};
+    use std::time::Duration;
+    use crate::sys::time::Instant;
+    use super::Timer;
+
+    struct Report(usize, Mutex<mpsc::Sender<usize>>);
+    impl Wake for Report {
+        fn wake(self: Arc<Self>) {
+            self.1.lock().unwrap().send(self.0).unwrap();
+        }
+    }
+
+    #[test]
+    fn fires_in_deadline_order() {
+        let (sender, receiver) = mpsc::channel();
+        let now = Instant::now();
+        let timers: Vec<Timer> = [30, 10, 20].into_iter().enumerate().map(|(id, millis)| {
+            let waker = Waker::from(Arc::new(Report(id, Mutex::new(sender.clone()))));
+            Timer::new(now + Duration::from_millis(millis), waker)
+        }).collect();
+        let dropped = Timer::new(now + Duration::from_millis(5), Waker::from(Arc::new(Report(3, Mutex::new(sender)))));
+        drop(dropped);
+        let fired: Vec<usize> = receiver.iter().collect();
+        assert_eq!(fired, [1, 2, 0]);
+        assert!(now.elapsed() >= Duration::from_millis(30));
+        drop(timers);
+    }
+}
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Seeded fault injection for wakes.
+
+The `Waker` contract leaves an executor a lot of room: it may poll a future that was not woken (a spurious
+wake), deliver a wake late, or coalesce several wakes that arrive before the next poll into one.  Well-behaved
+executors on a quiet test machine rarely do any of these, so a future that depends on being polled exactly once
+per wake, promptly, passes its tests and fails in production.
+
+[with_wake_faults] wraps a future so that, as decided by a seed, its wakes are
+
+* coalesced: dropped, when an earlier wake since the last poll has already been delivered,
+* duplicated: delivered twice,
+* delayed: delivered after a random delay,
+
+and it is sometimes woken spuriously right after returning `Pending`.  None of these breaks a correct future.
+[check_seeds] runs a test under each of a range of seeds, and reports the seed that failed, so that the failure
+can be reproduced with [sleep_on_with_wake_faults]:
+
+```
+test_executors::wake_faults::check_seeds(0..16, || async {
+    test_executors::future_util::yield_now().await;
+});
+```
+*/
+
+use std::future::{Future, IntoFuture};
+use std::ops::Range;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+use crate::rng::Rng;
+
+/**
+Which wake faults [with_wake_faults] injects, and how often.
+
+Each probability is between 0 and 1, and is checked independently for every wake (or, for spurious wakes, every
+`Pending` poll).
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WakeFaults {
+    seed: u64,
+    coalesce: f64,
+    duplicate: f64,
+    delay: f64,
+    max_delay: Duration,
+    spurious: f64,
+}
+
+impl WakeFaults {
+    /**
+    Injects each kind of fault a quarter of the time, with delays of up to 5ms, as decided by `seed`.
+    */
+    pub fn new(seed: u64) -> Self {
+        WakeFaults { seed, coalesce: 0.25, duplicate: 0.25, delay: 0.25, max_delay: Duration::from_millis(5), spurious: 0.25 }
+    }
+
+    /**
+    The probability of dropping a wake when an earlier one has been delivered since the last poll.
+    */
+    pub fn coalesce(mut self, probability: f64) -> Self {
+        self.coalesce = probability;
+        self
+    }
+
+    /**
+    The probability of delivering a wake twice.
+    */
+    pub fn duplicate(mut self, probability: f64) -> Self {
+        self.duplicate = probability;
+        self
+    }
+
+    /**
+    The probability of delivering a wake late, and the longest delay.
+    */
+    pub fn delay(mut self, probability: f64, max_delay: Duration) -> Self {
+        self.delay = probability;
+        self.max_delay = max_delay;
+        self
+    }
+
+    /**
+    The probability of waking the future right after it returns `Pending`, whether or not anything woke it.
+    */
+    pub fn spurious(mut self, probability: f64) -> Self {
+        self.spurious = probability;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+struct Shared {
+    faults: WakeFaults,
+    rng: Mutex<Rng>,
+    outer: Mutex<Waker>,
+    //a wake has been delivered since the last poll, so a later one may be coalesced with it
+    delivered: AtomicBool,
+}
+
+impl Shared {
+    fn chance(&self, probability: f64) -> bool {
+        (self.rng.lock().unwrap().next() as f64 / u64::MAX as f64) < probability
+    }
+
+    fn deliver(&self) {
+        let outer = self.outer.lock().unwrap().clone();
+        if self.chance(self.faults.delay) {
+            let delay = self.rng.lock().unwrap().duration_up_to(self.faults.max_delay);
+            crate::logging::trace!("delaying wake by {delay:?}", delay = delay);
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            crate::timer::wake_at(crate::sys::time::Instant::now() + delay, outer);
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+            wasm_bindgen_futures::spawn_local(async move {
+                crate::time::sleep(delay).await;
+                outer.wake();
+            });
+        } else {
+            outer.wake();
+        }
+    }
+}
+
+struct FaultyWaker(Arc<Shared>);
+
+impl Wake for FaultyWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let shared = &self.0;
+        if shared.delivered.swap(true, Ordering::AcqRel) && shared.chance(shared.faults.coalesce) {
+            crate::logging::trace!("coalescing wake");
+            return;
+        }
+        shared.deliver();
+        if shared.chance(shared.faults.duplicate) {
+            crate::logging::trace!("duplicating wake");
+            shared.deliver();
+        }
+    }
+}
+
+/**
+Wraps `future` so that its wakes are perturbed according to `faults`.
+*/
+pub fn with_wake_faults<F: IntoFuture>(future: F, faults: WakeFaults) -> WithWakeFaults<F::IntoFuture> {
+    WithWakeFaults { future: Box::pin(future.into_future()), faults, waker: None }
+}
+
+/**
+Blocks the calling thread until a future is ready, like [crate::sleep_on], perturbing its wakes according to
+`faults`.
+*/
+pub fn sleep_on_with_wake_faults<F: IntoFuture>(future: F, faults: WakeFaults) -> F::Output {
+    crate::sleep_on(with_wake_faults(future, faults))
+}
+
+/**
+Runs the future made by `test` with [sleep_on_with_wake_faults] once for each seed, with the faults of
+[WakeFaults::new].
+
+# Panics
+If the test panics under any seed, after printing the seed.
+*/
+pub fn check_seeds<T: Fn() -> F, F: IntoFuture>(seeds: Range<u64>, test: T) {
+    for seed in seeds {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(|| sleep_on_with_wake_faults(test(), WakeFaults::new(seed)))) {
+            eprintln!("failed with wake fault seed {seed}; reproduce with WakeFaults::new({seed})");
+            resume_unwind(payload);
+        }
+    }
+}
+
+/**
+The future returned by [with_wake_faults].
+*/
+#[must_use = "futures do nothing unless polled"]
+pub struct WithWakeFaults<F> {
+    future: Pin<Box<F>>,
+    faults: WakeFaults,
+    waker: Option<Arc<FaultyWaker>>,
+}
+
+impl<F: Future> Future for WithWakeFaults<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let faults = this.faults;
+        let waker = this.waker.get_or_insert_with(|| Arc::new(FaultyWaker(Arc::new(Shared {
+            faults,
+            rng: Mutex::new(Rng::new(faults.seed)),
+            outer: Mutex::new(cx.waker().clone()),
+            delivered: AtomicBool::new(false),
+        })))).clone();
+        let shared = &waker.0;
+        {
+            let mut outer = shared.outer.lock().unwrap();
+            if !outer.will_wake(cx.waker()) {
+                *outer = cx.waker().clone();
+            }
+        }
+        shared.delivered.store(false, Ordering::Release);
+        let poll = this.future.as_mut().poll(&mut Context::from_waker(&Waker::from(waker.clone())));
+        if poll.is_pending() && shared.chance(faults.spurious) {
+            crate::logging::trace!("waking spuriously");
+            cx.waker().wake_by_ref();
+        }
+        poll
+    }
+}
+
+impl<F> std::fmt::Debug for WithWakeFaults<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithWakeFaults").field("faults", &self.faults).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::Poll;
+    use std::time::Duration;
+
+    /**
+    Assumes it is only polled again once the thread it started has finished, which spurious wakes break.
+    */
+    fn trusts_its_wakes() -> impl std::future::Future<Output = ()> {
+        let mut done: Option<Arc<AtomicBool>> = None;
+        std::future::poll_fn(move |cx| match &done {
+            None => {
+                let flag = Arc::new(AtomicBool::new(false));
+                let (set, waker) = (flag.clone(), cx.waker().clone());
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(2));
+                    set.store(true, Ordering::Release);
+                    waker.wake();
+                });
+                done = Some(flag);
+                Poll::Pending
+            }
+            Some(flag) => {
+                assert!(flag.load(Ordering::Acquire), "polled before the thread finished");
+                Poll::Ready(())
+            }
+        })
+    }
+
+    #[test]
+    fn correct_future_survives() {
+        super::check_seeds(0..32, || async {
+            crate::time::sleep(Duration::from_millis(1)).await;
+            crate::future_util::yield_now().await;
+        });
+    }
+
+    #[test]
+    fn finds_failing_seed() {
+        assert!(std::panic::catch_unwind(|| super::check_seeds(0..32, trusts_its_wakes)).is_err());
+        //never spurious, so it passes
+        super::sleep_on_with_wake_faults(trusts_its_wakes(), super::WakeFaults::new(0).spurious(0.0));
+    }
+}
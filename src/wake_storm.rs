@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Checking that an executor coalesces wakes.
+
+A waker may be woken many times before its task is next polled, and an executor should poll the task once for all
+of them, not once per wake.  An executor that queues the task for every wake keeps polling it long after the
+wakes have stopped, and a busy future (say, one fed by a channel with many producers) can starve everything else.
+
+[wake_storm] returns a future that, when first polled, starts some threads that call `wake_by_ref` thousands of
+times in a burst, then stays pending for a quiet period after they finish, counting its polls.  Run it on any
+executor, including your own, and check the [WakeStormReport]:
+
+```
+let report = test_executors::sleep_on(test_executors::wake_storm::wake_storm(4, 1_000));
+assert_eq!(report.wakes(), 4_000);
+report.assert_coalesced(2_000);
+```
+
+The bound to check against depends on the executor: a task may legitimately be polled once per wake when it is
+polled faster than it is woken.  What a coalescing executor does not do is poll once per wake after the burst is
+over; [WakeStormReport::polls_after_storm] counts those polls.
+*/
+
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use crate::time::Sleep;
+
+/**
+How long the future stays pending after the last wake, so that an executor with a backlog of wakes polls it.
+*/
+const QUIET_PERIOD: Duration = Duration::from_millis(20);
+
+/**
+How a [wake_storm] was polled.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WakeStormReport {
+    wakes: u64,
+    polls: u64,
+    polls_after_storm: u64,
+}
+
+impl WakeStormReport {
+    /**
+    How many wakes the threads made in total.
+    */
+    pub fn wakes(&self) -> u64 {
+        self.wakes
+    }
+
+    /**
+    How many times the future was polled after its first poll, while the threads were waking it or afterwards.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /**
+    How many of [Self::polls] came after every thread had finished waking.
+
+    The last wake can land after the poll that saw the threads finish, and the quiet period's timer wakes the
+    future too, so a coalescing executor shows a few.
+    */
+    pub fn polls_after_storm(&self) -> u64 {
+        self.polls_after_storm
+    }
+
+    /**
+    # Panics
+    With this report as the message, if the future was polled more than `max_polls` times.
+    */
+    #[track_caller]
+    pub fn assert_coalesced(&self, max_polls: u64) {
+        if self.polls > max_polls {
+            panic!("{self}, more than the expected {max_polls}");
+        }
+    }
+}
+
+impl Display for WakeStormReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} wakes caused {} polls ({} after the wakes stopped)", self.wakes, self.polls, self.polls_after_storm)
+    }
+}
+
+struct Storm {
+    finished: Arc<AtomicBool>,
+    quiet: Option<Sleep>,
+}
+
+impl std::fmt::Debug for Storm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Storm").field("finished", &self.finished).finish_non_exhaustive()
+    }
+}
+
+/**
+The future returned by [wake_storm].
+*/
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct WakeStorm {
+    threads: usize,
+    wakes_per_thread: usize,
+    storm: Option<Storm>,
+    report: WakeStormReport,
+}
+
+/**
+A future that is woken `wakes_per_thread` times from each of `threads` threads, all at once, and reports how often
+it was polled.
+*/
+pub fn wake_storm(threads: usize, wakes_per_thread: usize) -> WakeStorm {
+    WakeStorm {
+        threads,
+        wakes_per_thread,
+        storm: None,
+        report: WakeStormReport { wakes: (threads * wakes_per_thread) as u64, polls: 0, polls_after_storm: 0 },
+    }
+}
+
+impl Future for WakeStorm {
+    type Output = WakeStormReport;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<WakeStormReport> {
+        let this = self.get_mut();
+        let Some(storm) = this.storm.as_mut() else {
+            let finished = Arc::new(AtomicBool::new(false));
+            let remaining = Arc::new(AtomicUsize::new(this.threads));
+            let barrier = Arc::new(Barrier::new(this.threads));
+            for _ in 0..this.threads {
+                let (waker, wakes, barrier) = (cx.waker().clone(), this.wakes_per_thread, barrier.clone());
+                let (finished, remaining) = (finished.clone(), remaining.clone());
+                std::thread::Builder::new()
+                    .name("test_executors wake_storm".to_string())
+                    .spawn(move || {
+                        barrier.wait();
+                        for _ in 0..wakes {
+                            waker.wake_by_ref();
+                        }
+                        if remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                            finished.store(true, Ordering::Release);
+                            //so that the quiet period starts even if every earlier wake was coalesced away
+                            waker.wake();
+                        }
+                    })
+                    .expect("Cant spawn thread");
+            }
+            if this.threads == 0 {
+                finished.store(true, Ordering::Release);
+                cx.waker().wake_by_ref();
+            }
+            this.storm = Some(Storm { finished, quiet: None });
+            return Poll::Pending;
+        };
+        this.report.polls += 1;
+        if !storm.finished.load(Ordering::Acquire) {
+            return Poll::Pending;
+        }
+        this.report.polls_after_storm += 1;
+        let quiet = storm.quiet.get_or_insert_with(|| crate::time::sleep(QUIET_PERIOD));
+        match Pin::new(quiet).poll(cx) {
+            Poll::Ready(()) => {
+                crate::logging::trace!("wake storm over: {report}", report = this.report.to_string());
+                Poll::Ready(this.report)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::task::{Context, Wake, Waker};
+    use super::wake_storm;
+
+    #[test]
+    fn sleep_on_coalesces() {
+        let report = crate::sleep_on(wake_storm(4, 1_000));
+        assert_eq!(report.wakes(), 4_000);
+        assert!(report.polls_after_storm() <= 4, "{report}");
+    }
+
+    /**
+    An executor that polls once per wake, which is what the storm catches.
+    */
+    #[test]
+    fn catches_executor_polling_per_wake() {
+        struct Queue(std::sync::Mutex<mpsc::Sender<()>>);
+        impl Wake for Queue {
+            fn wake(self: Arc<Self>) {
+                let _ = self.0.lock().unwrap().send(());
+            }
+        }
+        let (sender, receiver) = mpsc::channel();
+        let waker = Waker::from(Arc::new(Queue(std::sync::Mutex::new(sender))));
+        let mut storm = std::pin::pin!(wake_storm(2, 500));
+        let report = loop {
+            if let std::task::Poll::Ready(report) = storm.as_mut().poll(&mut Context::from_waker(&waker)) {
+                break report;
+            }
+            receiver.recv().unwrap();
+        };
+        assert!(report.polls() >= 1_000, "{report}");
+        assert!(std::panic::catch_unwind(|| report.assert_coalesced(100)).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Checking how a future handles the wakers it is polled with.
+
+A future must arrange for the waker from its *latest* poll to be woken: an executor, or a combinator like `join`,
+may poll it with a different waker each time.  A future that stores only the first waker it sees works on simple
+executors, where every waker wakes the same task, and breaks inside combinators.  A future that clones the waker
+into a list on every poll, without checking `Waker::will_wake`, works too, but grows the list for as long as it is
+pending.
+
+[check_waker_reuse] drives a future like [crate::sleep_on], polling it with a fresh, distinguishable waker every
+other poll and with the same waker as before in between.  It returns a [WakerReuse] reporting whether the future
+kept (or woke) the waker from each poll, whether it cloned a waker it already held, and whether it woke wakers it
+had been given replacements for.  If the future can no longer be woken at all, it returns a [LostWake] instead, as
+[crate::lost_wake::check_on] does.
+
+```
+use test_executors::waker_reuse::check_waker_reuse;
+let (output, report) = check_waker_reuse(async { 3 }).unwrap();
+assert_eq!(output, 3);
+report.assert_ok();
+```
+*/
+
+use std::fmt::Display;
+use std::future::{Future, IntoFuture};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+use crate::lost_wake::LostWake;
+
+/**
+How often [check_waker_reuse] looks at the wakers while waiting to be woken.
+*/
+const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+How a future handled its wakers, as reported by [check_waker_reuse].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WakerReuse {
+    future: &'static str,
+    polls: u64,
+    unregistered_polls: u64,
+    redundant_clones: u64,
+    stale_wakes: u64,
+}
+
+impl WakerReuse {
+    /**
+    How many times the future was polled.
+    */
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /**
+    Whether, every time it returned `Pending`, the future kept or woke the waker it had just been polled with.
+    */
+    pub fn reregistered(&self) -> bool {
+        self.unregistered_polls == 0
+    }
+
+    /**
+    Whether the future never cloned a waker while it still held a copy that would wake the same task.
+    */
+    pub fn respected_will_wake(&self) -> bool {
+        self.redundant_clones == 0
+    }
+
+    /**
+    How many times the future woke a waker after being polled with a different one.
+    */
+    pub fn stale_wakes(&self) -> u64 {
+        self.stale_wakes
+    }
+
+    /**
+    Whether the future handled its wakers correctly.
+    */
+    pub fn is_ok(&self) -> bool {
+        self.reregistered() && self.respected_will_wake() && self.stale_wakes == 0
+    }
+
+    /**
+    # Panics
+    With this report as the message, unless the future handled its wakers correctly.
+    */
+    #[track_caller]
+    pub fn assert_ok(&self) {
+        if !self.is_ok() {
+            panic!("{self}");
+        }
+    }
+}
+
+impl Display for WakerReuse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_ok() {
+            return write!(f, "`{}` handled its wakers correctly over {} polls", self.future, self.polls);
+        }
+        write!(f, "`{}` mishandled its wakers over {} polls:", self.future, self.polls)?;
+        if self.unregistered_polls > 0 {
+            write!(f, " {} times it returned Pending without keeping or waking the waker it was polled with;", self.unregistered_polls)?;
+        }
+        if self.redundant_clones > 0 {
+            write!(f, " {} times it cloned a waker it already held, rather than checking will_wake;", self.redundant_clones)?;
+        }
+        if self.stale_wakes > 0 {
+            write!(f, " {} times it woke a waker it had since been given a replacement for;", self.stale_wakes)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    //the waker of the latest poll
+    current: usize,
+    woken: bool,
+    stale_wakes: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+struct Probe {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Wake for Probe {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.shared.state.lock().unwrap();
+        if self.id != state.current {
+            crate::logging::trace!("stale waker {id} woken", id = self.id);
+            state.stale_wakes += 1;
+        }
+        state.woken = true;
+        self.shared.condvar.notify_one();
+    }
+}
+
+/**
+Copies of a probe's waker held by the future; we hold the probe itself.
+*/
+fn held(probe: &Arc<Probe>) -> usize {
+    Arc::strong_count(probe) - 1
+}
+
+/**
+Blocks the calling thread until a future is ready, checking how it handles its wakers.
+*/
+#[track_caller]
+pub fn check_waker_reuse<F: IntoFuture>(future: F) -> Result<(F::Output, WakerReuse), LostWake> {
+    let location = core::panic::Location::caller();
+    let _sleeping = crate::SleepingGuard::enter(false, "check_waker_reuse", location);
+    let future_name = core::any::type_name::<F::IntoFuture>();
+    let mut future = core::pin::pin!(future.into_future());
+    let shared = Arc::new(Shared { state: Mutex::new(State::default()), condvar: Condvar::new() });
+    let mut probes: Vec<Arc<Probe>> = Vec::new();
+    let mut report = WakerReuse { future: future_name, polls: 0, unregistered_polls: 0, redundant_clones: 0, stale_wakes: 0 };
+    loop {
+        //polls 2n and 2n + 1 share waker n
+        let id = (report.polls / 2) as usize;
+        if probes.len() == id {
+            probes.push(Arc::new(Probe { id, shared: shared.clone() }));
+        }
+        let probe = &probes[id];
+        shared.state.lock().unwrap().current = id;
+        let held_before = held(probe);
+        let waker = Waker::from(probe.clone());
+        let poll = report.polls;
+        report.polls += 1;
+        let result = future.as_mut().poll(&mut Context::from_waker(&waker));
+        drop(waker);
+        if let Poll::Ready(value) = result {
+            report.stale_wakes = shared.state.lock().unwrap().stale_wakes;
+            return Ok((value, report));
+        }
+        let mut state = shared.state.lock().unwrap();
+        if held(probe) == 0 && !state.woken {
+            report.unregistered_polls += 1;
+        }
+        if held_before > 0 && held(probe) > held_before {
+            report.redundant_clones += 1;
+        }
+        while !state.woken {
+            //a copy is dropped only after a wake through it has set `woken`, so with the lock held this is exact
+            if probes.iter().all(|probe| held(probe) == 0) {
+                return Err(LostWake::new(future_name, poll, location));
+            }
+            state = shared.condvar.wait_timeout(state, CHECK_INTERVAL).unwrap().0;
+        }
+        state.woken = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::task::{Poll, Waker};
+    use std::time::Duration;
+    use super::check_waker_reuse;
+
+    /**
+    Wakes the latest of `wakers` from another thread, shortly.
+    */
+    fn wake_soon(wakers: &Arc<Mutex<Vec<Waker>>>) {
+        let wakers = wakers.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(1));
+            if let Some(waker) = wakers.lock().unwrap().last() {
+                waker.wake_by_ref();
+            }
+        });
+    }
+
+    /**
+    A future that is ready on its fourth poll, storing wakers with `store`.
+    */
+    fn future(store: impl Fn(&mut Vec<Waker>, &Waker)) -> impl std::future::Future<Output = ()> {
+        let wakers = Arc::new(Mutex::new(Vec::new()));
+        let mut polls = 0;
+        std::future::poll_fn(move |cx| {
+            polls += 1;
+            if polls == 4 {
+                return Poll::Ready(());
+            }
+            store(&mut wakers.lock().unwrap(), cx.waker());
+            wake_soon(&wakers);
+            Poll::Pending
+        })
+    }
+
+    #[test]
+    fn accepts_correct_future() {
+        let (_, report) = check_waker_reuse(future(|wakers, waker| match wakers.first_mut() {
+            Some(stored) if stored.will_wake(waker) => {}
+            _ => *wakers = vec![waker.clone()],
+        })).unwrap();
+        assert_eq!(report.polls(), 4);
+        report.assert_ok();
+    }
+
+    #[test]
+    fn reports_first_waker_only() {
+        let (_, report) = check_waker_reuse(future(|wakers, waker| {
+            if wakers.is_empty() {
+                wakers.push(waker.clone());
+            }
+        })).unwrap();
+        assert!(!report.reregistered());
+        assert!(report.stale_wakes() > 0);
+        assert!(report.to_string().contains("without keeping or waking"), "{report}");
+    }
+
+    #[test]
+    fn reports_redundant_clones() {
+        let (_, report) = check_waker_reuse(future(|wakers, waker| wakers.push(waker.clone()))).unwrap();
+        assert!(report.reregistered());
+        assert!(!report.respected_will_wake());
+    }
+}
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Building wakers for tests.
+
+[waker_from_fn] makes a waker that runs a closure when woken, so a test can count wakes, record them, or forward
+them somewhere without writing a `RawWakerVTable` or a `Wake` impl:
+
+```
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+let wakes = Arc::new(AtomicUsize::new(0));
+let counted = wakes.clone();
+let waker = test_executors::wakers::waker_from_fn(move || {
+    counted.fetch_add(1, Ordering::Relaxed);
+});
+waker.wake_by_ref();
+waker.clone().wake();
+assert_eq!(wakes.load(Ordering::Relaxed), 2);
+```
+
+[waker_for_thread] makes a waker that unparks a thread, which is all a hand-rolled `block_on` loop needs:
+
+```
+use std::future::Future;
+use std::task::{Context, Poll};
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = test_executors::wakers::waker_for_thread(std::thread::current());
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        //an unpark before we get here is remembered, so no wake is lost
+        std::thread::park();
+    }
+}
+assert_eq!(block_on(async { 1 + 1 }), 2);
+```
+*/
+
+use std::sync::{Arc, Mutex};
+use std::task::{Wake, Waker};
+use std::thread::Thread;
+
+struct FnWake<F>(Mutex<F>);
+
+impl<F: FnMut() + Send + Sync + 'static> Wake for FnWake<F> {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        (self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))()
+    }
+}
+
+/**
+Creates a waker that calls `f` each time it is woken, through any of its clones.
+
+Wakes from several threads are serialized, so `f` runs one call at a time.  `f` must not wake the same waker
+itself, which would deadlock.
+*/
+pub fn waker_from_fn<F: FnMut() + Send + Sync + 'static>(f: F) -> Waker {
+    Waker::from(Arc::new(FnWake(Mutex::new(f))))
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        crate::logging::trace!("unparking thread");
+        self.0.unpark();
+    }
+}
+
+/**
+Creates a waker that unparks `thread` each time it is woken.
+
+`std::thread::park` can also return spuriously, so a loop that parks must poll again (or otherwise check) after
+every return, as polling would anyway.
+*/
+pub fn waker_for_thread(thread: Thread) -> Waker {
+    Waker::from(Arc::new(Unpark(thread)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::{waker_for_thread, waker_from_fn};
+
+    #[test]
+    fn calls_fn_from_clones_and_threads() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut n = 0;
+        let record = log.clone();
+        let waker = waker_from_fn(move || {
+            n += 1;
+            record.lock().unwrap().push(n);
+        });
+        let clone = waker.clone();
+        std::thread::spawn(move || clone.wake()).join().unwrap();
+        waker.wake_by_ref();
+        assert!(waker.will_wake(&waker.clone()));
+        drop(waker);
+        assert_eq!(*log.lock().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn unparks_thread() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let parked = std::thread::spawn(move || {
+            sender.send(waker_for_thread(std::thread::current())).unwrap();
+            std::thread::park();
+        });
+        receiver.recv().unwrap().wake();
+        parked.join().unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+/*!
+Executors that give up on a future after a timeout, so that a hung test fails with diagnostics instead of
+hanging the test binary.
+
+On native targets, [sleep_on_timeout] drives the future like [crate::sleep_on] while the timer thread shared
+with [crate::time] wakes it once the deadline passes, and [spin_on_timeout] checks the deadline between the polls of [crate::spin_on].  On wasm32-unknown-unknown, where the main thread cannot block, [timeout_js] instead
+races the future against a JavaScript `setTimeout` Promise.
+
+A future that never returns from `poll` cannot be interrupted by either mechanism.
+*/
+
+use std::panic::Location;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use crate::sys::time::Instant;
+use crate::ParkPolicy;
+use crate::error::TimeoutError;
+
+/**
+Blocks the calling thread until a future is ready, or until `timeout` elapses.
+
+# Example
+```
+use std::time::Duration;
+use test_executors::pend_forever::PendForever;
+let result = test_executors::watchdog::sleep_on_timeout(PendForever, Duration::from_millis(10));
+assert!(result.is_err());
+```
+
+# Panics
+Like [crate::sleep_on], if called from inside a future that a blocking executor is already driving on this thread.
+*/
+#[track_caller]
+pub fn sleep_on_timeout<F: Future>(future: F, timeout: Duration) -> Result<F::Output, TimeoutError> {
+    sleep_on_timeout_at(future, timeout, Some(Location::caller()), ParkPolicy::Park)
+}
+
+/**
+[sleep_on_timeout], reporting `location` as where it was called, for callers that know better than
+`#[track_caller]`, and waiting between polls as `policy` says, as [crate::sleep_on_with] does.
+*/
+pub(crate) fn sleep_on_timeout_at<F: Future>(future: F, timeout: Duration, location: Option<&'static Location<'static>>, policy: ParkPolicy) -> Result<F::Output, TimeoutError> {
+    let _sleeping = location.map(|location| crate::SleepingGuard::enter(false, "sleep_on_timeout", location));
+    let _call_site = location.map(|location| crate::call_site::enter("sleep_on_timeout", location));
+    let shared = Arc::new(crate::SimpleWakeShared::new());
+    let waker = Waker::from(shared.clone());
+    let start = Instant::now();
+    //a timeout too long to represent never fires
+    let deadline = start.checked_add(timeout);
+    //dropping the timer dismisses the watchdog
+    #[cfg(not(target_os = "wasi"))]
+    let _watchdog = deadline.map(|deadline| crate::timer::Timer::new(deadline, waker.clone()));
+
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    let mut polls = 0;
+    loop {
+        crate::logging::trace!("polling future");
+        polls += 1;
+        if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
+            crate::logging::trace!("future is ready");
+            return Ok(val);
+        }
+        crate::logging::trace!("future is not ready");
+        //WASI has no timer thread, but sleep_on's wait there returns periodically
+        if start.elapsed() < timeout {
+            wait(&shared, &waker, policy, deadline);
+        }
+        if start.elapsed() >= timeout {
+            return Err(TimeoutError::new(timeout, start.elapsed(), polls).called_at(location));
+        }
+    }
+}
+
+/**
+Waits for `shared` to be woken, as `policy` says, or for the watchdog due at `deadline`, if any, to wake it.
+*/
+fn wait(shared: &crate::SimpleWakeShared, waker: &Waker, policy: ParkPolicy, deadline: Option<Instant>) {
+    match policy {
+        ParkPolicy::Park => shared.wait(),
+        ParkPolicy::SpinThenPark { spins } => {
+            for _ in 0..spins {
+                if shared.try_wait() {
+                    return;
+                }
+                crate::sys::spin_loop();
+            }
+            shared.wait();
+        }
+        ParkPolicy::ParkTimeout(max) => {
+            //a second timer, dismissed like the watchdog once the wait is over, polls again after `max`
+            #[cfg(not(target_os = "wasi"))]
+            let _poll_again = Instant::now().checked_add(max)
+                .filter(|poll_at| deadline.map_or(true, |deadline| *poll_at < deadline))
+                .map(|poll_at| crate::timer::Timer::new(poll_at, waker.clone()));
+            #[cfg(target_os = "wasi")]
+            let _ = (waker, max, deadline);
+            shared.wait();
+        }
+    }
+}
+
+/**
+Busy-polls a future until it is ready, or until `timeout` elapses.
+
+This is the [crate::spin_on] counterpart of [sleep_on_timeout]; the deadline is checked between polls.  It panics
+when nested as [sleep_on_timeout] does.
+*/
+#[track_caller]
+pub fn spin_on_timeout<F: Future>(future: F, timeout: Duration) -> Result<F::Output, TimeoutError> {
+    spin_on_timeout_at(future, timeout, Some(Location::caller()))
+}
+
+/**
+[spin_on_timeout], reporting `location` as where it was called.
+*/
+pub(crate) fn spin_on_timeout_at<F: Future>(future: F, timeout: Duration, location: Option<&'static Location<'static>>) -> Result<F::Output, TimeoutError> {
+    let _sleeping = location.map(|location| crate::SleepingGuard::enter(false, "spin_on_timeout", location));
+    let _call_site = location.map(|location| crate::call_site::enter("spin_on_timeout", location));
+    let waker = crate::noop_waker::noop_waker();
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    let start = Instant::now();
+    let mut polls = 0;
+    loop {
+        polls += 1;
+        if let Poll::Ready(val) = future.as_mut().poll(&mut context) {
+            return Ok(val);
+        }
+        if start.elapsed() >= timeout {
+            return Err(TimeoutError::new(timeout, start.elapsed(), polls).called_at(location));
+        }
+        crate::sys::spin_loop();
+    }
+}
+
+/**
+Awaits a future, or gives up once a JavaScript `setTimeout` of `timeout` fires.
+
+This is the wasm32-unknown-unknown counterpart of [sleep_on_timeout].
+*/
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub async fn timeout_js<F: Future>(future: F, timeout: Duration) -> Result<F::Output, TimeoutError> {
+    let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+    let mut timer = wasm_bindgen_futures::JsFuture::from(crate::js::timeout_promise(millis));
+    let mut future = std::pin::pin!(future);
+    let start = Instant::now();
+    let mut polls = 0;
+    std::future::poll_fn(|cx| {
+        polls += 1;
+        if let Poll::Ready(val) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(val));
+        }
+        if std::pin::Pin::new(&mut timer).poll(cx).is_ready() {
+            return Poll::Ready(Err(TimeoutError::new(timeout, start.elapsed(), polls)));
+        }
+        Poll::Pending
+    }).await
+}
+
+#[cfg(test)] mod tests {
+    use std::time::Duration;
+
+    #[test] fn ready_future_completes() {
+        let result = super::sleep_on_timeout(async { 3 }, Duration::from_secs(10));
+        assert_eq!(result, Ok(3));
+    }
+
+    #[test] fn unrepresentable_timeout_never_fires() {
+        assert_eq!(super::sleep_on_timeout(crate::future_util::yield_now(), Duration::MAX), Ok(()));
+        assert_eq!(super::spin_on_timeout(crate::future_util::yield_now(), Duration::MAX), Ok(()));
+    }
+
+    #[test] fn spin_pending_future_times_out() {
+        let err = super::spin_on_timeout(crate::pend_forever::PendForever, Duration::from_millis(10)).unwrap_err();
+        assert!(err.polls() > 1);
+    }
+
+    #[test] fn pending_future_times_out() {
+        let err = super::sleep_on_timeout(crate::pend_forever::PendForever, Duration::from_millis(10)).unwrap_err();
+        assert_eq!(err.polls(), 1);
+        assert!(err.elapsed() >= Duration::from_millis(10));
+        assert_eq!(err.location().map(|location| location.line()), Some(line!() - 3));
+        assert!(err.to_string().ends_with(&format!("; waited for at {}", err.location().unwrap())), "{err}");
+    }
+}
@@ -2,16 +2,97 @@
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use quote::{quote, format_ident};
+use quote::{quote, format_ident, ToTokens};
 use syn::{parse_macro_input, ItemFn};
 
 /**
 A procedural macro that converts an async function into a test function.
 
-On most platforms, the test function generates a stub function that uses the sleep_on runtime.
+On most platforms, the test function generates a stub function that uses the sleep_on runtime.  The stub has the
+same name as the async fn, so `cargo test <name>` and test reports refer to it as written.
 
-On wasm32 targets, this macro is equivalent to `#[wasm_bindgen_test::wasm_bindgen_test]`. This is because
-it is generally not allowed to block the main thread in a browser environment.
+On wasm32-unknown-unknown targets, this macro is equivalent to `#[wasm_bindgen_test::wasm_bindgen_test]`. This is because
+it is generally not allowed to block the main thread in a browser environment.  With test_executors'
+`console-panic-hook` feature, the test first installs `console_error_panic_hook`, so that panics are logged
+readably.
+
+On WASI targets (`wasm32-wasip1`, `wasm32-wasip2`), tests run under the standard test harness (e.g. via wasmtime),
+so the native expansion is used.
+
+Like synchronous tests, async tests may return a `Result<(), E>` where `E: Debug`, so the body can use `?`.
+
+Attributes written on the async fn are forwarded to the generated test, so `#[should_panic]`, `#[ignore]` and
+third-party test attributes work as expected.  `#[cfg]`, `#[cfg_attr]` and lint attributes apply to both the
+test and the async fn; doc comments stay on the async fn.  (On wasm32-unknown-unknown, wasm-bindgen-test's own
+support for these attributes is used.)
+
+# Arguments
+
+* `timeout_ms = N`: fail the test if it does not complete within N milliseconds, rather than hanging the test
+  binary.  Natively this uses `test_executors::watchdog`; on wasm32-unknown-unknown the body is
+  raced against a `setTimeout` Promise.
+* `timeout = "none"`: let the test run for as long as it takes.  Otherwise, tests without `timeout_ms` fail after
+  a default of 60 seconds natively, which the `TEST_EXECUTORS_DEFAULT_TIMEOUT` environment variable overrides with
+  a number of seconds, a number followed by `s` or `ms`, or `none`.  (On wasm32-unknown-unknown only `timeout_ms`
+  applies, and `chaos_seeds` tests have no timeout.)
+* `executor = "spin" | "sleep" | "spawn"`: how the test is driven natively.  `"sleep"` uses `sleep_on`, `"spin"`
+  uses `spin_on`, and `"spawn"` runs the body with `sleep_on` on a new thread (which requires a `Send` future).
+  Without it, the test is driven by the strategy `test_executors::blocking::default_executor` picks, `sleep_on`
+  unless the `TEST_EXECUTOR` environment variable says otherwise.  On wasm32-unknown-unknown this is ignored.
+* `repeat = N`: run the body N times (at least 1) in one test, for shaking out races.  Every iteration must pass,
+  and the failing iteration is reported.
+* `retries = N`: rerun a failed body up to N more times before failing the test, for known-flaky
+  integration-style tests.  Each failed attempt is reported.
+* `detect_flaky = N`: run the body N times (at least 1) even if some runs fail, report each failure and the pass
+  rate, and fail the test if any run failed, for burning in concurrent code.  With `chaos_seeds = M`, run `i` uses
+  chaos seed `i % M`.  Cannot be combined with `repeat` or `retries`.
+
+* `matrix`: expand into one test per polling strategy, in a module named after the async fn: `<name>::spin`
+  (`spin_on`), `<name>::sleep` (`sleep_on`) and `<name>::spawn_runtime` (a task on `SpawnRuntime`, which requires
+  a `Send` future).  The async fn moves into that module, so relative `super::` paths in its body gain a level.
+  On wasm32-unknown-unknown a single test is generated.
+
+* `global_runtime` or `global_runtime = "spin" | "sleep" | "spawn"`: make a runtime from `test_executors::aruntime`
+  (`SpawnRuntime` by default) available to code that spawns through some_executor.  It is installed as the
+  global executor if none has been set yet (some_executor's global executor can only be set once per process, so
+  it is not restored afterwards), and as the test thread's executor for the duration of the test.
+
+* `serial = "group"`: run the test while holding a process-wide lock named `group`, so that tests of the same
+  group run one at a time even when libtest runs tests in parallel, as tests sharing the global runtime or other
+  process-global state must.  Time spent waiting for the lock does not count towards the timeout.
+
+* `setup = path`: an async fn run before the body, whose output is passed to the async fn as its only argument.
+  Each iteration (see `repeat` and `retries`) gets a fresh value.
+* `teardown = path`: an async fn taking no arguments, run after the body even if the body panics or returns an
+  `Err`.  The test still fails afterwards.  (On wasm32-unknown-unknown, panics abort, so teardown only runs
+  when the body returns.)
+
+* `chaos_seeds = N`: run the body under `test_executors::deterministic::DeterministicRuntime` once for each of
+  the seeds `0..N`, so that tasks it spawns with `test_executors::deterministic::spawn` run in N different
+  orders.  A failing seed is reported, and can be reproduced with `DeterministicRuntime::new(seed)`, or by
+  running the test with `TEST_EXECUTORS_CHAOS_SEED=<seed>`, which replaces the N seeds.  Cannot be
+  combined with `executor`, `matrix` or `timeout_ms`.  On wasm32-unknown-unknown the body instead runs once
+  on wasm-bindgen-test's executor, where `deterministic::spawn` is unavailable.
+* `wasm(run_in = "browser" | "dedicated_worker" | "shared_worker" | "service_worker" | "node")`: the environment
+  wasm-bindgen-test runs the test in, as with `wasm_bindgen_test_configure!`.  wasm-bindgen-test chooses one
+  environment per test binary, so tests that need different environments belong in different integration test
+  files under `tests/`, and must not be mixed with a crate-wide `wasm_bindgen_test_configure!`.  `"node"` is
+  wasm-bindgen-test's default and emits no configuration.  Natively this is ignored.
+* `wasm_env = "node" | "browser"`: run the test only when the wasm tests are running in Node, or only when they
+  are not (in a browser or worker).  In the other environment the test logs that it was skipped and passes, so
+  one suite can hold tests for both.  Which environment wasm-bindgen-test uses is still chosen by
+  `wasm(run_in = ...)` or `wasm_bindgen_test_configure!`.  Natively this is ignored.
+
+Parameterized tests are written with one `#[case(args...)]` attribute per case, like rstest.  Each case becomes
+its own test in a module named after the async fn: `<name>::case_1`, `<name>::case_2`, and so on, or
+`<name>::case_1_<label>` for `#[case::label(...)]`.  The arguments are evaluated for each run.  Cases cannot be
+combined with `matrix` or `setup`.
+
+`repeat`, `retries`, `detect_flaky`, `serial` and `global_runtime` apply natively; on wasm32-unknown-unknown the body runs once.
+
+Natively, with test_executors' `logwise` feature, the body runs inside a logwise context named after the test and
+where it is defined, such as `hello_world (tests/example.rs:4)`, whichever executor drives it, so the logs of tests
+running in parallel can be told apart.
 
 # Example
 ```rust
@@ -21,45 +102,682 @@ use test_executors::async_test;
 async fn hello_world() {
     assert_eq!(1 + 1, 2);
 }
+
+#[async_test(timeout_ms = 500)]
+async fn finishes_quickly() {
+    assert_eq!(2 + 2, 4);
+}
 ```
 */
 #[proc_macro_attribute]
-pub fn async_test(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn async_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = AsyncTestArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(item as ItemFn);
 
+    // Attributes like `#[should_panic]` or `#[ignore]` are meaningless on the async fn, so they move to the generated test
+    let mut test_attrs = Vec::new();
+    let mut inner_attrs = Vec::new();
+    let mut cases = Vec::new();
+    for attr in &input.attrs {
+        if attr.path().segments.first().is_some_and(|s| s.ident == "case") {
+            match parse_case(attr, cases.len() + 1) {
+                Ok(case) => cases.push(case),
+                Err(e) => return e.to_compile_error().into(),
+            }
+            continue;
+        }
+        match attribute_placement(attr) {
+            Placement::Test => test_attrs.push(attr.clone()),
+            Placement::Inner => inner_attrs.push(attr.clone()),
+            Placement::Both => {
+                test_attrs.push(attr.clone());
+                inner_attrs.push(attr.clone());
+            }
+        }
+    }
+    if !cases.is_empty() {
+        let conflict = if args.matrix { Some("matrix") } else if args.setup.is_some() { Some("setup") } else { None };
+        if let Some(conflict) = conflict {
+            let message = format!("`#[case]` cannot be combined with `{conflict}`");
+            return syn::Error::new_spanned(&input.sig.ident, message).to_compile_error().into();
+        }
+    }
+    let mut inner = input.clone();
+    inner.attrs = inner_attrs;
+
     // Extract the async function's name
     let fn_name = &input.sig.ident;
-
-    // Generate a new name for the test function by prefixing "async_test_"
-    let test_fn_name = format_ident!("async_test_{}", fn_name);
+    // The generated test returns whatever the async fn does, so `-> Result<(), E>` works as it does for sync tests
+    let output_type = &input.sig.output;
 
     // Generate output for non-WASM targets (e.g., using `test_executors::sleep_on`)
-    let non_wasm_output = quote! {
-        // Original async function (not compiled into the test)
-        #input
+    let timeout = match &args.timeout_ms {
+        Some(timeout_ms) => quote! { ::test_executors::macro_support::Timeout::After(::std::time::Duration::from_millis(#timeout_ms)) },
+        None if args.no_timeout => quote! { ::test_executors::macro_support::Timeout::Never },
+        None => quote! { ::test_executors::macro_support::Timeout::Default },
+    };
+    let repeat = match &args.repeat {
+        Some(repeat) => quote! { #repeat },
+        None => quote! { 1 },
+    };
+    let retries = match &args.retries {
+        Some(retries) => quote! { #retries },
+        None => quote! { 0 },
+    };
+    let global_runtime = match &args.global_runtime {
+        Some(runtime) => quote! { ::std::option::Option::Some(::test_executors::macro_support::Runtime::#runtime) },
+        None => quote! { ::std::option::Option::None },
+    };
+    let serial = match &args.serial {
+        Some(group) => quote! { ::std::option::Option::Some(#group) },
+        None => quote! { ::std::option::Option::None },
+    };
+    let detect_flaky = match &args.detect_flaky {
+        Some(runs) => quote! { #runs },
+        None => quote! { 0 },
+    };
+    let chaos_seeds = match &args.chaos_seeds {
+        Some(seeds) => quote! { #seeds },
+        None => quote! { 0 },
+    };
+    // An expression for one run of the body, given the call of the async fn, including setup and teardown
+    let call = match &args.setup {
+        Some(setup) => quote! { #fn_name(#setup().await) },
+        None => quote! { #fn_name() },
+    };
+    let body = |call: &dyn ToTokens| match (&args.setup, &args.teardown) {
+        (None, None) => quote! { #call },
+        (_, Some(teardown)) => quote! {
+            async move { ::test_executors::macro_support::teardown_after(#call, #teardown()).await }
+        },
+        (Some(_), None) => quote! { async move { #call.await } },
+    };
+    let run = |executor: syn::Ident, body: &dyn ToTokens| quote! {
+        ::test_executors::macro_support::Test {
+            name: stringify!(#fn_name),
+            context: concat!(stringify!(#fn_name), " (", file!(), ":", line!(), ")"),
+            location: ::core::panic::Location::caller(),
+            timeout: #timeout,
+            repeat: #repeat,
+            retries: #retries,
+            global_runtime: #global_runtime,
+            chaos_seeds: #chaos_seeds,
+            detect_flaky: #detect_flaky,
+            serial: #serial,
+        }.#executor(|| #body)
+    };
+    let skip_env = args.wasm_env.as_ref().map(|env| quote! {
+        if ::test_executors::macro_support::skip_wasm_env(stringify!(#fn_name), ::test_executors::macro_support::WasmEnv::#env) {
+            return ::test_executors::macro_support::TestOutcome::passed();
+        }
+    });
+    let panic_hook = quote! {
+        ::test_executors::macro_support::install_panic_hook();
+        #skip_env
+    };
+    let run_js = |body: &dyn ToTokens| match &args.timeout_ms {
+        Some(timeout_ms) => quote! {
+            #panic_hook
+            ::test_executors::watchdog::timeout_js(#body, ::std::time::Duration::from_millis(#timeout_ms)).await
+                .unwrap_or_else(|e| panic!("async test `{}` timed out: {}", stringify!(#fn_name), e))
+        },
+        None => quote! {
+            #panic_hook
+            #body.await
+        },
+    };
+    let native = quote! { #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))] };
+    let wasm = quote! { #[cfg(all(target_arch = "wasm32", target_os = "unknown"))] };
+    // The generated test takes the async fn's name, and the async fn is nested inside it (where it shadows the
+    // test), so that `cargo test <name>` filters and test reports use the user's name.
+    let non_wasm_output = if !cases.is_empty() {
+        // One test per case, grouped in a module so that `cargo test <name>` selects all of them
+        let tests = cases.iter().map(|(case, exprs)| {
+            let run = run(args.executor(), &body(&quote! { #fn_name(#exprs) }));
+            quote! {
+                #[test]
+                #(#test_attrs)*
+                fn #case() #output_type {
+                    #run
+                }
+            }
+        });
+        quote! {
+            #native
+            #[cfg(test)]
+            mod #fn_name {
+                #[allow(unused_imports)]
+                use super::*;
 
-        // Generated synchronous test function with a new name
-        #[test]
-        fn #test_fn_name() {
-            ::test_executors::sleep_on(#fn_name())
+                #inner
+
+                #(#tests)*
+            }
+        }
+    } else if args.matrix {
+        // One test per executor, grouped in a module so that `cargo test <name>` selects all of them
+        let tests = ["spin", "sleep", "spawn_runtime"].map(|name| {
+            let executor = format_ident!("{}", name);
+            let run = run(executor.clone(), &body(&call));
+            quote! {
+                #[test]
+                #(#test_attrs)*
+                fn #executor() #output_type {
+                    #run
+                }
+            }
+        });
+        quote! {
+            #native
+            #[cfg(test)]
+            mod #fn_name {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #inner
+
+                #(#tests)*
+            }
+        }
+    } else {
+        let run = run(args.executor(), &body(&call));
+        quote! {
+            #native
+            #[test]
+            #(#test_attrs)*
+            fn #fn_name() #output_type {
+                #inner
+
+                #run
+            }
         }
     };
 
-    // Generate output for wasm32 targets (use `wasm_bindgen_test`)
-    let wasm_output = quote! {
-        #[::wasm_bindgen_test::wasm_bindgen_test]
-        #input
+    // Generate output for wasm32-unknown-unknown targets (use `wasm_bindgen_test`)
+    let wasm_output = if !cases.is_empty() {
+        let tests = cases.iter().map(|(case, exprs)| {
+            let run = run_js(&body(&quote! { #fn_name(#exprs) }));
+            quote! {
+                #[::wasm_bindgen_test::wasm_bindgen_test]
+                #(#test_attrs)*
+                async fn #case() #output_type {
+                    #run
+                }
+            }
+        });
+        quote! {
+            #wasm
+            #[cfg(test)]
+            mod #fn_name {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #inner
+
+                #(#tests)*
+            }
+        }
+    } else if args.timeout_ms.is_some() || args.has_fixture() {
+        let run = run_js(&body(&call));
+        quote! {
+            #wasm
+            #[::wasm_bindgen_test::wasm_bindgen_test]
+            #(#test_attrs)*
+            async fn #fn_name() #output_type {
+                #inner
+
+                #run
+            }
+        }
+    } else {
+        let mut input = input.clone();
+        let prelude: syn::Block = syn::parse_quote! { { #panic_hook } };
+        input.block.stmts.splice(0..0, prelude.stmts);
+        quote! {
+            #wasm
+            #[::wasm_bindgen_test::wasm_bindgen_test]
+            #input
+        }
     };
 
-    // Use `cfg` attributes to conditionally compile the correct output
+    // wasm-bindgen-test reads its configuration from a custom section; the anonymous const keeps the static it
+    // defines from colliding with other tests that use the same environment
+    let wasm_config = args.wasm_run_in.as_ref().map(|run_in| quote! {
+        #wasm
+        const _: () = {
+            ::wasm_bindgen_test::wasm_bindgen_test_configure!(#run_in);
+        };
+    });
+
     let output = quote! {
-        #[cfg(target_arch = "wasm32")]
+        #wasm_config
         #wasm_output
-
-        #[cfg(not(target_arch = "wasm32"))]
         #non_wasm_output
     };
 
     TokenStream::from(output)
-}
\ No newline at end of file
+}
+
+/**
+Parses `#[case(args...)]` or `#[case::name(args...)]` into the generated test's name and the arguments.
+*/
+fn parse_case(attr: &syn::Attribute, index: usize) -> syn::Result<(syn::Ident, syn::punctuated::Punctuated<syn::Expr, syn::Token![,]>)> {
+    let segments = &attr.path().segments;
+    let name = match segments.len() {
+        1 => format_ident!("case_{}", index),
+        2 => format_ident!("case_{}_{}", index, segments[1].ident),
+        _ => return Err(syn::Error::new_spanned(attr, "expected `#[case(...)]` or `#[case::name(...)]`")),
+    };
+    let exprs = attr.parse_args_with(syn::punctuated::Punctuated::parse_terminated)?;
+    Ok((name, exprs))
+}
+
+/**
+Where an attribute written on the async fn ends up in the expansion.
+*/
+enum Placement {
+    /// Only on the generated test function, e.g. `#[should_panic]`, `#[ignore]` or third-party test attributes.
+    Test,
+    /// Only on the async fn that holds the body, e.g. doc comments.
+    Inner,
+    /// On both, e.g. `#[cfg]`, which must remove both functions, and lint levels, which must cover the body.
+    Both,
+}
+
+fn attribute_placement(attr: &syn::Attribute) -> Placement {
+    const BOTH: &[&str] = &["cfg", "cfg_attr", "allow", "warn", "deny", "forbid", "expect"];
+    let path = attr.path();
+    if path.is_ident("doc") {
+        Placement::Inner
+    } else if BOTH.iter().any(|name| path.is_ident(name)) {
+        Placement::Both
+    } else {
+        Placement::Test
+    }
+}
+
+/**
+Arguments accepted by [macro@async_test].
+*/
+#[derive(Default)]
+struct AsyncTestArgs {
+    timeout_ms: Option<syn::LitInt>,
+    /// `timeout = "none"`, which turns off the default timeout.
+    no_timeout: bool,
+    executor: Option<syn::Ident>,
+    repeat: Option<syn::LitInt>,
+    retries: Option<syn::LitInt>,
+    matrix: bool,
+    global_runtime: Option<syn::Ident>,
+    setup: Option<syn::Path>,
+    teardown: Option<syn::Path>,
+    /// The `wasm_bindgen_test_configure!` option from `wasm(run_in = "...")`, if any.
+    wasm_run_in: Option<syn::Ident>,
+    /// The `test_executors::macro_support::WasmEnv` variant from `wasm_env = "..."`, if any.
+    wasm_env: Option<syn::Ident>,
+    chaos_seeds: Option<syn::LitInt>,
+    detect_flaky: Option<syn::LitInt>,
+    serial: Option<syn::LitStr>,
+}
+
+impl AsyncTestArgs {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("timeout_ms") {
+            if self.chaos_seeds.is_some() {
+                return Err(meta.error("`chaos_seeds` cannot be combined with `timeout_ms`"));
+            }
+            if self.no_timeout {
+                return Err(meta.error("`timeout_ms` cannot be combined with `timeout = \"none\"`"));
+            }
+            self.timeout_ms = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("timeout") {
+            let value: syn::LitStr = meta.value()?.parse()?;
+            if value.value() != "none" {
+                return Err(syn::Error::new(value.span(), "expected \"none\"; use `timeout_ms = N` for a timeout"));
+            }
+            if self.timeout_ms.is_some() {
+                return Err(syn::Error::new(value.span(), "`timeout_ms` cannot be combined with `timeout = \"none\"`"));
+            }
+            self.no_timeout = true;
+            Ok(())
+        } else if meta.path.is_ident("repeat") {
+            if self.detect_flaky.is_some() {
+                return Err(meta.error("`detect_flaky` cannot be combined with `repeat`"));
+            }
+            let repeat: syn::LitInt = meta.value()?.parse()?;
+            if repeat.base10_parse::<u32>()? == 0 {
+                return Err(syn::Error::new(repeat.span(), "`repeat` needs at least one iteration"));
+            }
+            self.repeat = Some(repeat);
+            Ok(())
+        } else if meta.path.is_ident("retries") {
+            if self.detect_flaky.is_some() {
+                return Err(meta.error("`detect_flaky` cannot be combined with `retries`"));
+            }
+            self.retries = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("serial") {
+            self.serial = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("detect_flaky") {
+            if self.repeat.is_some() || self.retries.is_some() {
+                return Err(meta.error("`detect_flaky` cannot be combined with `repeat` or `retries`"));
+            }
+            let runs: syn::LitInt = meta.value()?.parse()?;
+            if runs.base10_parse::<u32>()? == 0 {
+                return Err(syn::Error::new(runs.span(), "`detect_flaky` needs at least one run"));
+            }
+            self.detect_flaky = Some(runs);
+            Ok(())
+        } else if meta.path.is_ident("global_runtime") {
+            let runtime = if meta.input.peek(syn::Token![=]) {
+                let name: syn::LitStr = meta.value()?.parse()?;
+                match name.value().as_str() {
+                    "spin" => format_ident!("Spin", span = name.span()),
+                    "sleep" => format_ident!("Sleep", span = name.span()),
+                    "spawn" => format_ident!("Spawn", span = name.span()),
+                    _ => return Err(syn::Error::new(name.span(), "expected \"spin\", \"sleep\" or \"spawn\"")),
+                }
+            } else {
+                format_ident!("Spawn")
+            };
+            self.global_runtime = Some(runtime);
+            Ok(())
+        } else if meta.path.is_ident("setup") {
+            self.setup = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("teardown") {
+            self.teardown = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("wasm") {
+            meta.parse_nested_meta(|meta| {
+                if meta.path.is_ident("run_in") {
+                    let name: syn::LitStr = meta.value()?.parse()?;
+                    let option = match name.value().as_str() {
+                        "browser" => "run_in_browser",
+                        "dedicated_worker" => "run_in_dedicated_worker",
+                        "shared_worker" => "run_in_shared_worker",
+                        "service_worker" => "run_in_service_worker",
+                        //node is what wasm-bindgen-test uses when nothing is configured
+                        "node" => {
+                            self.wasm_run_in = None;
+                            return Ok(());
+                        }
+                        _ => return Err(syn::Error::new(name.span(),
+                            "expected \"browser\", \"dedicated_worker\", \"shared_worker\", \"service_worker\" or \"node\"")),
+                    };
+                    self.wasm_run_in = Some(syn::Ident::new(option, name.span()));
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported wasm argument"))
+                }
+            })
+        } else if meta.path.is_ident("wasm_env") {
+            let name: syn::LitStr = meta.value()?.parse()?;
+            let env = match name.value().as_str() {
+                "node" => "Node",
+                "browser" => "Browser",
+                _ => return Err(syn::Error::new(name.span(), "expected \"node\" or \"browser\"")),
+            };
+            self.wasm_env = Some(syn::Ident::new(env, name.span()));
+            Ok(())
+        } else if meta.path.is_ident("chaos_seeds") {
+            if self.matrix || self.executor.is_some() || self.timeout_ms.is_some() {
+                return Err(meta.error("`chaos_seeds` runs the test on DeterministicRuntime and cannot be combined with `matrix`, `executor` or `timeout_ms`"));
+            }
+            self.chaos_seeds = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("matrix") {
+            if self.chaos_seeds.is_some() {
+                return Err(meta.error("`chaos_seeds` runs the test on DeterministicRuntime and cannot be combined with `matrix`"));
+            }
+            if self.executor.is_some() {
+                return Err(meta.error("`matrix` runs every executor and cannot be combined with `executor`"));
+            }
+            self.matrix = true;
+            Ok(())
+        } else if meta.path.is_ident("executor") {
+            if self.chaos_seeds.is_some() {
+                return Err(meta.error("`chaos_seeds` runs the test on DeterministicRuntime and cannot be combined with `executor`"));
+            }
+            if self.matrix {
+                return Err(meta.error("`matrix` runs every executor and cannot be combined with `executor`"));
+            }
+            let name: syn::LitStr = meta.value()?.parse()?;
+            match name.value().as_str() {
+                "spin" | "sleep" | "spawn" => {
+                    self.executor = Some(syn::Ident::new(&name.value(), name.span()));
+                    Ok(())
+                }
+                _ => Err(syn::Error::new(name.span(), "expected \"spin\", \"sleep\" or \"spawn\"")),
+            }
+        } else {
+            Err(meta.error("unsupported async_test argument"))
+        }
+    }
+
+    /**
+    Whether the body is wrapped by `setup` or `teardown`.
+    */
+    fn has_fixture(&self) -> bool {
+        self.setup.is_some() || self.teardown.is_some()
+    }
+
+    /**
+    The `test_executors::macro_support::Test` method that drives the test natively.
+    */
+    fn executor(&self) -> syn::Ident {
+        if self.chaos_seeds.is_some() {
+            return format_ident!("chaos");
+        }
+        self.executor.clone().unwrap_or_else(|| format_ident!("configured"))
+    }
+}
+
+/**
+A procedural macro that turns an async `main` into a sync one.
+
+Natively, the generated `main` drives the async fn to completion with `test_executors::sleep_on`, and returns
+its output, so `-> Result<(), E>` works as it does for a sync `main`.
+
+On wasm32-unknown-unknown, blocking is not allowed, so the async fn is started with `test_executors::spawn_local`
+and `main` returns immediately.  An `Err` returned later is reported as a panic.
+
+# Example
+```rust,ignore
+#[test_executors::async_main]
+async fn main() {
+    println!("{}", async { "hello world" }.await);
+}
+```
+*/
+#[proc_macro_attribute]
+pub fn async_main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let parser = syn::meta::parser(|meta| Err(meta.error("async_main takes no arguments")));
+    parse_macro_input!(attr with parser);
+
+    let mut input = parse_macro_input!(item as ItemFn);
+    if input.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(&input.sig, "async_main functions must be async").to_compile_error().into();
+    }
+    let fn_name = input.sig.ident.clone();
+    let output_type = input.sig.output.clone();
+    // Attributes on the original function belong on the generated sync fn
+    let attrs = std::mem::take(&mut input.attrs);
+
+    let output = quote! {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        #(#attrs)*
+        fn #fn_name() #output_type {
+            #input
+
+            ::test_executors::sleep_on(#fn_name())
+        }
+
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        #(#attrs)*
+        fn #fn_name() {
+            #input
+
+            ::test_executors::spawn_local(async {
+                ::test_executors::macro_support::main_returned(#fn_name().await)
+            })
+        }
+    };
+    TokenStream::from(output)
+}
+
+/**
+A procedural macro that turns an async function into a micro-benchmark driven by `test_executors::spin_on`.
+
+The generated sync function of the same name runs the body for a number of warmup iterations, then measures a
+number of iterations with `test_executors::bench::Bench`, and prints the mean time per iteration and the mean
+and percentile poll latency.
+
+In test builds (including `cargo bench` with the default harness) the function is a `#[test]`.  In a
+`harness = false` target it is an ordinary function, to be called from e.g. a libtest-mimic `Trial`.
+
+# Arguments
+
+* `iterations = N`: the number of measured iterations (default `test_executors::bench::DEFAULT_ITERATIONS`).
+* `warmup = N`: the number of unmeasured iterations run first (default `test_executors::bench::DEFAULT_WARMUP`).
+
+# Example
+```rust,ignore
+use test_executors::async_bench;
+
+#[async_bench(iterations = 1000, warmup = 100)]
+async fn ready_future() {
+    std::hint::black_box(async { 1 + 1 }.await);
+}
+```
+*/
+#[proc_macro_attribute]
+pub fn async_bench(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut iterations: Option<syn::LitInt> = None;
+    let mut warmup: Option<syn::LitInt> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("iterations") {
+            iterations = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("warmup") {
+            warmup = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported async_bench argument"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let mut input = parse_macro_input!(item as ItemFn);
+    let fn_name = input.sig.ident.clone();
+    // Attributes on the original function belong on the generated benchmark
+    let attrs = std::mem::take(&mut input.attrs);
+    let iterations = iterations.map(|iterations| quote! { .iterations(#iterations) });
+    let warmup = warmup.map(|warmup| quote! { .warmup(#warmup) });
+
+    let output = quote! {
+        #[cfg_attr(all(test, not(all(target_arch = "wasm32", target_os = "unknown"))), test)]
+        #[cfg_attr(all(test, target_arch = "wasm32", target_os = "unknown"), ::wasm_bindgen_test::wasm_bindgen_test)]
+        #(#attrs)*
+        fn #fn_name() {
+            #input
+
+            let report = ::test_executors::bench::Bench::new(stringify!(#fn_name))
+                #iterations
+                #warmup
+                .run(#fn_name);
+            println!("{report}");
+        }
+    };
+    TokenStream::from(output)
+}
+
+/**
+A procedural macro that turns an async function into a [proptest](https://crates.io/crates/proptest) property test.
+
+Each argument is generated from `proptest::arbitrary::any::<T>()`, or from the strategy given with a
+`#[strategy(...)]` attribute on the argument.  Every case runs under `sleep_on`, and failing inputs are shrunk
+as usual.  Requires the `proptest` feature of test_executors.
+
+Optionally, `#[async_proptest(cases = N)]` sets the number of cases.
+
+This macro is not supported on wasm32-unknown-unknown.
+
+# Example
+```rust,ignore
+use test_executors::async_proptest;
+
+#[async_proptest(cases = 64)]
+async fn addition_commutes(a: u32, #[strategy(0..10u32)] b: u32) {
+    assert_eq!(a.wrapping_add(b), b.wrapping_add(a));
+}
+```
+*/
+#[proc_macro_attribute]
+pub fn async_proptest(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut cases: Option<syn::LitInt> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("cases") {
+            cases = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported async_proptest argument"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let mut input = parse_macro_input!(item as ItemFn);
+    let fn_name = input.sig.ident.clone();
+    // Attributes on the original function belong on the generated test
+    let attrs = std::mem::take(&mut input.attrs);
+
+    let mut strategies = Vec::new();
+    let mut arg_names = Vec::new();
+    for (index, arg) in input.sig.inputs.iter_mut().enumerate() {
+        let syn::FnArg::Typed(arg) = arg else {
+            return syn::Error::new_spanned(arg, "async_proptest functions cannot take self").to_compile_error().into();
+        };
+        let position = arg.attrs.iter().position(|a| a.path().is_ident("strategy"));
+        let strategy = match position {
+            Some(position) => {
+                let attr = arg.attrs.remove(position);
+                match attr.parse_args::<syn::Expr>() {
+                    Ok(expr) => quote! { #expr },
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            }
+            None => {
+                let ty = &arg.ty;
+                quote! { ::test_executors::prop::any::<#ty>() }
+            }
+        };
+        strategies.push(strategy);
+        arg_names.push(format_ident!("__arg{}", index));
+    }
+    if arg_names.is_empty() {
+        return syn::Error::new_spanned(&input.sig, "async_proptest functions need at least one argument").to_compile_error().into();
+    }
+
+    let config = match cases {
+        Some(cases) => quote! { ::test_executors::prop::Config::with_cases(#cases) },
+        None => quote! { ::test_executors::prop::Config::default() },
+    };
+
+    let output = quote! {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        #[test]
+        #(#attrs)*
+        fn #fn_name() {
+            #input
+
+            ::test_executors::prop::run(#config, (#(#strategies,)*), |(#(#arg_names,)*)| #fn_name(#(#arg_names),*));
+        }
+    };
+    TokenStream::from(output)
+}
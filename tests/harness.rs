@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+use std::time::Duration;
+use test_executors::harness::{AsyncTest, Executor, Harness};
+
+async fn passes() {
+    assert_eq!(async { 1 }.await, 1);
+}
+
+async fn returns_ok() -> Result<(), std::num::ParseIntError> {
+    let parsed: u8 = async { "42" }.await.parse()?;
+    assert_eq!(parsed, 42);
+    Ok(())
+}
+
+async fn not_send() {
+    let value = std::rc::Rc::new(1);
+    async {}.await;
+    assert_eq!(*value, 1);
+}
+
+async fn hangs() {
+    test_executors::pend_forever::PendForever.await
+}
+
+fn main() {
+    Harness::new()
+        .timeout(Duration::from_secs(10))
+        .test_threads(2)
+        .test(AsyncTest::new("passes", passes))
+        .test(AsyncTest::new("returns_ok", returns_ok))
+        .test(AsyncTest::new("on_spin", passes).executor(Executor::Spin))
+        .test(AsyncTest::new("not_send", not_send))
+        .test(AsyncTest::new("hangs", hangs).timeout(Duration::from_millis(10)).ignored(true))
+        .run()
+}